@@ -0,0 +1,15 @@
+fn main() {
+    // Node.js addons need platform specific linker arguments
+    #[cfg(feature = "node")]
+    napi_build::setup();
+
+    // Regenerate the C header for the capi feature
+    #[cfg(feature = "capi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        cbindgen::generate(&crate_dir)
+            .expect("could not generate C header")
+            .write_to_file(std::path::Path::new(&crate_dir).join("include/zoterocoverage.h"));
+    }
+}
@@ -0,0 +1,60 @@
+#ifndef ZOTEROCOVERAGE_H
+#define ZOTEROCOVERAGE_H
+
+/* Generated by cbindgen from src/capi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define SCHEMA_VERSION 1
+
+/**
+ * The report was written to `out_json`
+ */
+#define ZC_OK 0
+
+/**
+ * A path was NULL or not valid UTF-8
+ */
+#define ZC_INVALID_ARGUMENT 1
+
+/**
+ * Reading or comparing failed, `out_json` holds {"error": "..."}
+ */
+#define ZC_CHECK_FAILED 2
+
+/**
+ * Internal error (panic)
+ */
+#define ZC_INTERNAL_ERROR 3
+
+/**
+ * Compare a document with a CSL-JSON bibliography file and write the report
+ * ({"schema_version": 1, "cited": [...], "uncited": [...], "unknown": [...], "citations": [...]}) as JSON
+ * to `out_json`. Each citation has its key, line and column (from 1) and the byte offsets `start` and
+ * `end` in the UTF-8 text of the document.
+ * Without a bibliography (NULL) the one named in the document's yaml header is used.
+ *
+ * # Safety
+ *
+ * `document_path` and `bibliography_path` must be NULL or point to NUL-terminated strings,
+ * `out_json` must be NULL or point to writable memory for a pointer. The string stored in
+ * `out_json` must be released with `zoterocoverage_free_string`.
+ */
+int zoterocoverage_check_document(const char *document_path,
+                                  const char *bibliography_path,
+                                  char **out_json);
+
+/**
+ * Release a string returned by this library.
+ *
+ * # Safety
+ *
+ * `s` must be NULL or a pointer obtained from this library that has not been freed yet.
+ */
+void zoterocoverage_free_string(char *s);
+
+#endif  /* ZOTEROCOVERAGE_H */
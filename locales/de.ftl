@@ -0,0 +1,152 @@
+# Meldungen des Berichts. Die ids müssen mit en.ftl übereinstimmen;
+# --porcelain, --summary und JSON werden nie übersetzt.
+
+all-cited = Alle Quellen zitiert
+uncited-heading = { $count ->
+    [one] 1 Quelle nicht zitiert:
+   *[other] { $count } Quellen nicht zitiert:
+}
+uncited-with-pdf = { $entry } (PDF)
+suppressed-heading = { $count ->
+    [one] 1 Quelle ausgenommen:
+   *[other] { $count } Quellen ausgenommen:
+}
+severity-warning = Warnung
+severity-error = Fehler
+error-count = { $count ->
+    [one] 1 Befund auf Fehlerstufe
+   *[other] { $count } Befunde auf Fehlerstufe
+}
+strict-skipped = { $count ->
+    [one] Fehler (--strict): 1 fehlerhafter Eintrag der Bibliographie übersprungen
+   *[other] Fehler (--strict): { $count } fehlerhafte Einträge der Bibliographie übersprungen
+}
+strict-suppressed = { $count ->
+    [one] Fehler (--strict): 1 nicht zitierter Eintrag ausgenommen
+   *[other] Fehler (--strict): { $count } nicht zitierte Einträge ausgenommen
+}
+
+finding-uncited = { $key } ist nicht zitiert
+finding-unknown = { $key } ist zitiert, aber nicht in der Bibliographie
+finding-required = { $key } ist Pflicht, aber nicht zitiert
+finding-key-case = { $key } ist zitiert, die Bibliographie hat aber { $matches }
+finding-duplicate-key = { $key } ist der Schlüssel mehrerer Einträge
+finding-conflicting-entry = { $key } unterscheidet sich zwischen { $first } und { $other }, der Eintrag aus { $first } wird verwendet
+finding-missing-fields = Bei { $key } fehlt { $fields }
+finding-stale-key = { $key } wurde in { $current } umbenannt
+finding-key-year = { $key } ist { $year } erschienen
+finding-key-pattern = { $key } passt nicht zu key_pattern
+finding-missing-locator = Zeile { $line }, Spalte { $column }: { $key } ({ $type }) ist ohne Fundstelle zitiert
+finding-missing-locator-label = Zeile { $line }, Spalte { $column }: { $key } ({ $type }) ist ohne { $labels } zitiert
+finding-metadata-invalid = { $error }
+finding-metadata-not-a-path = { $field }: { $value } ist kein Pfad
+finding-metadata-paths = { $field }: Pfad oder Liste von Pfaden erwartet, nicht { $value }
+finding-metadata-csl = { $field }: Pfad eines CSL-Stils erwartet, nicht { $value }
+finding-metadata-boolean = { $field }: true oder false erwartet, nicht { $value }
+finding-metadata-missing-file = { $field }: { $value } existiert nicht
+finding-stale-export = { $bibliography } wurde { $unit ->
+    [minutes] { $amount } Minuten
+    [hours] { $amount } Stunden
+   *[days] { $amount } Tage
+} vor der letzten Änderung von { $document } exportiert, der Export ist womöglich veraltet
+finding-over-cited = { $key } ist { $count }-mal zitiert, öfter als { $max }-mal
+finding-repeated-citation = Zeile { $line }, Spalte { $column }: { $citation } ist in derselben Fußnote oder demselben Satz erneut zitiert
+finding-in-document = { $document }: { $message }
+
+legal-case-law = Rechtsprechung
+legal-case-law-in = Rechtsprechung ({ $jurisdiction })
+legal-commentaries = Kommentare
+legal-literature = Literatur
+
+age-heading = Nicht zitierte Quellen nach Jahr:
+age-undated = ohne Jahr
+age-old-year = { $year }: { $keys } (älter als { $max_age } Jahre)
+age-old-total = { $count ->
+    [one] 1 nicht zitierte Quelle ist
+   *[other] { $count } nicht zitierte Quellen sind
+} älter als { $max_age } Jahre
+
+project-mapping = { $mapping }: { $documents } Dokumente, { $cited } von { $total } Quellen zitiert
+project-no-documents = Warnung: keine Dokumente passen zu { $patterns }
+merge-heading = { $reports } Berichte zusammengeführt: { $cited } von { $total } Quellen zitiert
+workspace-summary = Zusammenfassung:
+workspace-total = gesamt
+on-complete-failed = Warnung: { $command } fehlgeschlagen: { $error }
+
+density-total = Zitierdichte: { $density } Zitate pro 1000 Wörter ({ $citations } Zitate, { $words } Wörter)
+density-section = { $heading }: { $density } ({ $citations } Zitate, { $words } Wörter)
+
+clusters-total = Zitatgruppen: { $clusters }, durchschnittlich { $average } Quellen pro Gruppe
+clusters-footnotes = Fußnoten mit Zitaten: { $footnotes }, davon { $single } mit nur einer Quelle
+clusters-largest = Größte Zitatgruppen:
+clusters-largest-row = Zeile { $line }: { $keys } Quellen
+
+chart-sections = Zitate pro Abschnitt:
+chart-years = Einträge pro Jahr (# zitiert, - nicht zitiert):
+
+library-total = Bibliothek: { $total } Einträge, { $cited } zitiert, { $uncited } nicht zitiert
+library-by-year = Einträge pro Jahr:
+library-by-type = Einträge pro Typ:
+library-by-language = Einträge pro Sprache:
+library-by-jurisdiction = Einträge pro Gerichtsbarkeit (Rechtsprechung):
+library-bucket = { $bucket }: { $total } ({ $cited } zitiert, { $uncited } nicht zitiert)
+
+collection-heading = Abdeckung pro Sammlung:
+collection-row = { $collection }  { $cited }/{ $total } ({ $percent } %)
+collection-none = (keine Sammlung)
+collection-unknown = Die Bibliographie enthält keine Sammlungen; sie sind in Better-BibTeX-JSON-Exporten und Bibliotheken der Zotero Web API enthalten
+
+auth-prompt = Zotero-API-Schlüssel:
+auth-stored = API-Schlüssel im Schlüsselbund des Systems gespeichert
+auth-removed = API-Schlüssel aus dem Schlüsselbund des Systems entfernt
+auth-none = Kein API-Schlüssel gespeichert
+
+rename-done = { $path }: { $count } umbenannt
+rename-not-in-bibliography = Warnung: { $key } nicht in { $bibliography } gefunden
+rename-none = Keine Zitate von { $key } gefunden
+rename-pending = { $count ->
+    [one] 1 Zitat umzubenennen, mit --write erneut ausführen, um es zu ändern
+   *[other] { $count } Zitate umzubenennen, mit --write erneut ausführen, um sie zu ändern
+}
+fix-none = Keine veralteten Schlüssel gefunden
+fix-pending = { $count ->
+    [one] 1 Zitat mit veraltetem Schlüssel zu korrigieren
+   *[other] { $count } Zitate mit veraltetem Schlüssel zu korrigieren
+}
+search-none = { $key } ist nicht zitiert
+diff-same = { $old } und { $new } zitieren dieselben Schlüssel
+
+annotate-written = { $path } geschrieben: { $unknown } unbekannte Schlüssel, { $uncited } nicht zitierte Einträge
+annotate-unknown = unbekannter Schlüssel @{ $key }
+annotate-unknown-similar = unbekannter Schlüssel @{ $key }, vielleicht @{ $similar }?
+annotate-stale = veralteter Schlüssel @{ $key }, umbenannt in @{ $current }
+
+explain-not-cited = Dokument: nicht zitiert
+explain-not-recognized = Dokument: @{ $key } in Zeile { $lines }, der Extraktor { $extractor } erkennt es aber nicht als Schlüssel
+explain-found = Dokument: { $count ->
+    [one] 1 Zitat
+   *[other] { $count } Zitate
+} vom Extraktor { $extractor } gefunden
+explain-found-lines = Dokument: { $count ->
+    [one] 1 Zitat
+   *[other] { $count } Zitate
+} vom Extraktor { $extractor } gefunden, Zeile { $lines }
+explain-excluded = Dokument: @{ $key } in Zeile { $line }, ausgenommen durch { $option }
+explain-locators = Fundstellen: { $locators }
+explain-locator = { $locator } (Zeile { $line })
+explain-no-locator = keine (Zeile { $line })
+explain-entry = Bibliographie: Eintrag in { $bibliography }
+explain-entry-case = Bibliographie: kein Eintrag in { $bibliography }, aber { $similar } unterscheidet sich nur in der Groß- und Kleinschreibung
+explain-no-entry = Bibliographie: kein Eintrag in { $bibliography }
+explain-stale = Aliase: veralteter Schlüssel, umbenannt in { $current } (fix schreibt ihn um)
+explain-current = Aliase: aktueller Schlüssel für { $stale }
+explain-dropped = Skript: von keep_key verworfen
+explain-normalized = Skript: normalisiert zu { $key }
+explain-kept = Skript: unverändert
+explain-suppressed = Ausnahme: allow-uncited
+explain-suppressed-reason = Ausnahme: allow-uncited, { $reason }
+explain-result-cited = Ergebnis: zitiert
+explain-result-suppressed = Ergebnis: ausgenommen, absichtlich nicht zitiert
+explain-result-uncited = Ergebnis: nicht zitiert
+explain-result-unknown = Ergebnis: unbekannt, zitiert, aber nicht in der Bibliographie
+explain-result-missing = Ergebnis: nicht im Bericht
@@ -0,0 +1,122 @@
+# Messages of the human-readable report. Keep ids in sync with de.ftl;
+# the --porcelain, --summary and JSON output are never translated.
+
+all-cited = All sources cited
+uncited-heading = { $count } Sources not cited:
+uncited-with-pdf = { $entry } (PDF)
+suppressed-heading = { $count } Sources suppressed:
+severity-warning = warning
+severity-error = error
+error-count = { $count } error-level findings
+strict-skipped = Error (--strict): { $count } malformed bibliography entries skipped
+strict-suppressed = Error (--strict): { $count } uncited entries suppressed
+
+finding-uncited = { $key } is not cited
+finding-unknown = { $key } is cited but not in the bibliography
+finding-required = { $key } is required but not cited
+finding-key-case = { $key } is cited, but the bibliography has { $matches }
+finding-duplicate-key = { $key } is the key of several entries
+finding-conflicting-entry = { $key } differs between { $first } and { $other }, the entry of { $first } is used
+finding-missing-fields = { $key } has no { $fields }
+finding-stale-key = { $key } was renamed to { $current }
+finding-key-year = { $key } was issued in { $year }
+finding-key-pattern = { $key } does not match key_pattern
+finding-missing-locator = line { $line }, column { $column }: { $key } ({ $type }) is cited without a locator
+finding-missing-locator-label = line { $line }, column { $column }: { $key } ({ $type }) is cited without { $labels }
+finding-metadata-invalid = { $error }
+finding-metadata-not-a-path = { $field }: { $value } is not a path
+finding-metadata-paths = { $field }: expected a path or a list of paths, not { $value }
+finding-metadata-csl = { $field }: expected the path of a CSL style, not { $value }
+finding-metadata-boolean = { $field }: expected true or false, not { $value }
+finding-metadata-missing-file = { $field }: { $value } does not exist
+finding-stale-export = { $bibliography } was exported { $unit ->
+    [minutes] { $amount } minutes
+    [hours] { $amount } hours
+   *[days] { $amount } days
+} before { $document } was last changed, the export may be stale
+finding-over-cited = { $key } is cited { $count } times, more than { $max }
+finding-repeated-citation = line { $line }, column { $column }: { $citation } is cited again in the same footnote or sentence
+finding-in-document = { $document }: { $message }
+
+legal-case-law = Case law
+legal-case-law-in = Case law ({ $jurisdiction })
+legal-commentaries = Commentaries
+legal-literature = Literature
+
+age-heading = Uncited entries by year:
+age-undated = undated
+age-old-year = { $year }: { $keys } (older than { $max_age } years)
+age-old-total = { $count } uncited entries are older than { $max_age } years
+
+project-mapping = { $mapping }: { $documents } documents, { $cited } of { $total } sources cited
+project-no-documents = Warning: no documents match { $patterns }
+merge-heading = Merged { $reports } reports: { $cited } of { $total } sources cited
+workspace-summary = Summary:
+workspace-total = all
+on-complete-failed = Warning: { $command } failed: { $error }
+
+density-total = Citation density: { $density } citations per 1000 words ({ $citations } citations, { $words } words)
+density-section = { $heading }: { $density } ({ $citations } citations, { $words } words)
+
+clusters-total = Citation clusters: { $clusters }, { $average } sources per cluster on average
+clusters-footnotes = Footnotes with citations: { $footnotes }, { $single } of them citing a single source
+clusters-largest = Largest clusters:
+clusters-largest-row = line { $line }: { $keys } sources
+
+chart-sections = Citations per section:
+chart-years = Entries per year (# cited, - uncited):
+
+library-total = Library: { $total } entries, { $cited } cited, { $uncited } uncited
+library-by-year = Entries per year:
+library-by-type = Entries per type:
+library-by-language = Entries per language:
+library-by-jurisdiction = Entries per jurisdiction (legal cases):
+library-bucket = { $bucket }: { $total } ({ $cited } cited, { $uncited } uncited)
+
+collection-heading = Coverage per collection:
+collection-row = { $collection }  { $cited }/{ $total } ({ $percent }%)
+collection-none = (no collection)
+collection-unknown = The bibliography has no collections; they come with Better BibTeX JSON exports and Zotero Web API libraries
+
+auth-prompt = Zotero API key:
+auth-stored = API key stored in the system keyring
+auth-removed = API key removed from the system keyring
+auth-none = No API key stored
+
+rename-done = { $path }: { $count } renamed
+rename-not-in-bibliography = Warning: { $key } not found in { $bibliography }
+rename-none = No citations of { $key } found
+rename-pending = { $count } citations to rename, run again with --write to apply
+fix-none = No stale keys found
+fix-pending = { $count } stale citations to fix
+search-none = { $key } is not cited
+diff-same = { $old } and { $new } cite the same keys
+
+annotate-written = Wrote { $path }: { $unknown } unknown keys, { $uncited } uncited entries
+annotate-unknown = unknown key @{ $key }
+annotate-unknown-similar = unknown key @{ $key }, did you mean @{ $similar }
+annotate-stale = stale key @{ $key }, renamed to @{ $current }
+
+explain-not-cited = document: not cited
+explain-not-recognized = document: @{ $key } on line { $lines }, but the { $extractor } extractor does not recognize it as a key
+explain-found = document: { $count } citations found by the { $extractor } extractor
+explain-found-lines = document: { $count } citations found by the { $extractor } extractor, line { $lines }
+explain-excluded = document: @{ $key } on line { $line }, excluded by { $option }
+explain-locators = locators: { $locators }
+explain-locator = { $locator } (line { $line })
+explain-no-locator = none (line { $line })
+explain-entry = bibliography: entry in { $bibliography }
+explain-entry-case = bibliography: no entry in { $bibliography }, but { $similar } differs only in case
+explain-no-entry = bibliography: no entry in { $bibliography }
+explain-stale = aliases: stale key, renamed to { $current } (fix rewrites it)
+explain-current = aliases: current key for { $stale }
+explain-dropped = script: dropped by keep_key
+explain-normalized = script: normalized to { $key }
+explain-kept = script: kept unchanged
+explain-suppressed = suppression: allow-uncited
+explain-suppressed-reason = suppression: allow-uncited, { $reason }
+explain-result-cited = result: cited
+explain-result-suppressed = result: suppressed, intentionally uncited
+explain-result-uncited = result: uncited
+explain-result-unknown = result: unknown, cited but not in the bibliography
+explain-result-missing = result: not in the report
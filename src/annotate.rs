@@ -0,0 +1,62 @@
+// A copy of the document with the findings written into it, for co-authors who don't run the check
+
+// A remark at a line (1-based) of the document, e.g. unknown key @X, did you mean @Y
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub line: usize,
+    pub message: String,
+}
+
+// The document with every note as an HTML comment <!-- ZC: ... --> at the end of its line, which
+// neither HTML output nor pandoc's other formats show, and the uncited entries (key and reference)
+// in a section at the end. Keys there are code, so they don't turn into citations.
+pub fn annotate(document: &str, notes: &[Note], uncited: &[(String, Option<String>)]) -> String {
+    let mut annotated = String::with_capacity(document.len());
+    for (number, line) in document.split_inclusive('\n').enumerate() {
+        let text = line.trim_end_matches(['\r', '\n']);
+        annotated.push_str(text);
+        for note in notes.iter().filter(|note| note.line == number + 1) {
+            annotated.push_str(&format!(" <!-- ZC: {} -->", note.message));
+        }
+        annotated.push_str(&line[text.len()..]);
+    }
+    if uncited.is_empty() {
+        return annotated;
+    }
+    if !annotated.is_empty() && !annotated.ends_with('\n') {
+        annotated.push('\n');
+    }
+    annotated.push_str("\n<!-- ZC: uncited entries -->\n\n## Uncited entries\n\n");
+    for (key, reference) in uncited {
+        match reference {
+            Some(reference) => annotated.push_str(&format!("- `{}`: {}\n", key, reference)),
+            None => annotated.push_str(&format!("- `{}`\n", key)),
+        }
+    }
+    annotated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Note, annotate};
+    use crate::pandoc::cited_keys;
+
+    #[test]
+    fn test_annotate() {
+        let document = "Text [@Smiht.2009].\r\nMore @A.2020 and @Old.2019\r\nEnd";
+        let notes = [
+            Note { line: 1, message: "unknown key @Smiht.2009, did you mean @Smith.2009".to_string() },
+            Note { line: 2, message: "stale key @Old.2019, renamed to @New.2019".to_string() },
+        ];
+        let uncited = [("Smith.2009".to_string(), Some("Smith, Adam (2009): Essays".to_string())), ("B.2021".to_string(), None)];
+        let annotated = annotate(document, &notes, &uncited);
+        assert_eq!(
+            annotated,
+            "Text [@Smiht.2009]. <!-- ZC: unknown key @Smiht.2009, did you mean @Smith.2009 -->\r\n\
+             More @A.2020 and @Old.2019 <!-- ZC: stale key @Old.2019, renamed to @New.2019 -->\r\nEnd\n\
+             \n<!-- ZC: uncited entries -->\n\n## Uncited entries\n\n- `Smith.2009`: Smith, Adam (2009): Essays\n- `B.2021`\n"
+        );
+        assert_eq!(cited_keys(&annotated), cited_keys(document));
+        assert_eq!(annotate(document, &[], &[]), document);
+    }
+}
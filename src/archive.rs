@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use zip::ZipArchive;
+
+use crate::encoding;
+
+// Zip archives of Zotero exports with files: the bibliography next to a files/ directory holding the
+// attachments, e.g. Exported Items.rdf and files/12/paper.pdf
+pub fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+// The text of the bibliography in the archive: its CSL-JSON or Better BibTeX JSON file, else its
+// MODS XML file, else its Zotero RDF file. Attachments are never read.
+pub fn read_bibliography(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let mut archive = ZipArchive::new(File::open(path)?)
+        .map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+    let names: Vec<String> = archive.file_names()
+        .filter(|name| !name.ends_with('/') && !is_attachment(name))
+        .map(str::to_string)
+        .collect();
+    let with_extension = |extension: &str| -> Vec<&String> {
+        names.iter().filter(|name| has_extension(name, extension)).collect()
+    };
+    let candidates = ["json", "xml", "rdf"].into_iter().map(with_extension).find(|names| !names.is_empty());
+    let name = match candidates.as_deref() {
+        Some([name]) => name.to_string(),
+        None | Some([]) => return Err(format!("no bibliography found in {}", path.display()).into()),
+        Some(several) => {
+            let names: Vec<_> = several.iter().map(|name| name.as_str()).collect();
+            return Err(format!("several bibliographies in {}: {}", path.display(), names.join(", ")).into());
+        }
+    };
+    let mut bytes = Vec::new();
+    archive.by_name(&name)?.read_to_end(&mut bytes)?;
+    encoding::decode(&bytes)
+}
+
+// Zotero puts attachments in files/<item id>/, at the top of the archive or in its single folder
+fn is_attachment(name: &str) -> bool {
+    name.split('/').rev().skip(1).any(|directory| directory == "files")
+}
+
+fn has_extension(name: &str, extension: &str) -> bool {
+    Path::new(name).extension().is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_bibliography;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    fn write_zip(path: &std::path::Path, files: &[(&str, &str)]) {
+        let mut writer = ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, content) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_read_bibliography() {
+        let directory = std::env::temp_dir().join("zoterocoverage-archive");
+        std::fs::create_dir_all(&directory).unwrap();
+        let export = directory.join("export.zip");
+        write_zip(&export, &[
+            ("Exported Items/Exported Items.json", "[{\"id\": \"A.2020\"}]"),
+            ("Exported Items/files/12/data.json", "{}"),
+            ("Exported Items/files/12/paper.pdf", "%PDF"),
+        ]);
+        assert_eq!(read_bibliography(&export).unwrap(), "[{\"id\": \"A.2020\"}]");
+
+        write_zip(&export, &[("Exported Items.rdf", "<rdf:RDF/>"), ("files/3/paper.pdf", "%PDF"), ("files/3/notes.xml", "<x/>")]);
+        assert_eq!(read_bibliography(&export).unwrap(), "<rdf:RDF/>");
+
+        write_zip(&export, &[("a.json", "[]"), ("b.json", "[]")]);
+        assert!(read_bibliography(&export).unwrap_err().to_string().contains("a.json, b.json"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
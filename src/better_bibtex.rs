@@ -0,0 +1,236 @@
+use regex::Regex;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+
+// Better BibTeX JSON exports are an object with config, collections and items,
+// CSL-JSON is an array
+pub fn is_better_bibtex(bibliography: &str) -> bool {
+    bibliography.trim_start().starts_with('{')
+}
+
+// CSL type for a Zotero item type
+pub(crate) fn csl_type(item_type: &str) -> &'static str {
+    match item_type {
+        "book" => "book",
+        "bookSection" => "chapter",
+        "journalArticle" => "article-journal",
+        "magazineArticle" => "article-magazine",
+        "newspaperArticle" => "article-newspaper",
+        "case" => "legal_case",
+        "statute" => "legislation",
+        "thesis" => "thesis",
+        "report" => "report",
+        "webpage" => "webpage",
+        "encyclopediaArticle" => "entry-encyclopedia",
+        "dictionaryEntry" => "entry-dictionary",
+        "conferencePaper" => "paper-conference",
+        "manuscript" => "manuscript",
+        _ => "document",
+    }
+}
+
+// Zotero dates like 2024-03-04, 2024-03 or 2024 as CSL date-parts
+pub(crate) fn csl_date(date: &str) -> Option<Value> {
+    let re = Regex::new(r"^(\d{4})(?:-(\d{1,2})(?:-(\d{1,2}))?)?").unwrap();
+    let caps = re.captures(date.trim())?;
+    let parts: Vec<u32> = caps
+        .iter()
+        .skip(1)
+        .map_while(|part| part.and_then(|p| p.as_str().parse().ok()))
+        // Zotero writes unknown parts as 00
+        .take_while(|&part| part != 0)
+        .collect();
+    Some(json!({"date-parts": [parts]}))
+}
+
+fn csl_names(creators: &[Value], creator_type: &str) -> Vec<Value> {
+    creators
+        .iter()
+        .filter(|c| c["creatorType"].as_str().unwrap_or("author") == creator_type)
+        .map(|c| match c["name"].as_str() {
+            Some(name) => json!({"literal": name}),
+            None => json!({"family": c["lastName"], "given": c["firstName"]}),
+        })
+        .collect()
+}
+
+fn item_to_csl(item: &Value, collections: &HashMap<i64, Vec<String>>) -> Option<Value> {
+    let item_type = item["itemType"].as_str()?;
+    if matches!(item_type, "note" | "attachment" | "annotation") {
+        return None;
+    }
+    let key = item["citationKey"].as_str()?;
+
+    let mut entry = Map::new();
+    entry.insert("id".into(), Value::from(key));
+    entry.insert("citation-key".into(), Value::from(key));
+    entry.insert("type".into(), Value::from(csl_type(item_type)));
+    // Cases and statutes keep their title in caseName / nameOfAct
+    if let Some(title) = ["title", "caseName", "nameOfAct", "subject"].iter().find_map(|f| item[f].as_str()) {
+        entry.insert("title".into(), Value::from(title));
+    }
+    if let Some(creators) = item["creators"].as_array() {
+        for role in ["author", "editor"] {
+            let names = csl_names(creators, role);
+            if !names.is_empty() {
+                entry.insert(role.into(), Value::from(names));
+            }
+        }
+    }
+    if let Some(issued) = item["date"].as_str().and_then(csl_date) {
+        entry.insert("issued".into(), issued);
+    }
+    if let Some(accessed) = item["accessDate"].as_str().and_then(csl_date) {
+        entry.insert("accessed".into(), accessed);
+    }
+    for (from, to) in [("language", "language"), ("DOI", "DOI"), ("url", "URL")] {
+        if let Some(value) = item[from].as_str().filter(|v| !v.is_empty()) {
+            entry.insert(to.into(), Value::from(value));
+        }
+    }
+
+    // Not part of CSL-JSON, kept for sorting, grouping and reading lists
+    if let Some(added) = item["dateAdded"].as_str() {
+        entry.insert("dateAdded".into(), Value::from(added));
+    }
+    if let Some(names) = item["itemID"].as_i64().and_then(|id| collections.get(&id)) {
+        entry.insert("collections".into(), Value::from(names.clone()));
+    }
+    if let Some(attachments) = item["attachments"].as_array().filter(|a| !a.is_empty()) {
+        entry.insert("attachments".into(), Value::from(attachments.clone()));
+    }
+    Some(Value::Object(entry))
+}
+
+// Convert a Better BibTeX JSON export to CSL-JSON; items without citation key are skipped
+pub fn to_csl_json(bibliography: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let export: Value = serde_json::from_str(bibliography)?;
+    let items = export["items"]
+        .as_array()
+        .ok_or("not a Better BibTeX JSON export, items missing")?;
+
+    let mut collections: HashMap<i64, Vec<String>> = HashMap::new();
+    if let Some(all) = export["collections"].as_object() {
+        for collection in all.values() {
+            let name = collection["name"].as_str().unwrap_or_default();
+            for id in collection["items"].as_array().into_iter().flatten().filter_map(Value::as_i64) {
+                collections.entry(id).or_default().push(name.to_string());
+            }
+        }
+    }
+
+    let entries: Vec<Value> = items.iter().filter_map(|item| item_to_csl(item, &collections)).collect();
+    Ok(serde_json::to_string(&entries)?)
+}
+
+// The bibliography as CSL-JSON, converting Better BibTeX JSON exports
+pub fn normalize(bibliography: String) -> Result<String, Box<dyn std::error::Error>> {
+    if is_better_bibtex(&bibliography) {
+        to_csl_json(&bibliography)
+    } else {
+        Ok(bibliography)
+    }
+}
+
+// Fresh exports from Better BibTeX running in Zotero, through its local pull export.
+// Its local API has no call to re-run one particular auto-export, so the file is written here
+// the way an auto-export would, with the Better CSL JSON translator.
+#[cfg(feature = "network")]
+pub mod pull_export {
+    use serde_json::Value;
+    use std::path::Path;
+
+    use crate::network::HttpClient;
+    use crate::safe_write;
+    use crate::source::NetworkOptions;
+
+    const BASE_URL: &str = "http://127.0.0.1:23119/better-bibtex/export/";
+
+    // The export is e.g. collection?/1/Thesis or library?/1/library for the whole personal library
+    pub fn url(export: &str) -> String {
+        format!("{}{}.csljson", BASE_URL, export)
+    }
+
+    // Export to path and return once the file is written
+    pub fn refresh(path: &Path, export: &str, options: &NetworkOptions) -> Result<(), Box<dyn std::error::Error>> {
+        let url = url(export);
+        let mut response = HttpClient::local(options)
+            .get(&url, &[])
+            .map_err(|e| format!("could not export from Better BibTeX, is Zotero running? {}: {}", url, e))?;
+        let body = response.body_mut().read_to_string()?;
+        replace(path, &url, &body)
+    }
+
+    // The file is only replaced by a valid export, the previous one is kept as <path>.bak
+    fn replace(path: &Path, url: &str, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+        serde_json::from_str::<Vec<Value>>(body).map_err(|e| format!("{} returned no CSL JSON: {}", url, e))?;
+        safe_write::write(path, body.as_bytes(), true)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{replace, url};
+
+        #[test]
+        fn test_replace() {
+            assert_eq!(url("collection?/1/Thesis"), "http://127.0.0.1:23119/better-bibtex/export/collection?/1/Thesis.csljson");
+
+            let directory = std::env::temp_dir().join("zoterocoverage-pull-export");
+            std::fs::create_dir_all(&directory).unwrap();
+            let path = directory.join("refs.json");
+            std::fs::write(&path, "[{\"id\": \"A.2020\"}]").unwrap();
+            let error = replace(&path, "http://export", "<html>Not found</html>").unwrap_err();
+            assert!(error.to_string().starts_with("http://export returned no CSL JSON"));
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "[{\"id\": \"A.2020\"}]");
+
+            replace(&path, "http://export", "[{\"id\": \"B.2021\"}]").unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "[{\"id\": \"B.2021\"}]");
+            assert_eq!(std::fs::read_to_string(directory.join("refs.json.bak")).unwrap(), "[{\"id\": \"A.2020\"}]");
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_better_bibtex, to_csl_json};
+    use crate::library::get_library_entries;
+    use serde_json::Value;
+
+    #[test]
+    fn test_better_bibtex_to_csl_json() {
+        let export = r#"{
+  "config": {"id": "36a3b0b5-bad0-4a04-b79b-441c7cef77db", "label": "BetterBibTeX JSON"},
+  "collections": {
+    "K1": {"key": "K1", "name": "Thesis", "items": [1, 2], "collections": [], "parent": false}
+  },
+  "items": [
+    {"itemID": 1, "itemType": "book", "citationKey": "Alexander.2024", "title": "UWG",
+     "creators": [{"creatorType": "author", "firstName": "Christian", "lastName": "Alexander"}],
+     "date": "2024-03", "dateAdded": "2024-03-04T10:00:00Z", "language": "de",
+     "attachments": [{"title": "Full Text PDF", "path": "/papers/uwg.pdf"}]},
+    {"itemID": 2, "itemType": "case", "citationKey": "BGH.2024", "caseName": "Fluggastrechte", "date": "2024-00-00"},
+    {"itemID": 3, "itemType": "note", "note": "todo"}
+  ]
+}"#;
+        assert!(is_better_bibtex(export));
+        assert!(!is_better_bibtex("  [{\"id\": \"a\"}]"));
+
+        let csl = to_csl_json(export).unwrap();
+        let entries: Vec<Value> = serde_json::from_str(&csl).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["type"], "book");
+        assert_eq!(entries[0]["author"][0]["family"], "Alexander");
+        assert_eq!(entries[0]["collections"][0], "Thesis");
+        assert_eq!(entries[0]["attachments"][0]["path"], "/papers/uwg.pdf");
+        assert_eq!(entries[1]["title"], "Fluggastrechte");
+        assert_eq!(entries[1]["issued"]["date-parts"][0][0], 2024);
+
+        let library = get_library_entries(&csl).unwrap();
+        assert_eq!(library[0].year(), Some(2024));
+        assert_eq!(library[0].item_type.as_deref(), Some("book"));
+        assert!(library[0].added_days().is_some());
+
+        assert!(to_csl_json("{\"config\": {}}").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::panic;
+use std::path::Path;
+use std::ptr;
+
+/// The report was written to `out_json`
+pub const ZC_OK: c_int = 0;
+/// A path was NULL or not valid UTF-8
+pub const ZC_INVALID_ARGUMENT: c_int = 1;
+/// Reading or comparing failed, `out_json` holds {"error": "..."}
+pub const ZC_CHECK_FAILED: c_int = 2;
+/// Internal error (panic)
+pub const ZC_INTERNAL_ERROR: c_int = 3;
+
+unsafe fn path_arg<'a>(ptr: *const c_char) -> Option<&'a Path> {
+    if ptr.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok().map(Path::new)
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    // JSON never contains interior NUL bytes, serde_json escapes them
+    CString::new(s).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Compare a document with a CSL-JSON bibliography file and write the report
+/// ({"schema_version": 1, "cited": [...], "uncited": [...], "unknown": [...], "citations": [...]}) as JSON
+/// to `out_json`. Each citation has its key, line and column (from 1) and the byte offsets `start` and
+/// `end` in the UTF-8 text of the document.
+/// Without a bibliography (NULL) the one named in the document's yaml header is used.
+///
+/// # Safety
+///
+/// `document_path` and `bibliography_path` must be NULL or point to NUL-terminated strings,
+/// `out_json` must be NULL or point to writable memory for a pointer. The string stored in
+/// `out_json` must be released with `zoterocoverage_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zoterocoverage_check_document(
+    document_path: *const c_char,
+    bibliography_path: *const c_char,
+    out_json: *mut *mut c_char,
+) -> c_int {
+    let Some(document) = (unsafe { path_arg(document_path) }) else {
+        return ZC_INVALID_ARGUMENT;
+    };
+    let bibliography = unsafe { path_arg(bibliography_path) };
+    if !bibliography_path.is_null() && bibliography.is_none() {
+        return ZC_INVALID_ARGUMENT;
+    }
+
+    let result = panic::catch_unwind(|| {
+        let text = crate::encoding::read_to_string(document).map_err(|e| e.to_string())?;
+        let report = crate::check_document(&text, bibliography).map_err(|e| e.to_string())?;
+        Ok::<_, String>((report, crate::search::occurrences(&text)))
+    });
+    let (code, json) = match result {
+        Ok(Ok((report, citations))) => (ZC_OK, crate::report_json(&report, Some(&citations)).unwrap_or_default()),
+        Ok(Err(e)) => (ZC_CHECK_FAILED, serde_json::json!({ "error": e }).to_string()),
+        Err(_) => return ZC_INTERNAL_ERROR,
+    };
+    if !out_json.is_null() {
+        unsafe { *out_json = into_c_string(json) };
+    }
+    code
+}
+
+/// Release a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be NULL or a pointer obtained from this library that has not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zoterocoverage_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ZC_CHECK_FAILED, ZC_INVALID_ARGUMENT, zoterocoverage_check_document, zoterocoverage_free_string};
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    #[test]
+    fn test_check_document_errors() {
+        let mut out = ptr::null_mut();
+        let code = unsafe { zoterocoverage_check_document(ptr::null(), ptr::null(), &mut out) };
+        assert_eq!(code, ZC_INVALID_ARGUMENT);
+        assert!(out.is_null());
+
+        let missing = CString::new("/nonexistent/document.md").unwrap();
+        let code = unsafe { zoterocoverage_check_document(missing.as_ptr(), ptr::null(), &mut out) };
+        assert_eq!(code, ZC_CHECK_FAILED);
+        let json = unsafe { CStr::from_ptr(out) }.to_str().unwrap().to_string();
+        assert!(json.starts_with("{\"error\":"));
+        unsafe { zoterocoverage_free_string(out) };
+    }
+}
@@ -0,0 +1,129 @@
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::Path;
+
+use crate::encoding;
+
+// Chunks are about this large; a chunk only grows until the next break after this size
+pub const CHUNK_SIZE: usize = 8 << 20;
+
+// Without a blank line within this many chunk sizes, the chunk ends at a line break instead
+const MAX_CHUNKS_WITHOUT_BREAK: usize = 4;
+
+// One piece of a document with the line it starts on (1-based)
+pub struct Chunk<'a> {
+    pub text: Cow<'a, str>,
+    pub first_line: usize,
+}
+
+// A document too large to read into one string, like hundreds of MB of OCRed text, memory-mapped
+// and scanned chunk by chunk. Chunks end at blank lines outside code blocks, so no paragraph,
+// citation cluster or code block is split between two chunks.
+pub struct MappedDocument {
+    map: Mmap,
+}
+
+impl MappedDocument {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = File::open(path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+        // The file must not be truncated while it is mapped, which a document being checked isn't
+        let map = unsafe { Mmap::map(&file)? };
+        if matches!(&map[..], [0xFF, 0xFE, ..] | [0xFE, 0xFF, ..] | [_, 0, ..] | [0, _, ..]) {
+            return Err(format!("{} is UTF-16, chunked scanning needs UTF-8 or Latin-1", path.display()).into());
+        }
+        Ok(MappedDocument { map })
+    }
+
+    pub fn chunks(&self, size: usize) -> Chunks<'_> {
+        let bytes = &self.map[..];
+        Chunks {
+            bytes: bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes),
+            size: size.max(1),
+            line: 1,
+        }
+    }
+}
+
+pub struct Chunks<'a> {
+    bytes: &'a [u8],
+    size: usize,
+    line: usize,
+}
+
+// Where the chunk starting at the beginning of bytes ends: after the first blank line outside a
+// fenced code block once size bytes are passed, else at the last line break within the limit
+fn chunk_end(bytes: &[u8], size: usize) -> usize {
+    let limit = size.saturating_mul(MAX_CHUNKS_WITHOUT_BREAK);
+    if bytes.len() <= size {
+        return bytes.len();
+    }
+    let mut fence: Option<(u8, usize)> = None;
+    let mut start = 0;
+    let mut last_line_break = None;
+    while start < bytes.len() && start <= limit {
+        let end = bytes[start..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |p| start + p + 1);
+        let line = &bytes[start..end];
+        let trimmed = line.trim_ascii();
+        let run = |c: u8| trimmed.iter().take_while(|&&b| b == c).count();
+        match fence {
+            Some((c, length)) if run(c) >= length => fence = None,
+            Some(_) => {}
+            None => fence = [b'`', b'~'].into_iter().find(|&c| run(c) >= 3).map(|c| (c, run(c))),
+        }
+        if end <= limit && line.ends_with(b"\n") {
+            last_line_break = Some(end);
+        }
+        if fence.is_none() && trimmed.is_empty() && end >= size {
+            return end;
+        }
+        start = end;
+    }
+    match last_line_break {
+        Some(end) if end >= size => end,
+        // One enormous line: cut it, at the start of a UTF-8 character
+        _ => (limit..bytes.len()).find(|&i| bytes[i] & 0xC0 != 0x80).unwrap_or(bytes.len()),
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let (chunk, rest) = self.bytes.split_at(chunk_end(self.bytes, self.size));
+        self.bytes = rest;
+        let first_line = self.line;
+        self.line += chunk.iter().filter(|&&b| b == b'\n').count();
+        let text = match std::str::from_utf8(chunk) {
+            Ok(text) => Ok(Cow::Borrowed(text)),
+            Err(_) => encoding::decode(chunk).map(Cow::Owned),
+        };
+        Some(text.map(|text| Chunk { text, first_line }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MappedDocument;
+
+    #[test]
+    fn test_chunks() {
+        let path = std::env::temp_dir().join("zoterocoverage-chunks.md");
+        let document = "[@A.2020;\n@B.2021]\n\n```\n@Code.2020\n\n```\n\nText @C.2022\n\nMüller @D.2023\n";
+        std::fs::write(&path, document.as_bytes()).unwrap();
+        let mapped = MappedDocument::open(&path).unwrap();
+
+        let chunks: Vec<(String, usize)> = mapped
+            .chunks(8)
+            .map(|chunk| chunk.map(|c| (c.text.into_owned(), c.first_line)).unwrap())
+            .collect();
+        let starts: Vec<usize> = chunks.iter().map(|c| c.1).collect();
+        assert_eq!(starts, vec![1, 4, 9, 11]);
+        assert_eq!(chunks[1].0, "```\n@Code.2020\n\n```\n\n");
+        assert_eq!(chunks.iter().map(|c| c.0.as_str()).collect::<String>(), document);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
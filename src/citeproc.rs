@@ -0,0 +1,156 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+// The style of references like in the front matter of a document: csl, a CSL file or URL
+// (pandoc's default is Chicago author-date), and lang for the locale, e.g. de-DE
+#[derive(Deserialize, Debug, Default)]
+pub struct Style {
+    #[serde(default)]
+    pub csl: Option<String>,
+    #[serde(default)]
+    pub lang: Option<String>,
+}
+
+// The references of the entries with these keys, by key, as plain text formatted by pandoc's
+// citeproc. pandoc reads a document citing the keys with nocite, with their entries in its
+// references field.
+pub fn render(bibliography_json: &str, keys: &[String], style: &Style) -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+    if keys.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let input = nocite_document(bibliography_json, keys, style)?;
+    let mut child = Command::new("pandoc")
+        .args(["--citeproc", "--from", "markdown", "--to", "json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run pandoc to format references: {}", e))?;
+    let mut stdin = child.stdin.take().ok_or("pandoc stdin unavailable")?;
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(format!("pandoc --citeproc failed with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(references(&serde_json::from_slice(&output.stdout)?))
+}
+
+// The markdown document for pandoc, its metadata written as JSON, which is YAML too
+fn nocite_document(bibliography_json: &str, keys: &[String], style: &Style) -> Result<String, Box<dyn std::error::Error>> {
+    let entries: Vec<Value> = serde_json::from_str(bibliography_json)?;
+    let references: Vec<Value> = entries
+        .into_iter()
+        .filter_map(|mut entry| {
+            let key = entry.get("citation-key").or(entry.get("id"))?.as_str()?.to_string();
+            if !keys.contains(&key) {
+                return None;
+            }
+            // pandoc looks entries up by id
+            entry["id"] = Value::String(key);
+            Some(entry)
+        })
+        .collect();
+    let nocite: Vec<String> = keys.iter().map(|key| format!("@{{{}}}", key)).collect();
+    let mut metadata = serde_json::json!({ "references": references, "nocite": nocite.join(", ") });
+    if let Some(ref csl) = style.csl {
+        metadata["csl"] = Value::String(csl.clone());
+    }
+    if let Some(ref lang) = style.lang {
+        metadata["lang"] = Value::String(lang.clone());
+    }
+    Ok(format!("---\n{}\n...\n", metadata))
+}
+
+// The entries of the bibliography pandoc appends, divs with the id ref-<key>, from its JSON AST
+fn references(ast: &Value) -> BTreeMap<String, String> {
+    let mut references = BTreeMap::new();
+    collect_references(&ast["blocks"], &mut references);
+    references
+}
+
+fn collect_references(value: &Value, references: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Array(values) => values.iter().for_each(|v| collect_references(v, references)),
+        Value::Object(element) if element.get("t").and_then(Value::as_str) == Some("Div") => {
+            let content = &element["c"];
+            match content[0][0].as_str().and_then(|id| id.strip_prefix("ref-")) {
+                Some(key) => {
+                    let mut text = String::new();
+                    plain_text(&content[1], &mut text);
+                    references.insert(key.to_string(), text.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+                None => collect_references(&content[1], references),
+            }
+        }
+        _ => {}
+    }
+}
+
+// The text of pandoc inlines and blocks, like pandoc's stringify; attributes and link targets are
+// plain strings in the AST, only Str elements hold text
+fn plain_text(value: &Value, text: &mut String) {
+    match value {
+        Value::Array(values) => values.iter().for_each(|v| plain_text(v, text)),
+        Value::Object(element) => match element.get("t").and_then(Value::as_str) {
+            Some("Str") => text.push_str(element["c"].as_str().unwrap_or_default()),
+            Some("Space" | "SoftBreak" | "LineBreak") => text.push(' '),
+            Some("Quoted") => {
+                let (open, close) = match element["c"][0]["t"].as_str() {
+                    Some("SingleQuote") => ('‘', '’'),
+                    _ => ('“', '”'),
+                };
+                text.push(open);
+                plain_text(&element["c"][1], text);
+                text.push(close);
+            }
+            // Blocks of an entry, e.g. the number and the text of numeric styles
+            Some("Para" | "Plain" | "Div") => {
+                plain_text(&element["c"], text);
+                text.push(' ');
+            }
+            _ => plain_text(&element["c"], text),
+        },
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Style, nocite_document, references};
+
+    #[test]
+    fn test_nocite_document() {
+        let bibliography = r#"[{"id": "x1", "citation-key": "Alexander.2024", "title": "UWG"}, {"id": "B", "title": "Other"}]"#;
+        let style = Style { csl: Some("apa.csl".to_string()), lang: None };
+        let document = nocite_document(bibliography, &["Alexander.2024".to_string()], &style).unwrap();
+        let metadata: serde_json::Value = serde_yaml::from_str(document.trim_start_matches("---\n").trim_end_matches("...\n")).unwrap();
+        assert_eq!(metadata["references"], serde_json::json!([{"id": "Alexander.2024", "citation-key": "Alexander.2024", "title": "UWG"}]));
+        assert_eq!(metadata["nocite"], "@{Alexander.2024}");
+        assert_eq!(metadata["csl"], "apa.csl");
+        assert!(metadata.get("lang").is_none());
+    }
+
+    #[test]
+    fn test_references() {
+        // pandoc --citeproc -t json, shortened
+        let ast = serde_json::json!({"pandoc-api-version": [1, 23], "meta": {}, "blocks": [
+            {"t": "Div", "c": [["refs", ["references", "csl-bib-body"], []], [
+                {"t": "Div", "c": [["ref-Alexander.2024", ["csl-entry"], []], [
+                    {"t": "Para", "c": [{"t": "Str", "c": "Alexander,"}, {"t": "Space"}, {"t": "Str", "c": "Christian."},
+                                        {"t": "Space"}, {"t": "Str", "c": "2024."}, {"t": "SoftBreak"},
+                                        {"t": "Emph", "c": [{"t": "Str", "c": "UWG"}]}, {"t": "Str", "c": "."}]}]]},
+                {"t": "Div", "c": [["ref-BGH.2024", ["csl-entry"], []], [
+                    {"t": "Div", "c": [["", ["csl-left-margin"], []], [{"t": "Plain", "c": [{"t": "Str", "c": "[1]"}]}]]},
+                    {"t": "Div", "c": [["", ["csl-right-inline"], []], [{"t": "Plain", "c": [
+                        {"t": "Quoted", "c": [{"t": "DoubleQuote"}, [{"t": "Str", "c": "Urteil"}]]}, {"t": "Space"},
+                        {"t": "Link", "c": [["", [], []], [{"t": "Str", "c": "online"}], ["https://example.org", ""]]}]}]]}]]}]]}]});
+        let references = references(&ast);
+        assert_eq!(references["Alexander.2024"], "Alexander, Christian. 2024. UWG.");
+        assert_eq!(references["BGH.2024"], "[1] “Urteil” online");
+    }
+}
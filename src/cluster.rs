@@ -0,0 +1,134 @@
+use regex::Regex;
+use std::fmt;
+
+use crate::pandoc::{Citation, CitationMode, parse_citations, repeated_citations};
+use crate::search::{Location, location};
+
+// A locator like p. 33 or Rn. 15 f.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locator {
+    pub label: String,
+    pub value: String,
+}
+
+impl fmt::Display for Locator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.label, self.value)
+    }
+}
+
+// One citation of a key, with its locator if it has one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cite {
+    pub key: String,
+    pub locator: Option<Locator>,
+    pub location: Location,
+}
+
+// Locator labels pandoc understands in English documents
+const LABELS: [&str; 20] = [
+    "p.", "pp.", "page", "pages", "chap.", "chapter", "sec.", "section", "para.", "paras.",
+    "§", "§§", "no.", "nos.", "vol.", "vols.", "fig.", "n.", "nn.", "col.",
+];
+
+// German legal locators: Randnummer, Seite, Artikel, Absatz
+const LEGAL_LABELS: [&str; 6] = ["Rn.", "Rdnr.", "Rz.", "S.", "Art.", "Abs."];
+
+fn locator_regex(legal: bool) -> Regex {
+    let mut labels: Vec<&str> = LABELS.to_vec();
+    if legal {
+        labels.extend(LEGAL_LABELS);
+    }
+    // Longest first, so pp. is not read as p.
+    labels.sort_by_key(|label| std::cmp::Reverse(label.len()));
+    let labels: Vec<String> = labels.into_iter().map(regex::escape).collect();
+    Regex::new(&format!(
+        r"^\s*,?\s*(?:(?<label>{})\s*)?(?<value>\d\w*(?:\s*[-–]\s*\d\w*)?(?:\s*ff?\.)?)",
+        labels.join("|")
+    ))
+    .unwrap()
+}
+
+// The locator at the start of the text following a key; a bare number is a page like in pandoc
+fn parse_locator(suffix: &str, re: &Regex) -> Option<Locator> {
+    let caps = re.captures(suffix)?;
+    Some(Locator {
+        label: caps.name("label").map_or("p.", |label| label.as_str()).to_string(),
+        value: caps["value"].to_string(),
+    })
+}
+
+// All citations in document order with their locators. Inside clusters the locator follows the key,
+// otherwise it is given in brackets (@A.2020 [p. 3]); in legal mode, Rn. and friends also
+// directly follow keys in the text, as usual in footnotes (vgl. @BGH.2024 Rn. 45).
+pub fn parse_cites(document: &str, legal: bool) -> Vec<Cite> {
+    to_cites(document, parse_citations(document), legal)
+}
+
+// Citations of a key already cited in the same footnote or sentence, see pandoc::repeated_citations
+pub fn repeated_cites(document: &str, legal: bool) -> Vec<Cite> {
+    to_cites(document, repeated_citations(document), legal)
+}
+
+fn to_cites(document: &str, citations: Vec<Citation>, legal: bool) -> Vec<Cite> {
+    let locator = locator_regex(legal);
+    citations
+        .into_iter()
+        .map(|citation| {
+            let suffix = match citation.mode {
+                CitationMode::AuthorInText if citation.suffix.is_empty() && legal => {
+                    document[citation.span.end..].split(['\n', ';', ']', '@']).next()
+                }
+                _ => Some(citation.suffix.as_str()),
+            };
+            Cite {
+                locator: suffix.and_then(|suffix| parse_locator(suffix, &locator)),
+                location: location(document, citation.span.start),
+                key: citation.key,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cites;
+
+    #[test]
+    fn test_parse_cites() {
+        let document = "See [@Smith.2009, pp. 33-35; vgl. @BGH.2024 Rn. 45; @Jones.2010, 12]\n\
+                        and @Doe.2011 [chap. 2], @BGH.2010c Rn. 36^[@VGHBaden-Württemberg.2024 Rn. 3 f.]";
+        let locators = |legal| -> Vec<(String, Option<String>)> {
+            parse_cites(document, legal)
+                .into_iter()
+                .map(|cite| (cite.key, cite.locator.map(|l| l.to_string())))
+                .collect()
+        };
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(
+            locators(true),
+            vec![
+                ("Smith.2009".into(), some("pp. 33-35")),
+                ("BGH.2024".into(), some("Rn. 45")),
+                ("Jones.2010".into(), some("p. 12")),
+                ("Doe.2011".into(), some("chap. 2")),
+                ("BGH.2010c".into(), some("Rn. 36")),
+                ("VGHBaden-Württemberg.2024".into(), some("Rn. 3 f.")),
+            ]
+        );
+
+        let plain = locators(false);
+        assert_eq!(plain[1], ("BGH.2024".into(), None));
+        assert_eq!(plain[4], ("BGH.2010c".into(), None));
+        assert_eq!(plain[5], ("VGHBaden-Württemberg.2024".into(), None));
+
+        let cites = parse_cites(document, true);
+        assert_eq!((cites[3].location.line, cites[3].location.column), (2, 5));
+
+        // Hard-wrapped at 80 columns, the locator on the next line
+        let wrapped = parse_cites("Text [vgl. @BGH.2024 Rn.\n45; @Smith.2009,\npp. 33-35]", true);
+        let locators: Vec<Option<String>> = wrapped.iter().map(|cite| cite.locator.as_ref().map(|l| l.to_string())).collect();
+        assert_eq!(locators, vec![some("Rn. 45"), some("pp. 33-35")]);
+        assert_eq!(wrapped[1].location.line, 2);
+    }
+}
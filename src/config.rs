@@ -0,0 +1,253 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::mods;
+use crate::project::Mapping;
+use crate::rules::Rules;
+use crate::source::BibliographySource;
+use crate::syntax::Syntax;
+
+// Name of the project-level config file, looked up in the working directory
+const PROJECT_CONFIG: &str = "zoterocoverage.toml";
+
+// Settings read from zoterocoverage.toml (working directory) or the user config directory
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    // Profile used when --profile is not given
+    #[serde(default)]
+    pub default_profile: Option<String>,
+
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+
+    // Project manifest: per-chapter libraries, checked with the project subcommand
+    #[serde(default)]
+    pub mappings: Vec<Mapping>,
+
+    // Independent projects of a workspace, e.g. several articles in one repository, each with its
+    // own documents and library; checked with check <name> or check --all
+    #[serde(default)]
+    pub projects: BTreeMap<String, Mapping>,
+
+    // Renamed keys, stale key = "current key", e.g. after changing the Better BibTeX key format; used by fix
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+
+    // Entries intentionally left uncited, key = "reason"; like allow-uncited comments in the document
+    #[serde(default)]
+    pub allow_uncited: BTreeMap<String, String>,
+
+    // Severity of the named checks, e.g. uncited = "error"
+    #[serde(default)]
+    pub rules: Rules,
+
+    // Rhai script with hooks (scripting feature), relative to the config file
+    #[serde(default)]
+    pub script: Option<PathBuf>,
+
+    // German legal writing, like --legal
+    #[serde(default)]
+    pub legal: bool,
+
+    // Item types whose citations need a locator, CSL type = accepted labels, e.g. legal_case = ["Rn."];
+    // an empty list accepts any locator
+    #[serde(default)]
+    pub require_locator: BTreeMap<String, Vec<String>>,
+
+    // Better BibTeX pull export written by --refresh-export, e.g. collection?/1/Thesis; required for it
+    #[serde(default)]
+    pub pull_export: Option<String>,
+
+    // Minutes the document may be newer than the bibliography file before stale-export warns; default 60
+    #[serde(default)]
+    pub stale_export_minutes: Option<u64>,
+
+    // Field of MODS XML bibliographies holding the citation key, e.g. identifier[citekey] for
+    // <identifier type="citekey">; default recordIdentifier
+    #[serde(default)]
+    pub mods_key: Option<String>,
+
+    // Regex every bibliography key should match, e.g. '[A-Z][\w-]*\.\d{4}[a-z]?' for Name.2024 keys;
+    // checked by key-pattern
+    #[serde(default)]
+    pub key_pattern: Option<String>,
+
+    // Citation syntax of further file extensions, e.g. mdx = "markdown"; they override the built-in ones
+    #[serde(default)]
+    pub extensions: BTreeMap<String, Syntax>,
+}
+
+// A named set of credentials and library, e.g. personal library vs group library
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    // Bibliography source, same syntax as --zotero-lib
+    #[serde(default)]
+    pub library: Option<BibliographySource>,
+
+    // Plaintext API key; prefer auth login --profile <name>, which uses the keyring
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let project = PathBuf::from(PROJECT_CONFIG);
+    if project.exists() {
+        return Some(project);
+    }
+    let user = dirs::config_dir()?.join("zoterocoverage").join("config.toml");
+    user.exists().then_some(user)
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Config, Box<dyn std::error::Error>> {
+        Ok(toml::from_str(content)?)
+    }
+
+    // Load the given config file, or the default one if it exists
+    pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn std::error::Error>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_config_path() {
+                Some(path) => path,
+                None => return Ok(Config::default()),
+            },
+        };
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("could not read config {}: {}", path.display(), e))?;
+        let mut config =
+            Config::parse(&content).map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+        if let (Some(script), Some(dir)) = (config.script.as_mut(), path.parent()) {
+            *script = dir.join(&*script);
+        }
+        Ok(config)
+    }
+
+    // This config with the named project as its only mapping, labeled with the name
+    pub fn project(&self, name: &str) -> Result<Config, String> {
+        let Some(project) = self.projects.get(name) else {
+            return Err(format!(
+                "unknown project {}, configured projects: {}",
+                name,
+                self.projects.keys().cloned().collect::<Vec<_>>().join(", ")
+            ));
+        };
+        let mapping = Mapping { name: Some(name.to_string()), ..project.clone() };
+        Ok(Config { mappings: vec![mapping], projects: BTreeMap::new(), ..self.clone() })
+    }
+
+    pub fn stale_export_window(&self) -> Duration {
+        Duration::from_secs(self.stale_export_minutes.unwrap_or(60) * 60)
+    }
+
+    pub fn mods_key(&self) -> &str {
+        self.mods_key.as_deref().unwrap_or(mods::DEFAULT_KEY_FIELD)
+    }
+
+    // key_pattern matching whole keys; None if not configured
+    pub fn key_pattern(&self) -> Result<Option<Regex>, String> {
+        self.key_pattern
+            .as_deref()
+            .map(|pattern| Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| format!("invalid key_pattern: {}", e)))
+            .transpose()
+    }
+
+    // The profile selected by name, or the default profile; an unknown name is an error
+    pub fn profile(&self, name: Option<&str>) -> Result<Option<(&str, &Profile)>, String> {
+        let Some(name) = name.or(self.default_profile.as_deref()) else {
+            return Ok(None);
+        };
+        match self.profiles.get_key_value(name) {
+            Some((name, profile)) => Ok(Some((name.as_str(), profile))),
+            None => Err(format!(
+                "unknown profile {}, configured profiles: {}",
+                name,
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::source::BibliographySource;
+
+    #[test]
+    fn test_config_profiles() {
+        let config = Config::parse(
+            r#"
+default_profile = "personal"
+
+[profiles.personal]
+library = "refs/personal.json"
+
+[profiles.work]
+library = "~/work/refs.json"
+api_key = "secret"
+"#,
+        )
+        .unwrap();
+
+        let (name, personal) = config.profile(None).unwrap().unwrap();
+        assert_eq!(name, "personal");
+        assert_eq!(personal.library, Some("refs/personal.json".parse().unwrap()));
+
+        let (_, work) = config.profile(Some("work")).unwrap().unwrap();
+        assert_eq!(work.api_key.as_deref(), Some("secret"));
+        assert!(matches!(work.library, Some(BibliographySource::File(_))));
+
+        assert!(config.profile(Some("university")).is_err());
+        assert_eq!(config.script, None);
+        assert!(Config::parse("script = \"hooks.rhai\"").unwrap().script.is_some());
+        let aliases = Config::parse("[aliases]\n\"smith2009\" = \"Smith.2009\"").unwrap().aliases;
+        assert_eq!(aliases["smith2009"], "Smith.2009");
+        let rules = Config::parse("[rules]\nuncited = \"error\"").unwrap().rules;
+        assert_eq!(rules.severity(crate::rules::Rule::Uncited), crate::rules::Severity::Error);
+    }
+
+    #[test]
+    fn test_config_projects() {
+        let config = Config::parse(
+            r#"
+legal = true
+
+[projects.article-a]
+documents = ["a/*.md"]
+library = "a/refs.json"
+
+[projects.article-b]
+documents = ["b/*.md"]
+library = "b/refs.json"
+"#,
+        )
+        .unwrap();
+        let project = config.project("article-b").unwrap();
+        assert_eq!(project.mappings.len(), 1);
+        assert_eq!(project.mappings[0].label(), "article-b");
+        assert_eq!(project.mappings[0].documents, vec!["b/*.md"]);
+        assert!(project.legal && project.projects.is_empty());
+        assert!(config.project("article-c").is_err());
+    }
+
+    #[test]
+    fn test_config_empty() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.profile(None).unwrap(), None);
+        assert!(Config::parse("[profiles.x]\nlibrary = \"zotero:nope\"").is_err());
+        assert!(Config::parse("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_config_key_pattern() {
+        let pattern = Config::parse(r#"key_pattern = '[A-Z][\w-]*\.\d{4}[a-z]?'"#).unwrap().key_pattern().unwrap().unwrap();
+        assert!(pattern.is_match("Alexander.2024a") && !pattern.is_match("see Alexander.2024"));
+        assert!(Config::parse("").unwrap().key_pattern().unwrap().is_none());
+        assert!(Config::parse("key_pattern = '(unclosed'").unwrap().key_pattern().is_err());
+    }
+}
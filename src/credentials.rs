@@ -0,0 +1,32 @@
+use keyring::Entry;
+
+// Keyring service under which credentials are stored
+const SERVICE: &str = "zoterocoverage";
+const API_KEY_USER: &str = "zotero-api-key";
+
+// Keys are stored per profile, without a profile under the plain user name
+fn api_key_entry(profile: Option<&str>) -> keyring::Result<Entry> {
+    match profile {
+        Some(profile) => Entry::new(SERVICE, &format!("{}:{}", API_KEY_USER, profile)),
+        None => Entry::new(SERVICE, API_KEY_USER),
+    }
+}
+
+pub fn store_api_key(profile: Option<&str>, api_key: &str) -> Result<(), Box<dyn std::error::Error>> {
+    api_key_entry(profile)?.set_password(api_key)?;
+    Ok(())
+}
+
+// The stored API key; None if there is none or the keyring is unavailable
+pub fn load_api_key(profile: Option<&str>) -> Option<String> {
+    api_key_entry(profile).ok()?.get_password().ok()
+}
+
+// Returns false if no key was stored
+pub fn delete_api_key(profile: Option<&str>) -> Result<bool, Box<dyn std::error::Error>> {
+    match api_key_entry(profile)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
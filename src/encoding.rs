@@ -0,0 +1,106 @@
+use std::path::Path;
+
+// The encoding decode found, so a changed file can be written back the same way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8 { bom: bool },
+    Utf16Le { bom: bool },
+    Utf16Be { bom: bool },
+    Latin1,
+}
+
+// Decode a document or bibliography: UTF-8 (with or without BOM), UTF-16 with BOM or starting
+// with an ASCII character, and anything else that isn't valid UTF-8 as Latin-1
+pub fn decode(bytes: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(decode_with_encoding(bytes)?.0)
+}
+
+// The text and the encoding it was found in
+pub fn decode_with_encoding(bytes: &[u8]) -> Result<(String, Encoding), Box<dyn std::error::Error>> {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return Ok((String::from_utf8(rest.to_vec())?, Encoding::Utf8 { bom: true }));
+    }
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => return Ok((decode_utf16(rest, u16::from_le_bytes)?, Encoding::Utf16Le { bom: true })),
+        [0xFE, 0xFF, rest @ ..] => return Ok((decode_utf16(rest, u16::from_be_bytes)?, Encoding::Utf16Be { bom: true })),
+        // Text never starts with NUL, so this is the high byte of an ASCII character
+        [c, 0, ..] if *c != 0 => return Ok((decode_utf16(bytes, u16::from_le_bytes)?, Encoding::Utf16Le { bom: false })),
+        [0, c, ..] if *c != 0 => return Ok((decode_utf16(bytes, u16::from_be_bytes)?, Encoding::Utf16Be { bom: false })),
+        _ => {}
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok((text.to_string(), Encoding::Utf8 { bom: false })),
+        // Latin-1 maps every byte to the code point of the same value
+        Err(_) => Ok((bytes.iter().map(|&b| char::from(b)).collect(), Encoding::Latin1)),
+    }
+}
+
+// The text in this encoding, with the BOM if the original had one. Latin-1 fails for characters
+// beyond U+00FF rather than writing a file that no longer reads back the same.
+pub fn encode(text: &str, encoding: Encoding) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let bom = |bom: bool, mark: &[u8]| if bom { mark.to_vec() } else { Vec::new() };
+    Ok(match encoding {
+        Encoding::Utf8 { bom: with_bom } => [bom(with_bom, &[0xEF, 0xBB, 0xBF]), text.as_bytes().to_vec()].concat(),
+        Encoding::Utf16Le { bom: with_bom } => {
+            bom(with_bom, &[0xFF, 0xFE]).into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect()
+        }
+        Encoding::Utf16Be { bom: with_bom } => {
+            bom(with_bom, &[0xFE, 0xFF]).into_iter().chain(text.encode_utf16().flat_map(u16::to_be_bytes)).collect()
+        }
+        Encoding::Latin1 => text
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| format!("cannot write {} in Latin-1, convert the file to UTF-8 first", c)))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Result<String, Box<dyn std::error::Error>> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err("invalid UTF-16: odd number of bytes".into());
+    }
+    let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    Ok(char::decode_utf16(units).collect::<Result<String, _>>()?)
+}
+
+// Read a file in any of the encodings supported by decode
+pub fn read_to_string(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    decode(&std::fs::read(path)?)
+}
+
+// Read a file and the encoding to write it back with
+pub fn read_with_encoding(path: &Path) -> Result<(String, Encoding), Box<dyn std::error::Error>> {
+    decode_with_encoding(&std::fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoding, decode, decode_with_encoding, encode};
+
+    #[test]
+    fn test_decode() {
+        let text = "[@Müller.2024]";
+        assert_eq!(decode(text.as_bytes()).unwrap(), text);
+        assert_eq!(decode(&[b"\xEF\xBB\xBF", text.as_bytes()].concat()).unwrap(), text);
+
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode(&[&[0xFF, 0xFE], &le[..]].concat()).unwrap(), text);
+        assert_eq!(decode(&[&[0xFE, 0xFF], &be[..]].concat()).unwrap(), text);
+        assert_eq!(decode(&le).unwrap(), text);
+
+        // ü in Latin-1
+        assert_eq!(decode(b"[@M\xFCller.2024]").unwrap(), text);
+        assert!(decode(&[0xFF, 0xFE, b'a']).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let originals: [&[u8]; 4] = [b"\xEF\xBB\xBF[@A.2024]", b"[@M\xFCller.2024]", b"\xFF\xFE[\0@\0", b"[\0@\0"];
+        for original in originals {
+            let (text, encoding) = decode_with_encoding(original).unwrap();
+            assert_eq!(encode(&text, encoding).unwrap(), original);
+        }
+        assert_eq!(decode_with_encoding(b"[@M\xFCller.2024]").unwrap().1, Encoding::Latin1);
+        assert!(encode("[@Łukasz.2024]", Encoding::Latin1).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use std::ops::Range;
+
+use crate::front_matter;
+use crate::pandoc::matching_bracket;
+
+// Parts of a document whose citations don't count towards coverage. They are blanked out before
+// extraction, keeping byte offsets and line numbers, so any extractor skips them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Exclusions {
+    // Block quotes (> ...), e.g. a quoted footnote apparatus of another author
+    pub blockquotes: bool,
+    // Image alt text, which pandoc turns into the figure caption, and figure attributes
+    pub figures: bool,
+}
+
+impl Exclusions {
+    pub fn apply(&self, document: String) -> String {
+        let mut ranges = Vec::new();
+        if self.blockquotes {
+            ranges.extend(blockquote_ranges(&document));
+        }
+        if self.figures {
+            ranges.extend(figure_ranges(&document));
+        }
+        blank(document, ranges)
+    }
+
+    // The option of the exclusion blanking out the text at offset, e.g. to explain a missing citation
+    pub fn excluded_by(&self, document: &str, offset: usize) -> Option<&'static str> {
+        let contains = |ranges: Vec<Range<usize>>| ranges.iter().any(|range| range.contains(&offset));
+        if self.blockquotes && contains(blockquote_ranges(document)) {
+            Some("--exclude-blockquotes")
+        } else if self.figures && contains(figure_ranges(document)) {
+            Some("--exclude-figures")
+        } else {
+            None
+        }
+    }
+}
+
+// The document with its YAML metadata blocks blanked out, for extracting the citations of the
+// text alone
+pub fn without_metadata(document: &str) -> String {
+    let blocks = front_matter::block_lines(document);
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    for (number, line) in document.split_inclusive('\n').enumerate() {
+        if blocks.iter().any(|block| block.contains(&number)) {
+            ranges.push(offset..offset + line.len());
+        }
+        offset += line.len();
+    }
+    blank(document.to_string(), ranges)
+}
+
+fn blank(document: String, ranges: Vec<Range<usize>>) -> String {
+    if ranges.is_empty() {
+        return document;
+    }
+    let mut bytes = document.into_bytes();
+    for range in ranges {
+        for byte in &mut bytes[range] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    }
+    // Ranges start and end at line breaks or ASCII brackets, so only whole characters were replaced
+    String::from_utf8(bytes).unwrap()
+}
+
+// Block quotes up to the blank line ending them, including lazy continuation lines without >;
+// a > in a fenced code block starts none
+fn blockquote_ranges(document: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut quote: Option<usize> = None;
+    let mut fence: Option<(char, usize)> = None;
+    let mut offset = 0;
+    for line in document.split_inclusive('\n') {
+        let trimmed = line.trim_start_matches(' ');
+        let indented = line.len() - trimmed.len() >= 4;
+        let run = |c: char| trimmed.chars().take_while(|&b| b == c).count();
+        match (quote, fence) {
+            (Some(start), _) if line.trim().is_empty() => {
+                ranges.push(start..offset);
+                quote = None;
+            }
+            (Some(_), _) => {}
+            (None, Some((c, length))) => {
+                if !indented && run(c) >= length {
+                    fence = None;
+                }
+            }
+            (None, None) if !indented && trimmed.starts_with('>') => quote = Some(offset),
+            (None, None) => {
+                if !indented {
+                    fence = ['`', '~'].into_iter().find(|&c| run(c) >= 3).map(|c| (c, run(c)));
+                }
+            }
+        }
+        offset += line.len();
+    }
+    if let Some(start) = quote {
+        ranges.push(start..offset);
+    }
+    ranges
+}
+
+// Images ![alt](source){attributes} or ![alt][reference], and the {attributes} of fenced divs
+// (::: {#fig-x fig-cap="..."})
+fn figure_ranges(document: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut from = 0;
+    while let Some(bang) = document[from..].find("![").map(|p| p + from) {
+        let Some(mut end) = matching_bracket(document, bang + 1).map(|close| close + 1) else {
+            from = bang + 2;
+            continue;
+        };
+        if document[end..].starts_with(['(', '['])
+            && let Some(close) = matching_bracket(document, end)
+        {
+            end = close + 1;
+        }
+        if document[end..].starts_with('{')
+            && let Some(close) = matching_bracket(document, end)
+        {
+            end = close + 1;
+        }
+        ranges.push(bang..end);
+        from = end;
+    }
+
+    let mut offset = 0;
+    for line in document.split_inclusive('\n') {
+        if line.trim_start().starts_with(":::")
+            && let Some(open) = line.find('{')
+            && let Some(close) = line[open..].find('}')
+        {
+            ranges.push(offset + open..offset + open + close + 1);
+        }
+        offset += line.len();
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exclusions, without_metadata};
+    use crate::pandoc::cited_keys;
+
+    #[test]
+    fn test_blockquotes() {
+        let document = "Own @A.2020.\n\n> Quoted text.[@B.2021]\n> > nested @C.2022\nlazy @D.2023\n\n\
+                        ```\n> @E.2024 in code\n```\n\n   > Über @F.2025\n\nOwn again @G.2026\n";
+        let excluded = Exclusions { blockquotes: true, ..Default::default() }.apply(document.to_string());
+        assert_eq!(excluded.len(), document.len());
+        assert_eq!(excluded.lines().count(), document.lines().count());
+        assert_eq!(cited_keys(&excluded), vec!["A.2020", "G.2026"]);
+        assert_eq!(Exclusions::default().apply(document.to_string()), document);
+        let exclusions = Exclusions { blockquotes: true, figures: true };
+        assert_eq!(exclusions.excluded_by(document, document.find("@B.2021").unwrap()), Some("--exclude-blockquotes"));
+        assert_eq!(exclusions.excluded_by(document, document.find("@G.2026").unwrap()), None);
+    }
+
+    #[test]
+    fn test_figures() {
+        let document = "Text @A.2020.\n\n![Map after @B.2021 [p. 3]](map.png){#fig:map width=50%}\n\n\
+                        ![Chart from\n@C.2022][chart] and @D.2023\n\n::: {#fig-x fig-cap=\"Per @E.2024\"}\nCaption @F.2025\n:::\n";
+        let excluded = Exclusions { figures: true, ..Default::default() }.apply(document.to_string());
+        assert_eq!(excluded.len(), document.len());
+        assert_eq!(cited_keys(&excluded), vec!["A.2020", "D.2023", "F.2025"]);
+        let exclusions = Exclusions { figures: true, ..Default::default() };
+        assert_eq!(exclusions.excluded_by(document, document.find("@E.2024").unwrap()), Some("--exclude-figures"));
+        assert_eq!(exclusions.excluded_by(document, document.find("@D.2023").unwrap()), None);
+    }
+
+    #[test]
+    fn test_without_metadata() {
+        let document = "---\nabstract: On @A.2020\n---\nText @B.2021\n";
+        let text = without_metadata(document);
+        assert_eq!(text.len(), document.len());
+        assert_eq!(cited_keys(&text), vec!["B.2021"]);
+    }
+}
@@ -0,0 +1,281 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::get_citations_document;
+use crate::pandoc::cited_keys;
+use crate::syntax::Syntax;
+
+// Finds the citation keys cited in a document
+pub trait Extractor {
+    // Short name used in verbose output
+    fn name(&self) -> &str;
+
+    // All cited keys in document order, including repetitions
+    fn extract(&self, document: &str) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+}
+
+// The built-in extractor, parsing citations like pandoc does (clusters, in-text and braced keys;
+// nothing in code or comments)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PandocExtractor;
+
+impl Extractor for PandocExtractor {
+    fn name(&self) -> &str {
+        "pandoc"
+    }
+
+    fn extract(&self, document: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(cited_keys(document))
+    }
+}
+
+// Finds @Name.2024 keys anywhere in the text, regardless of the citation syntax
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RegexExtractor;
+
+impl Extractor for RegexExtractor {
+    fn name(&self) -> &str {
+        "regex"
+    }
+
+    fn extract(&self, document: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(get_citations_document(document)?
+            .into_iter()
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+// The built-in extractor for the citation syntax of a markup; markdown is parsed like pandoc does
+impl Extractor for Syntax {
+    fn name(&self) -> &str {
+        match self {
+            Syntax::Markdown => "pandoc",
+            syntax => Syntax::name(*syntax),
+        }
+    }
+
+    fn extract(&self, document: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(self.cited_keys(document))
+    }
+}
+
+// Runs an external command through the shell, e.g. 'mytool --keys'.
+// The document is piped to its stdin; every non-empty output line is either a key
+// or a JSON object with a key field, e.g. {"key": "Smith2009a", "line": 12, "column": 4}
+#[derive(Debug, Clone)]
+pub struct CommandExtractor {
+    command: String,
+}
+
+impl CommandExtractor {
+    pub fn new(command: &str) -> Self {
+        CommandExtractor {
+            command: command.to_string(),
+        }
+    }
+
+}
+
+// A command line run through the shell, sh -c or cmd /C on Windows
+pub fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+// A key from one output line of an extractor command
+fn parse_key_line(line: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if !line.starts_with('{') {
+        return Ok(line.to_string());
+    }
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    match value.get("key").and_then(|key| key.as_str()) {
+        Some(key) => Ok(key.to_string()),
+        None => Err(format!("extractor output without key: {}", line).into()),
+    }
+}
+
+impl Extractor for CommandExtractor {
+    fn name(&self) -> &str {
+        &self.command
+    }
+
+    fn extract(&self, document: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut child = shell(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not run {}: {}", self.command, e))?;
+
+        // Write from a separate thread so a command streaming its output can't deadlock us
+        let mut stdin = child.stdin.take().ok_or("extractor stdin unavailable")?;
+        let input = document.to_string();
+        let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+        let output = child.wait_with_output()?;
+        // Commands may exit without reading all of the document
+        let _ = writer.join();
+        if !output.status.success() {
+            return Err(format!("{} failed with {}", self.command, output.status).into());
+        }
+
+        String::from_utf8(output.stdout)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(parse_key_line)
+            .collect()
+    }
+}
+
+// Third-party extractors compiled to WebAssembly, run in wasmtime.
+//
+// A plugin is a core wasm module without imports that exports
+//   memory                              its linear memory
+//   alloc(len: i32) -> i32              reserve len bytes for the document
+//   extract(ptr: i32, len: i32) -> i64  scan the UTF-8 document at ptr
+// extract returns (out_ptr << 32) | out_len, pointing at UTF-8 text with one key per line.
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm_plugin {
+    use std::path::Path;
+    use wasmtime::{Engine, Instance, Module, Store};
+
+    use super::Extractor;
+
+    pub struct WasmExtractor {
+        name: String,
+        engine: Engine,
+        module: Module,
+    }
+
+    impl WasmExtractor {
+        pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+            let bytes = std::fs::read(path)
+                .map_err(|e| format!("could not read plugin {}: {}", path.display(), e))?;
+            let name = path.file_stem().map_or("plugin".into(), |s| s.to_string_lossy().into_owned());
+            WasmExtractor::from_bytes(&name, &bytes)
+        }
+
+        // Compile a plugin from wasm (or wat) bytes
+        pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+            let engine = Engine::default();
+            let module = Module::new(&engine, bytes)?;
+            Ok(WasmExtractor {
+                name: name.to_string(),
+                engine,
+                module,
+            })
+        }
+    }
+
+    impl Extractor for WasmExtractor {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn extract(&self, document: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            // Fresh instance per document, plugins can't keep state between runs
+            let mut store = Store::new(&self.engine, ());
+            let instance = Instance::new(&mut store, &self.module, &[])?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| format!("plugin {} does not export its memory", self.name))?;
+            let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+            let extract = instance.get_typed_func::<(i32, i32), i64>(&mut store, "extract")?;
+
+            let len = i32::try_from(document.len())?;
+            let ptr = alloc.call(&mut store, len)?;
+            memory.write(&mut store, ptr as u32 as usize, document.as_bytes())?;
+
+            let packed = extract.call(&mut store, (ptr, len))? as u64;
+            let mut out = vec![0u8; (packed & 0xffff_ffff) as usize];
+            memory.read(&store, (packed >> 32) as usize, &mut out)?;
+
+            Ok(String::from_utf8(out)?
+                .lines()
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::to_string)
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandExtractor, Extractor, PandocExtractor, RegexExtractor, parse_key_line};
+
+    #[test]
+    fn test_regex_extractor() {
+        let keys = RegexExtractor.extract("[@BGH.2024; @BGH.2010c] and @BGH.2024").unwrap();
+        assert_eq!(keys, vec!["BGH.2024", "BGH.2010c", "BGH.2024"]);
+    }
+
+    #[test]
+    fn test_pandoc_extractor() {
+        let keys = PandocExtractor.extract("[@BGH.2024; @smith2009] and `@Code.2021` @VGHBaden-Württemberg.2024").unwrap();
+        assert_eq!(keys, vec!["BGH.2024", "smith2009", "VGHBaden-Württemberg.2024"]);
+    }
+
+    #[test]
+    fn test_parse_key_line() {
+        assert_eq!(parse_key_line("Smith2009a").unwrap(), "Smith2009a");
+        assert_eq!(
+            parse_key_line(r#"{"key": "Jones2010", "line": 3, "column": 7}"#).unwrap(),
+            "Jones2010"
+        );
+        assert!(parse_key_line(r#"{"line": 3}"#).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_extractor() {
+        let extractor = CommandExtractor::new("grep -o 'cite{[^}]*}' | tr -d '{}' | sed 's/^cite//'");
+        assert_eq!(
+            extractor.extract("see \\cite{Smith2009a} and \\cite{Jones2010}\n").unwrap(),
+            vec!["Smith2009a", "Jones2010"]
+        );
+        assert!(CommandExtractor::new("exit 3").extract("x").is_err());
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[test]
+    fn test_wasm_extractor() {
+        use super::wasm_plugin::WasmExtractor;
+
+        // Echo plugin: every line of the document is a key
+        let plugin = r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "extract") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len)))))
+"#;
+        let extractor = WasmExtractor::from_bytes("echo", plugin.as_bytes()).unwrap();
+        assert_eq!(extractor.name(), "echo");
+        assert_eq!(
+            extractor.extract("Smith2009a\n\n  Jones2010 \n").unwrap(),
+            vec!["Smith2009a", "Jones2010"]
+        );
+
+        let broken = WasmExtractor::from_bytes("broken", b"(module)").unwrap();
+        assert!(broken.extract("x").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::ops::Range;
+
+// An invalid YAML header, with the position in the document (1-based)
+#[derive(Clone, PartialEq)]
+pub struct FrontMatterError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    // The header is indented with tabs, which YAML doesn't allow
+    pub tabs: bool,
+    // A quoted string with backslashes is invalid, e.g. a Windows path in double quotes
+    pub escapes: bool,
+}
+
+impl fmt::Display for FrontMatterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid YAML header at line {}, column {}: {}", self.line, self.column, self.message)?;
+        if self.tabs {
+            write!(f, "\nhint: YAML does not allow tabs for indentation, use spaces instead")?;
+        }
+        if self.escapes {
+            write!(f, "\nhint: backslashes in double quotes start escapes, write Windows paths in single quotes, \
+                       e.g. bibliography: 'C:\\Users\\me\\refs.json', or with forward slashes")?;
+        }
+        Ok(())
+    }
+}
+
+// main reports errors with Debug, show the diagnostic rather than the struct
+impl fmt::Debug for FrontMatterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for FrontMatterError {}
+
+// The YAML metadata blocks of the document, each with the document line of its first line
+pub fn blocks(document: &str) -> Vec<(String, usize)> {
+    let lines: Vec<&str> = document.lines().collect();
+    block_lines(document)
+        .into_iter()
+        .map(|range| (lines[range.clone()].join("\n"), range.start + 1))
+        .collect()
+}
+
+// The lines (0-based) of the YAML metadata blocks, without the delimiters. Like pandoc, a block
+// opens with --- and closes with --- or ...; blocks after the first must follow a blank line and
+// not start with one, so horizontal rules are not mistaken for them. An unclosed header at the
+// start runs to the end of the document.
+pub fn block_lines(document: &str) -> Vec<Range<usize>> {
+    let lines: Vec<&str> = document.lines().collect();
+    let first = lines.iter().position(|line| !line.trim().is_empty()).unwrap_or(lines.len());
+    let mut blocks = Vec::new();
+    let mut i = first;
+    while i < lines.len() {
+        let opens = lines[i].trim() == "---"
+            && (i == first
+                || (lines[i - 1].trim().is_empty() && lines.get(i + 1).is_some_and(|next| !next.trim().is_empty())));
+        if !opens {
+            i += 1;
+            continue;
+        }
+        let close = lines[i + 1..].iter().position(|line| matches!(line.trim(), "---" | "..."));
+        match close {
+            Some(length) => {
+                blocks.push(i + 1..i + 1 + length);
+                i += length + 2;
+            }
+            None if i == first => {
+                blocks.push(i + 1..lines.len());
+                break;
+            }
+            None => i += 1,
+        }
+    }
+    blocks
+}
+
+// Deserialize the document's metadata; with several blocks, later definitions of a field win like in pandoc
+pub fn parse<T: DeserializeOwned>(document: &str) -> Result<T, Box<dyn std::error::Error>> {
+    let blocks = blocks(document);
+    if blocks.is_empty() {
+        return Err("the document has no YAML header".into());
+    }
+    let mut metadata = serde_yaml::Mapping::new();
+    for (yaml, first_line) in &blocks {
+        let block: serde_yaml::Value = serde_yaml::from_str(yaml).map_err(|e| diagnostic(e, yaml, *first_line))?;
+        if let serde_yaml::Value::Mapping(fields) = block {
+            metadata.extend(fields);
+        }
+    }
+    Ok(serde_yaml::from_value(serde_yaml::Value::Mapping(metadata))?)
+}
+
+// The strings of these metadata fields, also inside lists and maps, e.g. for abstract and
+// acknowledgements; no strings if the document has no header
+pub fn field_strings(document: &str, fields: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    fn collect(value: &serde_yaml::Value, strings: &mut Vec<String>) {
+        match value {
+            serde_yaml::Value::String(string) => strings.push(string.clone()),
+            serde_yaml::Value::Sequence(values) => values.iter().for_each(|v| collect(v, strings)),
+            serde_yaml::Value::Mapping(fields) => fields.values().for_each(|v| collect(v, strings)),
+            serde_yaml::Value::Tagged(tagged) => collect(&tagged.value, strings),
+            _ => {}
+        }
+    }
+    if blocks(document).is_empty() {
+        return Ok(Vec::new());
+    }
+    let metadata: serde_yaml::Mapping = parse(document)?;
+    let mut strings = Vec::new();
+    for field in fields {
+        if let Some(value) = metadata.get(field.as_str()) {
+            collect(value, &mut strings);
+        }
+    }
+    Ok(strings)
+}
+
+fn diagnostic(error: serde_yaml::Error, yaml: &str, first_line: usize) -> FrontMatterError {
+    // serde_yaml counts from the start of the block, the position is reported separately
+    let position = Regex::new(r" at line \d+ column \d+").unwrap();
+    let (line, column) = error.location().map_or((1, 1), |l| (l.line(), l.column()));
+    FrontMatterError {
+        line: first_line + line - 1,
+        column,
+        message: position.replace_all(&error.to_string(), "").into_owned(),
+        tabs: yaml.contains('\t'),
+        escapes: error.to_string().contains("quoted scalar") && yaml.contains('\\'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrontMatterError, blocks, field_strings, parse};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_front_matter_errors() {
+        let document = "---\ntitle: Thesis\nauthor:\n\t- Me\nbibliography: refs.json\n---\nText";
+        assert_eq!(blocks(document)[0].1, 2);
+        let error = parse::<BTreeMap<String, serde_yaml::Value>>(document).unwrap_err();
+        let error = error.downcast_ref::<FrontMatterError>().unwrap();
+        assert_eq!((error.line, error.tabs), (4, true));
+        assert!(!error.message.contains(" at line "));
+        assert!(error.to_string().contains("hint: YAML does not allow tabs"));
+
+        let error = parse::<BTreeMap<String, String>>("---\nbibliography: \"C:\\Users\\me\\refs.json\"\n---\n").unwrap_err();
+        assert!(error.to_string().contains("Windows paths in single quotes"));
+        let header: BTreeMap<String, String> = parse("---\nbibliography: 'C:\\Users\\me\\refs.json'\n---\n").unwrap();
+        assert_eq!(header["bibliography"], "C:\\Users\\me\\refs.json");
+
+        let header: BTreeMap<String, String> = parse("\n---\nbibliography: refs.json\n---\n").unwrap();
+        assert_eq!(header["bibliography"], "refs.json");
+        assert!(parse::<BTreeMap<String, String>>("No header").is_err());
+    }
+
+    #[test]
+    fn test_multiple_blocks() {
+        let document = "---\ntitle: Thesis\nbibliography: old.json\n...\nText\n\n---\n\nAfter a rule\n\n\
+                        ---\nbibliography: refs.json\n---\nMore text";
+        assert_eq!(blocks(document).iter().map(|b| b.1).collect::<Vec<_>>(), vec![2, 12]);
+        let header: BTreeMap<String, String> = parse(document).unwrap();
+        assert_eq!(header["bibliography"], "refs.json");
+        assert_eq!(header["title"], "Thesis");
+    }
+
+    #[test]
+    fn test_field_strings() {
+        let document = "---\ntitle: On @A.2020\nabstract: |\n  Building on @B.2021.\nacknowledgements:\n  - Thanks [@C.2022]\n---\nText";
+        let fields = ["abstract".to_string(), "acknowledgements".to_string(), "missing".to_string()];
+        assert_eq!(field_strings(document, &fields).unwrap(), vec!["Building on @B.2021.\n", "Thanks [@C.2022]"]);
+        assert!(field_strings("No header", &fields).unwrap().is_empty());
+    }
+}
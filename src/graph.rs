@@ -0,0 +1,204 @@
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::pandoc::cited_keys;
+
+// Undirected graph of citation keys; two keys share an edge when they are cited
+// in the same paragraph or footnote, the edge weight counts how often that happens
+#[derive(Debug, Default, PartialEq)]
+pub struct CoCitationGraph {
+    // key -> number of paragraphs / footnotes citing it
+    pub nodes: BTreeMap<String, usize>,
+    // (key, key) with the first key sorting before the second -> weight
+    pub edges: BTreeMap<(String, String), usize>,
+}
+
+// Split a paragraph into its inline footnotes (^[...]) and the remaining body text
+fn split_inline_footnotes(paragraph: &str) -> (Vec<&str>, String) {
+    let mut footnotes = Vec::new();
+    let mut body = String::new();
+    let mut rest = paragraph;
+
+    while let Some(start) = rest.find("^[") {
+        body.push_str(&rest[..start]);
+        let inner = &rest[start + 2..];
+        let mut depth = 1;
+        let mut end = None;
+        for (i, c) in inner.char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        match end {
+            Some(end) => {
+                footnotes.push(&inner[..end]);
+                body.push(' ');
+                rest = &inner[end + 1..];
+            }
+            None => {
+                // Unbalanced brackets, treat the remainder as plain text
+                body.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    body.push_str(rest);
+
+    (footnotes, body)
+}
+
+// Keys cited per paragraph or footnote. Paragraphs are separated by blank lines,
+// footnote definitions ([^1]: ...) therefore form their own unit, inline
+// footnotes are split off the paragraph they appear in.
+fn citation_units(document: &str) -> Vec<BTreeSet<String>> {
+    let paragraph_break = Regex::new(r"\n[ \t]*\r?\n").unwrap();
+    let mut units = Vec::new();
+
+    for paragraph in paragraph_break.split(document) {
+        let (footnotes, body) = split_inline_footnotes(paragraph);
+        for text in footnotes.into_iter().chain(std::iter::once(body.as_str())) {
+            let keys: BTreeSet<String> = cited_keys(text).into_iter().collect();
+            if !keys.is_empty() {
+                units.push(keys);
+            }
+        }
+    }
+
+    units
+}
+
+impl CoCitationGraph {
+    pub fn from_document(document: &str) -> Self {
+        let mut graph = CoCitationGraph::default();
+
+        for keys in citation_units(document) {
+            for key in &keys {
+                *graph.nodes.entry(key.clone()).or_insert(0) += 1;
+            }
+            // BTreeSet iterates sorted, so a < b for every pair
+            for (i, a) in keys.iter().enumerate() {
+                for b in keys.iter().skip(i + 1) {
+                    *graph.edges.entry((a.clone(), b.clone())).or_insert(0) += 1;
+                }
+            }
+        }
+
+        graph
+    }
+
+    pub fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+
+        let mut out = String::from("graph cocitation {\n");
+        for (key, count) in &self.nodes {
+            out.push_str(&format!("  {} [count={}];\n", quote(key), count));
+        }
+        for ((a, b), weight) in &self.edges {
+            out.push_str(&format!("  {} -- {} [weight={}];\n", quote(a), quote(b), weight));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_graphml(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"count\" for=\"node\" attr.name=\"count\" attr.type=\"int\"/>\n",
+            "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n",
+            "  <graph id=\"cocitation\" edgedefault=\"undirected\">\n",
+        ));
+        for (key, count) in &self.nodes {
+            out.push_str(&format!(
+                "    <node id=\"{}\"><data key=\"count\">{}</data></node>\n",
+                escape(key),
+                count
+            ));
+        }
+        for ((a, b), weight) in &self.edges {
+            out.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"weight\">{}</data></edge>\n",
+                escape(a),
+                escape(b),
+                weight
+            ));
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    // GraphML when the target file ends in .graphml, DOT otherwise
+    pub fn render_for(&self, path: &Path) -> String {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("graphml") => self.to_graphml(),
+            _ => self.to_dot(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CoCitationGraph, split_inline_footnotes};
+    use std::path::Path;
+
+    #[test]
+    fn test_split_inline_footnotes() {
+        let paragraph = "Text^[vgl. @BGH.2024 Rn. 45; [sic] @BGH.2010c] more @Alexander.2024";
+        let (footnotes, body) = split_inline_footnotes(paragraph);
+        assert_eq!(footnotes, vec!["vgl. @BGH.2024 Rn. 45; [sic] @BGH.2010c"]);
+        assert_eq!(body, "Text  more @Alexander.2024");
+    }
+
+    #[test]
+    fn test_cocitation_graph() {
+        let document = r#"First paragraph.[@BGH.2024; @BGH.2010c] Also @Alexander.2024.
+
+Second paragraph.^[@BGH.2024 Rn. 3; @LGHamburg.2024] Body @Alexander.2024
+
+[^1]: @BGH.2024; @BGH.2010c
+"#;
+        let graph = CoCitationGraph::from_document(document);
+        assert_eq!(graph.nodes["BGH.2024"], 3);
+        assert_eq!(graph.nodes["Alexander.2024"], 2);
+        assert_eq!(
+            graph.edges[&("BGH.2010c".to_string(), "BGH.2024".to_string())],
+            2
+        );
+        assert_eq!(
+            graph.edges[&("BGH.2024".to_string(), "LGHamburg.2024".to_string())],
+            1
+        );
+        // Footnote and paragraph body are separate units
+        assert!(!graph
+            .edges
+            .contains_key(&("Alexander.2024".to_string(), "LGHamburg.2024".to_string())));
+    }
+
+    #[test]
+    fn test_render_for() {
+        let graph = CoCitationGraph::from_document("[@a.2020; @b.2021]");
+        assert_eq!(
+            graph.render_for(Path::new("out.dot")),
+            "graph cocitation {\n  \"a.2020\" [count=1];\n  \"b.2021\" [count=1];\n  \"a.2020\" -- \"b.2021\" [weight=1];\n}\n"
+        );
+        assert!(graph
+            .render_for(Path::new("out.GraphML"))
+            .contains("<edge source=\"a.2020\" target=\"b.2021\"><data key=\"weight\">1</data></edge>"));
+    }
+}
@@ -0,0 +1,119 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::syntax::Syntax;
+use crate::{encoding, paths};
+
+// Include directives of pandoc-include style filters, each on a line of its own:
+//   !include chapter2.md
+//   !include-header metadata.md
+//   !include`startLine=5` chapter3.md
+//   $include$ appendix.md
+// or, in an .aux file, the \@input{chapter2.aux} lines LaTeX writes for \include{chapter2}.
+// The file names, relative to the including document
+pub fn directives(document: &str, syntax: Syntax) -> Vec<String> {
+    let directive = match syntax {
+        Syntax::Aux => Regex::new(r"(?m)^\\@input\{([^}\n]+)\}").unwrap(),
+        _ => Regex::new(r"(?m)^(?:!include(?:-header)?(?:`[^`\n]*`)?|\$include\$?)[ \t]+(\S[^\n]*?)\$?[ \t]*\r?$").unwrap(),
+    };
+    directive
+        .captures_iter(document)
+        .map(|caps| caps[1].trim_matches(['"', '\'']).to_string())
+        .collect()
+}
+
+// The documents included by the document at `path`, recursively and depth first, each read once.
+// visited holds the documents already read (e.g. the other documents of a project), which are
+// skipped. A document including itself, directly or through others, is an error naming the cycle.
+// The directives are those of the syntax of the document at `path`, for nested documents too.
+pub fn resolve(
+    path: &Path,
+    document: &str,
+    syntax: Syntax,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let mut including = vec![(identity(path), path.to_path_buf())];
+    resolve_nested(path, document, syntax, visited, &mut including)
+}
+
+// including holds the documents from the outermost to this one, by identity and by path as named
+fn resolve_nested(
+    path: &Path,
+    document: &str,
+    syntax: Syntax,
+    visited: &mut HashSet<PathBuf>,
+    including: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    visited.insert(identity(path));
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut included = Vec::new();
+    for name in directives(document, syntax) {
+        let included_path = directory.join(paths::native(&name));
+        let included_identity = identity(&included_path);
+        if let Some(start) = including.iter().position(|(identity, _)| *identity == included_identity) {
+            let cycle: Vec<_> = including[start..].iter().map(|(_, path)| path)
+                .chain([&included_path])
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(format!("include cycle: {}", cycle.join(" -> ")).into());
+        }
+        if !visited.insert(included_identity.clone()) {
+            continue;
+        }
+        let text = encoding::read_to_string(&included_path)
+            .map_err(|e| format!("could not read {} included from {}: {}", included_path.display(), path.display(), e))?;
+        including.push((included_identity, included_path.clone()));
+        let nested = resolve_nested(&included_path, &text, syntax, visited, including)?;
+        including.pop();
+        included.push((included_path, text));
+        included.extend(nested);
+    }
+    Ok(included)
+}
+
+// Paths naming the same file, like ./a.md, a.md and a symlink to it, are one document
+pub fn identity(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{directives, resolve};
+    use crate::syntax::Syntax;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_resolve() {
+        let document = "Text\n!include chapter1.md\n!include`startLine=2` \"chapter 2.md\"\n$include$ appendix.md\n\
+                        Not !include inline.md\n";
+        assert_eq!(directives(document, Syntax::Markdown), vec!["chapter1.md", "chapter 2.md", "appendix.md"]);
+        let aux = "\\relax\n\\@input{chapters/intro.aux}\n\\citation{A.2020}\n";
+        assert_eq!(directives(aux, Syntax::Aux), vec!["chapters/intro.aux"]);
+        assert!(directives(aux, Syntax::Markdown).is_empty());
+        assert!(directives(document, Syntax::Aux).is_empty());
+
+        let directory = std::env::temp_dir().join("zoterocoverage-include");
+        std::fs::create_dir_all(directory.join("parts")).unwrap();
+        std::fs::write(directory.join("main.md"), "!include parts/a.md\n!include parts/b.md\n").unwrap();
+        std::fs::write(directory.join("parts/a.md"), "@A.2020\n!include b.md\n").unwrap();
+        std::fs::write(directory.join("parts/b.md"), "@B.2021\n").unwrap();
+
+        let main = directory.join("main.md");
+        let text = std::fs::read_to_string(&main).unwrap();
+        let included = resolve(&main, &text, Syntax::Markdown, &mut HashSet::new()).unwrap();
+        let names: Vec<_> = included.iter().map(|(path, _)| path.strip_prefix(&directory).unwrap()).collect();
+        assert_eq!(names, vec![std::path::Path::new("parts/a.md"), std::path::Path::new("parts/b.md")]);
+
+        std::fs::write(directory.join("parts/b.md"), "@B.2021\n!include ../main.md\n").unwrap();
+        let error = resolve(&main, &text, Syntax::Markdown, &mut HashSet::new()).unwrap_err().to_string();
+        assert!(error.starts_with("include cycle: "), "{}", error);
+        assert!(error.contains("main.md -> "), "{}", error);
+        assert!(error.ends_with(&format!("a.md -> {0}b.md -> {0}../main.md", directory.join("parts/").display())), "{}", error);
+
+        std::fs::write(directory.join("missing.md"), "!include nowhere.md\n").unwrap();
+        let error = resolve(&directory.join("missing.md"), "!include nowhere.md\n", Syntax::Markdown, &mut HashSet::new()).unwrap_err();
+        assert!(error.to_string().contains("included from"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
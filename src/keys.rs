@@ -0,0 +1,102 @@
+use aho_corasick::{AhoCorasick, Anchored, Input, MatchKind, StartKind};
+
+// The bibliography keys as one Aho-Corasick automaton, so looking up the citations of a long
+// book walks each cited key once instead of hashing it against every entry
+pub struct KeyIndex {
+    automaton: AhoCorasick,
+}
+
+impl KeyIndex {
+    pub fn new<I, K>(keys: I) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<[u8]>,
+    {
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .start_kind(StartKind::Anchored)
+            .build(keys)?;
+        Ok(KeyIndex { automaton })
+    }
+
+    // Position of the key in the bibliography (the first one for duplicates), None for unknown keys
+    pub fn lookup(&self, key: &str) -> Option<usize> {
+        let input = Input::new(key).anchored(Anchored::Yes);
+        self.automaton
+            .find(input)
+            .filter(|m| m.end() == key.len())
+            .map(|m| m.pattern().as_usize())
+    }
+
+    // How often each bibliography key is cited, by position, and the unknown cited keys in order of
+    // first appearance
+    pub fn frequencies<'a>(&self, cited: &[&'a str]) -> (Vec<usize>, Vec<&'a str>) {
+        let mut counts = vec![0; self.automaton.patterns_len()];
+        let mut unknown: Vec<&str> = Vec::new();
+        for key in cited {
+            match self.lookup(key) {
+                Some(index) => counts[index] += 1,
+                None if !unknown.contains(key) => unknown.push(key),
+                None => {}
+            }
+        }
+        (counts, unknown)
+    }
+}
+
+// The bibliography key a cited key most likely means: one differing only in case, or else the
+// closest within two edits, e.g. a typo like Smiht.2009; None if no key is that close
+pub fn suggest<'a>(key: &str, keys: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let lowercase = key.to_lowercase();
+    keys.into_iter()
+        .map(|candidate| {
+            let distance = if candidate.to_lowercase() == lowercase { 0 } else { edit_distance(key, candidate) };
+            (distance, candidate)
+        })
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyIndex, suggest};
+
+    #[test]
+    fn test_key_index() {
+        let index = KeyIndex::new(["BGH.2024", "BGH.2024a", "Smith.2009", "BGH.2024"]).unwrap();
+        assert_eq!(index.lookup("BGH.2024"), Some(0));
+        assert_eq!(index.lookup("BGH.2024a"), Some(1));
+        assert_eq!(index.lookup("BGH.202"), None);
+        assert_eq!(index.lookup("BGH.2024ab"), None);
+
+        let (counts, unknown) = index.frequencies(&["BGH.2024a", "Jones.2010", "BGH.2024", "BGH.2024a", "Jones.2010"]);
+        assert_eq!(counts, vec![1, 2, 0, 0]);
+        assert_eq!(unknown, vec!["Jones.2010"]);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let keys = ["Smith.2009", "BGH.2024", "BGH.2024a", "Müller.2020"];
+        assert_eq!(suggest("Smiht.2009", keys), Some("Smith.2009"));
+        assert_eq!(suggest("bgh.2024", keys), Some("BGH.2024"));
+        assert_eq!(suggest("BGH.2024b", keys), Some("BGH.2024"));
+        assert_eq!(suggest("Muller.2020", keys), Some("Müller.2020"));
+        assert_eq!(suggest("Jones.2010", keys), None);
+    }
+}
@@ -0,0 +1,817 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+pub mod annotate;
+pub mod better_bibtex;
+pub mod cluster;
+pub mod encoding;
+pub mod exclude;
+pub mod extract;
+pub mod front_matter;
+pub mod graph;
+pub mod keys;
+pub mod library;
+pub mod pandoc;
+pub mod porcelain;
+pub mod rename;
+pub mod rules;
+pub mod search;
+pub mod stats;
+pub mod suppress;
+pub mod syntax;
+
+#[cfg(feature = "fs")]
+pub mod archive;
+#[cfg(feature = "fs")]
+pub mod chunks;
+#[cfg(feature = "fs")]
+pub mod citeproc;
+#[cfg(feature = "fs")]
+pub mod config;
+#[cfg(feature = "fs")]
+pub mod include;
+#[cfg(feature = "fs")]
+pub mod mods;
+#[cfg(feature = "fs")]
+pub mod paths;
+#[cfg(feature = "fs")]
+pub mod project;
+#[cfg(feature = "fs")]
+pub mod rdf;
+#[cfg(feature = "fs")]
+pub mod safe_write;
+#[cfg(feature = "fs")]
+pub mod source;
+
+#[cfg(feature = "network")]
+pub mod network;
+#[cfg(feature = "network")]
+pub mod zotero_api;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+// napi only registers the exports outside of test builds
+#[cfg(feature = "node")]
+#[cfg_attr(test, allow(dead_code))]
+mod node;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[derive(Deserialize, Debug, Clone, Hash, PartialEq)]
+pub struct Citations {
+    #[serde(rename = "citation-key")]
+    pub citation_key: String,
+}
+
+// Get Metadata from markdown document for Library
+// Bibliography field contains path
+#[derive(Deserialize)]
+struct Metadata {
+    bibliography: String
+}
+
+impl fmt::Display for Citations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.citation_key)
+    }
+}
+
+// A bibliography entry that could not be read, identified by its position and id
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedEntry {
+    pub index: usize,
+    pub id: Option<String>,
+    pub error: String,
+}
+
+impl fmt::Display for SkippedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.id {
+            Some(ref id) => write!(f, "entry {} ({}): {}", self.index, id, self.error),
+            None => write!(f, "entry {}: {}", self.index, self.error),
+        }
+    }
+}
+
+// The key of a CSL-JSON entry: citation-key (Better BibTeX), or else of plain Zotero exports the key
+// pinned in the note or else the id
+pub fn entry_key(entry: &serde_json::Value) -> Option<&str> {
+    match entry.get("citation-key") {
+        Some(key) => key.as_str(),
+        None => note_key(entry).or_else(|| entry.get("id").and_then(|id| id.as_str())),
+    }
+}
+
+// Zotero exports its extra field as the note
+fn note_key(entry: &serde_json::Value) -> Option<&str> {
+    extra_key(entry.get("note")?.as_str()?)
+}
+
+// The key of a Citation Key: Smith.2009 line in Zotero's extra field, where Better BibTeX pins
+// keys and which also holds without Better BibTeX installed
+pub(crate) fn extra_key(extra: &str) -> Option<&str> {
+    extra.lines().find_map(|line| {
+        let (field, key) = line.split_once(':')?;
+        let key = key.trim();
+        (field.trim().eq_ignore_ascii_case("citation key") && !key.is_empty()).then_some(key)
+    })
+}
+
+// The entry with citation-key filled in from the note or id if it has none
+pub(crate) fn with_entry_key(mut entry: serde_json::Value) -> serde_json::Value {
+    if entry.get("citation-key").is_none()
+        && let Some(key) = entry_key(&entry).map(str::to_string)
+        && let Some(fields) = entry.as_object_mut()
+    {
+        fields.insert("citation-key".to_string(), serde_json::Value::from(key));
+    }
+    entry
+}
+
+// Parse the entries one by one, so a single malformed entry doesn't fail the whole bibliography
+pub fn parse_bibliography(
+    bibliography: &str,
+) -> Result<(Vec<Citations>, Vec<SkippedEntry>), Box<dyn std::error::Error>> {
+    let entries: Vec<serde_json::Value> = serde_json::from_str(bibliography)?;
+    let mut citations = Vec::with_capacity(entries.len());
+    let mut skipped = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let id = entry.get("id").and_then(|id| id.as_str()).map(str::to_string);
+        match serde_json::from_value(with_entry_key(entry)) {
+            Ok(citation) => citations.push(citation),
+            Err(e) => skipped.push(SkippedEntry { index, id, error: e.to_string() }),
+        }
+    }
+    Ok((citations, skipped))
+}
+
+// The bibliography's entries; malformed ones are skipped with a warning
+pub fn get_citations_bibliography(
+    bibliography: &str,
+) -> Result<Vec<Citations>, Box<dyn std::error::Error>> {
+    Ok(get_citations_bibliography_skipping(bibliography)?.0)
+}
+
+// The bibliography's entries and the number of malformed ones, skipped with a warning
+pub fn get_citations_bibliography_skipping(
+    bibliography: &str,
+) -> Result<(Vec<Citations>, usize), Box<dyn std::error::Error>> {
+    let (citations, skipped) = parse_bibliography(bibliography)?;
+    for entry in &skipped {
+        eprintln!("Warning: skipping bibliography {}", entry);
+    }
+    Ok((citations, skipped.len()))
+}
+
+// Keys in the default Better BibTeX format Name.2024 anywhere in the text; pandoc::parse_citations
+// follows pandoc's citation grammar instead
+pub fn get_citations_document(document: &str) -> Result<Vec<&str>, Box<dyn std::error::Error>> {
+    let re = Regex::new(r"@(?<key>\w+\.\d{4}\w?)").unwrap();
+    let md_citations: Vec<&str> = re
+        .captures_iter(document)
+        .map(|caps| caps.name("key").unwrap().as_str())
+        .collect();
+
+    Ok(md_citations)
+}
+
+pub fn get_citation_difference(
+    document: Vec<&str>,
+    json: Vec<Citations>,
+) -> Result<Vec<Citations>, Box<dyn std::error::Error>> {
+    let document_set: HashSet<&str> = HashSet::from_iter(document);
+
+    let difference: Vec<_> = json
+        .iter()
+        .filter(|citation| !document_set.contains(&citation.citation_key[..]))
+        .cloned()
+        .collect();
+
+    Ok(difference)
+}
+
+// Keys cited only in the old or only in the new document, each in order of first citation
+pub fn compare_citations(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let only_in = |keys: &[String], other: &[String]| -> Vec<String> {
+        let other: HashSet<&String> = other.iter().collect();
+        let mut seen = HashSet::new();
+        keys.iter().filter(|key| !other.contains(key) && seen.insert(*key)).cloned().collect()
+    };
+    (only_in(old, new), only_in(new, old))
+}
+
+pub fn get_bibliography_path(document: &str) -> Result<String, Box<dyn std::error::Error>> {
+    // Extract the path of the bibliography given in the yaml header of the passed md file
+    let metadata: Metadata = front_matter::parse(document)?;
+    Ok(metadata.bibliography)
+}
+
+// Result of comparing a document against a bibliography
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    // Bibliography entries cited in the document
+    pub cited: Vec<String>,
+    // Bibliography entries not cited in the document
+    pub uncited: Vec<String>,
+    // Keys cited in the document but missing from the bibliography
+    pub unknown: Vec<String>,
+}
+
+impl CoverageReport {
+    // One line for shell prompts and status lines, e.g. "12/20 (60%), 2 unknown". Suppressed
+    // entries count towards the total; without entries the coverage is complete.
+    pub fn summary(&self, suppressed: usize) -> String {
+        let total = self.cited.len() + self.uncited.len() + suppressed;
+        format!("{}/{} ({}%), {} unknown", self.cited.len(), total, self.percent(suppressed), self.unknown.len())
+    }
+
+    // Percentage of the entries cited, counted like in summary
+    pub fn percent(&self, suppressed: usize) -> usize {
+        match self.cited.len() + self.uncited.len() + suppressed {
+            0 => 100,
+            total => self.cited.len() * 100 / total,
+        }
+    }
+}
+
+// The coverage in the shields.io endpoint schema, for a badge served from the repository, e.g.
+// https://img.shields.io/endpoint?url=<raw URL of the file>. The color follows the percentage.
+pub fn badge_json(report: &CoverageReport, suppressed: usize) -> Result<String, serde_json::Error> {
+    let percent = report.percent(suppressed);
+    let color = match percent {
+        90.. => "brightgreen",
+        75..90 => "green",
+        50..75 => "yellow",
+        25..50 => "orange",
+        _ => "red",
+    };
+    serde_json::to_string(&serde_json::json!({
+        "schemaVersion": 1,
+        "label": "citation coverage",
+        "message": format!("{}%", percent),
+        "color": color,
+    }))
+}
+
+// Version of the JSON report, incremented when a field is removed or changed
+pub const SCHEMA_VERSION: u32 = 1;
+
+// JSON Schema of the report, printed by the output-schema subcommand
+pub const REPORT_SCHEMA: &str = include_str!("../schema/report.schema.json");
+
+// The report as JSON, with the schema_version it follows and, if given, every citation with its position
+pub fn report_json(report: &CoverageReport, citations: Option<&[search::Occurrence]>) -> Result<String, serde_json::Error> {
+    #[derive(Serialize)]
+    struct Versioned<'a> {
+        schema_version: u32,
+        #[serde(flatten)]
+        report: &'a CoverageReport,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        citations: Option<&'a [search::Occurrence]>,
+    }
+    serde_json::to_string(&Versioned { schema_version: SCHEMA_VERSION, report, citations })
+}
+
+// A report written by report_json; reports of a newer schema_version are rejected
+pub fn parse_report_json(json: &str) -> Result<CoverageReport, Box<dyn std::error::Error>> {
+    let report: serde_json::Value = serde_json::from_str(json)?;
+    if let Some(version) = report["schema_version"].as_u64()
+        && version > SCHEMA_VERSION as u64
+    {
+        return Err(format!("report has schema_version {}, this version reads up to {}", version, SCHEMA_VERSION).into());
+    }
+    Ok(serde_json::from_value(report)?)
+}
+
+// Reports of runs over parts of one project, e.g. per-chapter CI jobs, as one: an entry is cited if
+// any run cited it and uncited if none did. Keys keep the order of their first appearance.
+pub fn merge_reports(reports: &[CoverageReport]) -> CoverageReport {
+    let mut cited: Vec<String> = Vec::new();
+    for key in reports.iter().flat_map(|report| &report.cited) {
+        if !cited.contains(key) {
+            cited.push(key.clone());
+        }
+    }
+    let mut merged = CoverageReport { cited, uncited: Vec::new(), unknown: Vec::new() };
+    for report in reports {
+        for (keys, merged_keys) in [(&report.uncited, &mut merged.uncited), (&report.unknown, &mut merged.unknown)] {
+            for key in keys {
+                if !merged.cited.contains(key) && !merged_keys.contains(key) {
+                    merged_keys.push(key.clone());
+                }
+            }
+        }
+    }
+    merged
+}
+
+// Compare a document with a CSL-JSON bibliography, without touching the filesystem
+pub fn check_coverage(document: &str, bibliography: &str) -> Result<CoverageReport, Box<dyn std::error::Error>> {
+    let bibliography = better_bibtex::normalize(bibliography.to_string())?;
+    let citations_bibliography = get_citations_bibliography(&bibliography)?;
+    let citations = pandoc::parse_citations(document);
+    let citations_document = citations.iter().map(|c| c.key.as_str()).collect();
+    coverage_report(citations_document, citations_bibliography)
+}
+
+// The report for already extracted document keys and bibliography entries
+pub fn coverage_report(
+    citations_document: Vec<&str>,
+    citations_bibliography: Vec<Citations>,
+) -> Result<CoverageReport, Box<dyn std::error::Error>> {
+    let index = keys::KeyIndex::new(citations_bibliography.iter().map(|c| &c.citation_key))?;
+    let (counts, unknown) = index.frequencies(&citations_document);
+    let unknown = unknown.into_iter().map(str::to_string).collect();
+
+    let (cited, uncited): (Vec<Citations>, Vec<Citations>) = citations_bibliography
+        .into_iter()
+        .partition(|c| index.lookup(&c.citation_key).is_some_and(|i| counts[i] > 0));
+    let cited = cited.into_iter().map(|c| c.citation_key).collect();
+    let uncited = uncited.into_iter().map(|c| c.citation_key).collect();
+
+    Ok(CoverageReport { cited, uncited, unknown })
+}
+
+// check_coverage on files; without a bibliography the one named in the document's yaml header is used.
+// Only file bibliographies are supported here, the CLI handles stdin and network sources.
+#[cfg(feature = "fs")]
+pub fn check_files(
+    document: &std::path::Path,
+    bibliography: Option<&std::path::Path>,
+) -> Result<CoverageReport, Box<dyn std::error::Error>> {
+    check_document(&encoding::read_to_string(document)?, bibliography)
+}
+
+// check_files for a document already read
+#[cfg(feature = "fs")]
+pub fn check_document(
+    document: &str,
+    bibliography: Option<&std::path::Path>,
+) -> Result<CoverageReport, Box<dyn std::error::Error>> {
+    let source = match bibliography {
+        Some(path) => source::BibliographySource::File(path.to_path_buf()),
+        None => source::BibliographySource::from_front_matter(document)?,
+    };
+    let bibliography = match source {
+        source::BibliographySource::File(path) => source::read_file(&path, mods::DEFAULT_KEY_FIELD)?,
+        other => return Err(format!("unsupported bibliography source {}", other).into()),
+    };
+    check_coverage(document, &bibliography)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Citations, CoverageReport, REPORT_SCHEMA, SCHEMA_VERSION, badge_json, check_coverage, compare_citations, get_citation_difference, merge_reports, parse_report_json, report_json, parse_bibliography, get_citations_bibliography, get_citations_document, get_bibliography_path,
+    };
+
+    #[test]
+    fn test_get_citations_bibliography() {
+        let testdata_json = r#"
+    [
+  {
+    "id": ".2024",
+    "accessed": {
+      "date-parts": [
+        [
+          "2025",
+          1,
+          29
+        ]
+      ]
+    },
+    "citation-key": ".2024",
+    "container-title": "JuristenZeitung",
+    "container-title-short": "JZ",
+    "DOI": "10.1628/jz-2024-0306",
+    "ISSN": "0022-6882",
+    "issue": "22",
+    "issued": {
+      "date-parts": [
+        [
+          "2024"
+        ]
+      ]
+    },
+    "language": "de",
+    "page": "1007",
+    "source": "DOI.org (Crossref)",
+    "title": "Potenzial und Grenzen eines Einsatzes von Large Language Models in der öffentlichen Verwaltung",
+    "type": "article-journal",
+    "URL": "https://www.mohrsiebeck.com/10.1628/jz-2024-0306",
+    "volume": "79"
+  },
+  {
+    "id": ".2024a",
+    "accessed": {
+      "date-parts": [
+        [
+          "2025",
+          1,
+          29
+        ]
+      ]
+    },
+    "citation-key": ".2024a",
+    "container-title": "Archiv für die civilistische Praxis",
+    "container-title-short": "AcP",
+    "DOI": "10.1628/acp-2024-0020",
+    "ISSN": "0003-8997",
+    "issue": "4-5",
+    "issued": {
+      "date-parts": [
+        [
+          "2024"
+        ]
+      ]
+    },
+    "language": "de",
+    "page": "477",
+    "source": "DOI.org (Crossref)",
+    "title": "Kryptowerte als Sachen",
+    "type": "article-journal",
+    "URL": "https://www.mohrsiebeck.com/10.1628/acp-2024-0020",
+    "volume": "224"
+  },
+  {
+    "id": "AGGelnhausen.2024",
+    "authority": "AG Gelnhausen",
+    "citation-key": "AGGelnhausen.2024",
+    "genre": "Urt.",
+    "issued": {
+      "date-parts": [
+        [
+          "2024",
+          3,
+          4
+        ]
+      ]
+    },
+    "jurisdiction": "de",
+    "number": "52 C 76/24",
+    "title": "AG Gelnhausen, 04.03.2024 - 52 C 76/24",
+    "type": "legal_case"
+  },
+  {
+    "id": "Alexander.2024",
+    "author": [
+      {
+        "family": "Alexander",
+        "given": ""
+      }
+    ],
+    "citation-key": "Alexander.2024",
+    "container-title": "UWG",
+    "edition": "42",
+    "editor": [
+      {
+        "family": "Köhler",
+        "given": ""
+      },
+      {
+        "family": "Bornkamm",
+        "given": ""
+      },
+      {
+        "family": "Feddersen",
+        "given": ""
+      }
+    ],
+    "issued": {
+      "date-parts": [
+        [
+          "2024"
+        ]
+      ]
+    },
+    "source": "beck-online",
+    "title": "§ 2 GeschGehG",
+    "type": "entry-encyclopedia"
+  },
+  {
+    "id": "Alexander.2024a",
+    "author": [
+      {
+        "family": "Alexander",
+        "given": ""
+      }
+    ],
+    "citation-key": "Alexander.2024a",
+    "container-title": "UWG",
+    "edition": "42",
+    "editor": [
+      {
+        "family": "Köhler",
+        "given": ""
+      },
+      {
+        "family": "Bornkamm",
+        "given": ""
+      },
+      {
+        "family": "Feddersen",
+        "given": ""
+      }
+    ],
+    "issued": {
+      "date-parts": [
+        [
+          "2024"
+        ]
+      ]
+    },
+    "source": "beck-online",
+    "title": "§ 6 GeschGehG",
+    "type": "entry-encyclopedia"
+  }
+  ]
+ "#;
+        let out = vec![
+            Citations {
+                citation_key: ".2024".to_string(),
+            },
+            Citations {
+                citation_key: ".2024a".to_string(),
+            },
+            Citations {
+                citation_key: "AGGelnhausen.2024".to_string(),
+            },
+            Citations {
+                citation_key: "Alexander.2024".to_string(),
+            },
+            Citations {
+                citation_key: "Alexander.2024a".to_string(),
+            },
+        ];
+        assert_eq!(get_citations_bibliography(testdata_json).unwrap(), out);
+    }
+
+    #[test]
+    fn test_get_citations_bibliography_missing_field() {
+        let testdata_json = r#"[{}]"#; // Missing citation-key
+        let result = get_citations_bibliography(testdata_json);
+        assert_eq!(result.unwrap(), vec![]);
+        assert!(get_citations_bibliography("{}").is_err());
+
+        let testdata_json = r#"[{"citation-key": "A.2020"}, {"id": "broken", "citation-key": 5}, {"id": 7}]"#;
+        let (citations, skipped) = parse_bibliography(testdata_json).unwrap();
+        assert_eq!(citations.len(), 1);
+        assert_eq!(skipped.len(), 2);
+        assert_eq!((skipped[0].index, skipped[0].id.as_deref()), (1, Some("broken")));
+    }
+
+    #[test]
+    fn test_get_citations_bibliography_id_fallback() {
+        // Plain Zotero export without Better BibTeX
+        let testdata_json = r#"[{"id": "Smith.2009", "type": "book"}, {"id": "x", "citation-key": "Jones.2010"},
+            {"id": "http://zotero.org/users/1/items/ABCD", "note": "Original date: 1999\nCitation Key: Miller.2011"},
+            {"id": "y", "citation-key": "Lee.2012", "note": "Citation Key: Stale.2000"}]"#;
+        let keys: Vec<String> = get_citations_bibliography(testdata_json)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.citation_key)
+            .collect();
+        assert_eq!(keys, vec!["Smith.2009", "Jones.2010", "Miller.2011", "Lee.2012"]);
+    }
+
+    #[test]
+    fn test_get_citations_document() {
+        let testdata_md = r#"Gemeinsame Voraussetzung beider Schranken ist zunächst, dass der
+Zugang zu den Daten rechtmäßig erfolgt.[@Bomhard.2024b Rn. 15] Dieser
+kann etwa auf einer dahingegenden Lizenz beruhen (welche aber etwa
+kein TDM zulässt) oder auch auf einer Einwilligung der:des
+Berechtigten. Eine solche kann sich (konkludent) durch öffentliche
+Zugänglichmachung im Internet ergeben.[@BGH.2024 Rn. 45--47;
+@BGH.2010c Rn. 36; so auch @LGHamburg.2024 Rn. 86] Das wird meist der
+Fall sein, zumindest, was den Zugang zu den Daten betrifft. @Alexander.2024; @Alexander.2024a
+"#;
+        assert_eq!(
+            get_citations_document(testdata_md).unwrap(),
+            vec![
+                "Bomhard.2024b",
+                "BGH.2024",
+                "BGH.2010c",
+                "LGHamburg.2024",
+                "Alexander.2024",
+                "Alexander.2024a"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_citations_document_invalid_format() {
+        let testdata_md = "Here is a citation @key.1991 and another @key.2002. Invalid @key.";
+        let result = get_citations_document(testdata_md).unwrap();
+        assert_eq!(result.len(), 2); // Should still extract key1 and key2
+        assert_eq!(result[0], "key.1991");
+        assert_eq!(result[1], "key.2002");
+    }
+
+    #[test]
+    fn test_get_citation_difference() {
+        let json: Vec<Citations> = vec![
+            Citations {
+                citation_key: ".2024".to_string(),
+            },
+            Citations {
+                citation_key: ".2024a".to_string(),
+            },
+            Citations {
+                citation_key: "AGGelnhausen.2024".to_string(),
+            },
+            Citations {
+                citation_key: "Alexander.2024".to_string(),
+            },
+            Citations {
+                citation_key: "Alexander.2024a".to_string(),
+            },
+        ];
+        let md = vec![
+            "Bomhard.2024b",
+            "BGH.2024",
+            "BGH.2010c",
+            "LGHamburg.2024",
+            "Alexander.2024",
+            "Alexander.2024a",
+        ];
+        let out = vec![
+            Citations {
+                citation_key: ".2024".to_string(),
+            },
+            Citations {
+                citation_key: ".2024a".to_string(),
+            },
+            Citations {
+                citation_key: "AGGelnhausen.2024".to_string(),
+            },
+        ];
+        // Expected output
+        // inputs (steal from the prints)
+        assert_eq!(get_citation_difference(md, json).unwrap(), out)
+    }
+    #[test]
+    fn test_get_citation_difference_empty() {
+        let document_citations: Vec<&str> = vec![];
+        let json_citations = vec![
+            Citations {
+                citation_key: "key1".to_string(),
+            },
+            Citations {
+                citation_key: "key2".to_string(),
+            },
+        ];
+
+        let result = get_citation_difference(document_citations, json_citations).unwrap();
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].citation_key, "key1");
+        assert_eq!(result[1].citation_key, "key2");
+    }
+
+    #[test]
+    fn test_get_citation_difference_all_match() {
+        let document_citations = vec!["key1", "key2"];
+        let json_citations = vec![
+            Citations {
+                citation_key: "key1".to_string(),
+            },
+            Citations {
+                citation_key: "key2".to_string(),
+            },
+        ];
+
+        let result = get_citation_difference(document_citations, json_citations).unwrap();
+        assert_eq!(result.len(), 0); // No differences
+    }
+
+    #[test]
+    fn test_get_citation_difference_duplicates_in_document() {
+        let document_citations = vec!["key1", "key1", "key3"];
+        let json_citations = vec![
+            Citations {
+                citation_key: "key1".to_string(),
+            },
+            Citations {
+                citation_key: "key2".to_string(),
+            },
+            Citations {
+                citation_key: "key3".to_string(),
+            },
+        ];
+
+        let result = get_citation_difference(document_citations, json_citations).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].citation_key, "key2"); // key2 should still be the only difference
+    }
+
+    #[test]
+    fn test_get_citation_difference_empty_document() {
+        let document_citations: Vec<&str> = vec![];
+        let json_citations = vec![
+            Citations {
+                citation_key: "key1".to_string(),
+            },
+            Citations {
+                citation_key: "key2".to_string(),
+            },
+        ];
+
+        let result = get_citation_difference(document_citations, json_citations).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].citation_key, "key1");
+    }
+    #[test]
+    fn test_compare_citations() {
+        let keys = |keys: &[&str]| -> Vec<String> { keys.iter().map(|k| k.to_string()).collect() };
+        let old = keys(&["BGH.2024", "Smith.2009", "Smith.2009", "Doe.2011"]);
+        let new = keys(&["Jones.2010", "BGH.2024", "Jones.2010"]);
+        assert_eq!(compare_citations(&old, &new), (keys(&["Smith.2009", "Doe.2011"]), keys(&["Jones.2010"])));
+        assert_eq!(compare_citations(&old, &old), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_get_bibliography_path() {
+        let header = r#"---
+        bibliography: a/b/c
+        ---
+        "#;
+        assert_eq!(get_bibliography_path(header).unwrap(), "a/b/c");
+    }
+
+    #[test]
+    fn test_get_bibliography_path_tab() {
+        let header = r#"---
+        foo:
+        \tbar: baz
+        bibliography: a/b/c
+        "#;
+        assert_eq!(get_bibliography_path(header).unwrap(), "a/b/c");
+    }
+
+    #[test]
+    fn test_check_coverage() {
+        let bibliography = r#"[{"citation-key": "BGH.2024"}, {"citation-key": "BGH.2010c"}, {"citation-key": "Alexander.2024"}]"#;
+        let document = "Text.[@BGH.2024; @Unknown.2020] More @BGH.2024 and @Unknown.2020";
+        let report = check_coverage(document, bibliography).unwrap();
+        assert_eq!(report.cited, vec!["BGH.2024"]);
+        assert_eq!(report.uncited, vec!["BGH.2010c", "Alexander.2024"]);
+        assert_eq!(report.unknown, vec!["Unknown.2020"]);
+    }
+
+    #[test]
+    fn test_summary() {
+        let keys = |n: usize| (0..n).map(|i| format!("K.{}", 2000 + i)).collect::<Vec<_>>();
+        let report = CoverageReport { cited: keys(2), uncited: keys(4), unknown: keys(1) };
+        assert_eq!(report.summary(0), "2/6 (33%), 1 unknown");
+        assert_eq!(report.summary(2), "2/8 (25%), 1 unknown");
+        let empty = CoverageReport { cited: vec![], uncited: vec![], unknown: vec![] };
+        assert_eq!(empty.summary(0), "0/0 (100%), 0 unknown");
+    }
+
+    #[test]
+    fn test_badge_json() {
+        let keys = |n: usize| (0..n).map(|i| format!("K.{}", 2000 + i)).collect::<Vec<_>>();
+        let report = CoverageReport { cited: keys(3), uncited: keys(1), unknown: keys(2) };
+        let badge: serde_json::Value = serde_json::from_str(&badge_json(&report, 0).unwrap()).unwrap();
+        assert_eq!(badge, serde_json::json!({
+            "schemaVersion": 1, "label": "citation coverage", "message": "75%", "color": "green"
+        }));
+        let badge: serde_json::Value = serde_json::from_str(&badge_json(&report, 8).unwrap()).unwrap();
+        assert_eq!((&badge["message"], &badge["color"]), (&serde_json::json!("25%"), &serde_json::json!("orange")));
+    }
+
+    #[test]
+    fn test_report_json() {
+        let report = CoverageReport { cited: vec!["A.2020".into()], uncited: vec![], unknown: vec!["B.2021".into()] };
+        let json: serde_json::Value = serde_json::from_str(&report_json(&report, None).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!({"schema_version": 1, "cited": ["A.2020"], "uncited": [], "unknown": ["B.2021"]}));
+
+        let citations = crate::search::occurrences("Text\n[@A.2020]");
+        let json: serde_json::Value = serde_json::from_str(&report_json(&report, Some(&citations)).unwrap()).unwrap();
+        assert_eq!(json["citations"], serde_json::json!([{"key": "A.2020", "line": 2, "column": 2, "start": 6, "end": 13, "footnote": false}]));
+
+        let schema: serde_json::Value = serde_json::from_str(REPORT_SCHEMA).unwrap();
+        assert_eq!(schema["properties"]["schema_version"]["const"], SCHEMA_VERSION);
+        let fields: Vec<&str> = schema["required"].as_array().unwrap().iter().filter_map(|f| f.as_str()).collect();
+        assert!(fields.iter().all(|field| json.get(field).is_some()));
+    }
+
+    #[test]
+    fn test_merge_reports() {
+        let keys = |keys: &[&str]| -> Vec<String> { keys.iter().map(|k| k.to_string()).collect() };
+        let chapter1 = parse_report_json(r#"{"schema_version": 1, "cited": ["A.2020"], "uncited": ["B.2021", "C.2022"], "unknown": ["X.2000"]}"#).unwrap();
+        let chapter2 = CoverageReport { cited: keys(&["B.2021", "A.2020"]), uncited: keys(&["C.2022"]), unknown: keys(&["Y.2001", "X.2000"]) };
+        let merged = merge_reports(&[chapter1, chapter2]);
+        assert_eq!(merged.cited, keys(&["A.2020", "B.2021"]));
+        assert_eq!(merged.uncited, keys(&["C.2022"]));
+        assert_eq!(merged.unknown, keys(&["X.2000", "Y.2001"]));
+
+        assert!(parse_report_json(r#"{"schema_version": 2, "cited": [], "uncited": [], "unknown": []}"#).is_err());
+    }
+}
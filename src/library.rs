@@ -0,0 +1,338 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+
+// Bibliography entry with the metadata used for library statistics
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LibraryEntry {
+    #[serde(rename = "citation-key")]
+    pub citation_key: String,
+
+    #[serde(rename = "type", default)]
+    pub item_type: Option<String>,
+
+    #[serde(default)]
+    pub title: Option<String>,
+
+    #[serde(default)]
+    pub author: Vec<CslName>,
+
+    #[serde(default)]
+    pub editor: Vec<CslName>,
+
+    // Court of a legal case
+    #[serde(default)]
+    pub authority: Option<String>,
+
+    #[serde(default)]
+    pub language: Option<String>,
+
+    #[serde(default)]
+    pub issued: Option<CslDate>,
+
+    #[serde(default)]
+    pub accessed: Option<CslDate>,
+
+    // When the item was added to Zotero, e.g. 2024-03-04T10:00:00Z (Web API and Better BibTeX)
+    #[serde(rename = "dateAdded", default)]
+    pub date_added: Option<String>,
+
+    // Zotero collections the item is in (Better BibTeX JSON and the Web API)
+    #[serde(default)]
+    pub collections: Vec<String>,
+
+    // Files attached to the item (Better BibTeX JSON and the Web API)
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+
+    // Court system of a legal case, e.g. de, eu or us:ca
+    #[serde(default)]
+    pub jurisdiction: Option<String>,
+}
+
+// Attached file, e.g. {"title": "Full Text PDF", "path": "/papers/uwg.pdf"}; Web API attachments
+// have a contentType instead of a path
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Attachment {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(rename = "contentType", default)]
+    pub content_type: Option<String>,
+}
+
+impl Attachment {
+    pub fn is_pdf(&self) -> bool {
+        self.content_type.as_deref() == Some("application/pdf")
+            || self.path.as_deref().is_some_and(|path| path.to_lowercase().ends_with(".pdf"))
+    }
+}
+
+// CSL-JSON name, either family and given name or a literal like an institution
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CslName {
+    #[serde(default)]
+    pub family: Option<String>,
+    #[serde(default)]
+    pub given: Option<String>,
+    #[serde(default)]
+    pub literal: Option<String>,
+}
+
+impl fmt::Display for CslName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.literal, &self.family, &self.given) {
+            (Some(literal), _, _) => write!(f, "{}", literal),
+            (None, Some(family), Some(given)) => write!(f, "{}, {}", family, given),
+            (None, Some(name), None) | (None, None, Some(name)) => write!(f, "{}", name),
+            (None, None, None) => Ok(()),
+        }
+    }
+}
+
+// CSL-JSON date, e.g. {"date-parts": [["2024", 3, 4]]}; parts may be strings or numbers
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CslDate {
+    #[serde(rename = "date-parts", default)]
+    pub date_parts: Vec<Vec<Value>>,
+}
+
+fn date_part(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+impl CslDate {
+    pub fn year(&self) -> Option<i32> {
+        date_part(self.date_parts.first()?.first()?).map(|y| y as i32)
+    }
+
+    // Days since 1970-01-01, missing month or day count as the first
+    pub fn days(&self) -> Option<i64> {
+        let parts = self.date_parts.first()?;
+        let part = |i: usize| parts.get(i).and_then(date_part);
+        Some(days_from_civil(part(0)?, part(1).unwrap_or(1), part(2).unwrap_or(1)))
+    }
+}
+
+// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's days_from_civil)
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Year of the given days since 1970-01-01 (Howard Hinnant's civil_from_days)
+pub fn year_from_days(days: i64) -> i32 {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let month_from_march = (5 * doy + 2) / 153;
+    (yoe + era * 400 + i64::from(month_from_march >= 10)) as i32
+}
+
+impl LibraryEntry {
+    pub fn year(&self) -> Option<i32> {
+        self.issued.as_ref().and_then(CslDate::year)
+    }
+
+    // A plain author-year reference, e.g. Alexander, Christian (2024): UWG; editors are marked (ed.),
+    // cases go by their court. Not a CSL style, but readable without knowing the keys.
+    // None if the entry has neither names nor title.
+    pub fn reference(&self) -> Option<String> {
+        let join = |names: &[CslName]| names.iter().map(CslName::to_string).collect::<Vec<_>>().join("; ");
+        let names = if !self.author.is_empty() {
+            Some(join(&self.author))
+        } else if !self.editor.is_empty() {
+            Some(format!("{} (ed.)", join(&self.editor)))
+        } else {
+            self.authority.clone()
+        };
+        if names.is_none() && self.title.is_none() {
+            return None;
+        }
+        let year = self.year().map_or("n.d.".to_string(), |y| y.to_string());
+        let mut reference = format!("{} ({})", names.unwrap_or_default(), year).trim_start().to_string();
+        if let Some(ref title) = self.title {
+            reference = format!("{}: {}", reference, title);
+        }
+        Some(reference)
+    }
+
+    // Whether the entry is within one of the jurisdictions, also matching sub-jurisdictions (us matches us:ca).
+    // Only legal cases with a jurisdiction can be outside.
+    pub fn in_jurisdictions(&self, jurisdictions: &[String]) -> bool {
+        let Some(ref jurisdiction) = self.jurisdiction else {
+            return true;
+        };
+        let jurisdiction = jurisdiction.to_lowercase();
+        self.item_type.as_deref() != Some("legal_case")
+            || jurisdictions.iter().map(|j| j.to_lowercase()).any(|wanted| {
+                jurisdiction == wanted || jurisdiction.starts_with(&format!("{}:", wanted))
+            })
+    }
+
+    // Whether a PDF is attached, i.e. the entry can be read right away
+    pub fn has_pdf(&self) -> bool {
+        self.attachments.iter().any(Attachment::is_pdf)
+    }
+
+    // When the entry was collected: dateAdded, or else the accessed date; days since 1970-01-01
+    pub fn added_days(&self) -> Option<i64> {
+        let added = self.date_added.as_deref().and_then(|date| {
+            let mut parts = date.get(..10)?.split('-').map(|p| p.parse::<i64>().ok());
+            Some(days_from_civil(parts.next()??, parts.next()??, parts.next()??))
+        });
+        added.or_else(|| self.accessed.as_ref().and_then(CslDate::days))
+    }
+}
+
+// Malformed entries are skipped, get_citations_bibliography already warns about them
+pub fn get_library_entries(
+    bibliography: &str,
+) -> Result<Vec<LibraryEntry>, Box<dyn std::error::Error>> {
+    let entries: Vec<Value> = serde_json::from_str(bibliography)?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| serde_json::from_value(crate::with_entry_key(entry)).ok())
+        .collect())
+}
+
+// The bibliography with only the entries for which keep is true, e.g. those of one collection
+pub fn retain_entries(
+    bibliography: &str,
+    keep: impl Fn(&LibraryEntry) -> bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut entries: Vec<Value> = serde_json::from_str(bibliography)?;
+    entries.retain(|entry| {
+        serde_json::from_value::<LibraryEntry>(crate::with_entry_key(entry.clone())).is_ok_and(|entry| keep(&entry))
+    });
+    Ok(serde_json::to_string(&entries)?)
+}
+
+// Several bibliographies as one, e.g. of repeated --zotero-lib. Of entries with the same key in
+// more than one bibliography the first listed is kept; duplicates within one stay for the rules to report.
+pub fn merge_bibliographies(bibliographies: &[String]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut merged: Vec<Value> = Vec::new();
+    let mut keys = HashSet::new();
+    for bibliography in bibliographies {
+        let entries: Vec<Value> = serde_json::from_str(bibliography)?;
+        let earlier = |entry: &Value| crate::entry_key(entry).is_some_and(|key| keys.contains(key));
+        let entries: Vec<Value> = entries.into_iter().filter(|entry| !earlier(entry)).collect();
+        keys.extend(entries.iter().filter_map(crate::entry_key).map(str::to_string));
+        merged.extend(entries);
+    }
+    Ok(serde_json::to_string(&merged)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{days_from_civil, get_library_entries, merge_bibliographies, year_from_days};
+    use serde_json::Value;
+
+    #[test]
+    fn test_get_library_entries() {
+        let testdata_json = r#"[
+  {
+    "citation-key": "AGGelnhausen.2024",
+    "type": "legal_case",
+    "jurisdiction": "de",
+    "issued": {"date-parts": [["2024", 3, 4]]},
+    "attachments": [{"title": "Snapshot", "contentType": "text/html"}]
+  },
+  {
+    "citation-key": "Smith.1999",
+    "language": "en",
+    "issued": {"date-parts": [[1999]]},
+    "attachments": [{"title": "Full Text PDF", "path": "/papers/Smith.PDF"}]
+  },
+  {
+    "citation-key": "Undated.0000"
+  }
+]"#;
+        let entries = get_library_entries(testdata_json).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].year(), Some(2024));
+        assert_eq!(entries[0].item_type.as_deref(), Some("legal_case"));
+        assert_eq!(entries[0].language, None);
+        assert_eq!(entries[1].year(), Some(1999));
+        assert_eq!(entries[1].language.as_deref(), Some("en"));
+        assert_eq!(entries[2].year(), None);
+
+        assert!(entries[0].in_jurisdictions(&["DE".to_string()]));
+        assert!(!entries[0].in_jurisdictions(&["eu".to_string()]));
+        assert!(entries[1].in_jurisdictions(&["eu".to_string()]));
+
+        assert!(!entries[0].has_pdf());
+        assert!(entries[1].has_pdf());
+        assert!(!entries[2].has_pdf());
+    }
+
+    #[test]
+    fn test_merge_bibliographies() {
+        let merged = merge_bibliographies(&[
+            r#"[{"citation-key": "A", "title": "First"}, {"id": "B"}, {"citation-key": "B"}]"#.to_string(),
+            r#"[{"id": "A", "title": "Second"}, {"citation-key": "C"}, {"title": "No key"}]"#.to_string(),
+        ])
+        .unwrap();
+        let entries: Vec<Value> = serde_json::from_str(&merged).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0]["title"], "First");
+        assert_eq!(entries[3]["citation-key"], "C");
+        assert_eq!(entries[4]["title"], "No key");
+    }
+
+    #[test]
+    fn test_reference() {
+        let entries = get_library_entries(
+            r#"[
+  {"citation-key": "A", "title": "UWG", "issued": {"date-parts": [[2024]]},
+   "author": [{"family": "Alexander", "given": "Christian"}, {"literal": "Bundesamt für Justiz"}]},
+  {"citation-key": "B", "title": "Handbuch", "editor": [{"family": "Köhler", "given": null}]},
+  {"citation-key": "C", "type": "legal_case", "authority": "BGH", "issued": {"date-parts": [[2010]]}},
+  {"citation-key": "D"}
+]"#,
+        )
+        .unwrap();
+        let references: Vec<Option<String>> = entries.iter().map(|e| e.reference()).collect();
+        assert_eq!(
+            references,
+            vec![
+                Some("Alexander, Christian; Bundesamt für Justiz (2024): UWG".to_string()),
+                Some("Köhler (ed.) (n.d.): Handbuch".to_string()),
+                Some("BGH (2010)".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_added_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2024, 3, 1), 19_783);
+        assert_eq!(year_from_days(19_783), 2024);
+        assert_eq!(year_from_days(days_from_civil(2023, 12, 31)), 2023);
+
+        let entries = get_library_entries(
+            r#"[
+  {"citation-key": "A", "dateAdded": "2024-03-01T09:15:00Z", "accessed": {"date-parts": [[2020, 1, 1]]}},
+  {"citation-key": "B", "accessed": {"date-parts": [["2024", "3"]]}},
+  {"citation-key": "C"}
+]"#,
+        )
+        .unwrap();
+        assert_eq!(entries[0].added_days(), Some(19_783));
+        assert_eq!(entries[1].added_days(), Some(19_783));
+        assert_eq!(entries[2].added_days(), None);
+    }
+}
@@ -1,529 +1,1931 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_file::Input;
-use regex::Regex;
-use serde::Deserialize;
-use std::collections::HashSet;
-use std::fmt;
-use std::io;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::Read;
-use yaml_front_matter::{YamlFrontMatter};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use zoterocoverage::extract::{CommandExtractor, Extractor};
+use zoterocoverage::syntax::{Registry, Syntax};
+use zoterocoverage::{
+    Citations, CoverageReport, REPORT_SCHEMA, annotate, badge_json, better_bibtex, chunks, citeproc, cluster, compare_citations, config, coverage_report, encoding, exclude,
+    front_matter, get_citations_bibliography_skipping, graph, include, keys, library, merge_reports, parse_report_json, porcelain, rename, report_json, rules, safe_write, search, source, stats, suppress,
+    zotero_api,
+};
+#[cfg(feature = "scripting")]
+use zoterocoverage::scripting::Hooks;
+
+mod credentials;
+mod messages;
+mod on_complete;
+mod update;
+
+use messages::msg;
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
     //path to document, md format (or any plain text format)
-    #[clap(short, long)]
-    document: Input,
+    #[clap(short, long, required = true)]
+    document: Option<Input>,
 
-    //path to zotero_lib, JSON format; optional
+    //path to zotero_lib, JSON format, - for stdin or zotero:users/<id> / zotero:groups/<id> for the Web API; optional.
+    //repeat to check against several libraries, of keys in more than one the first listed entry is used
     #[clap(short, long)]
-    zotero_lib: Option<Input>,
+    zotero_lib: Vec<source::BibliographySource>,
 
-    #[clap(short, long)]
+    //path to the config file; defaults to ./zoterocoverage.toml, then the user config directory
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+
+    //profile from the config file selecting library and credentials; defaults to default_profile
+    #[clap(long, global = true, env = "ZOTEROCOVERAGE_PROFILE")]
+    profile: Option<String>,
+
+    //Zotero Web API key, needed for private libraries; defaults to the key stored with auth login
+    #[clap(long, env = "ZOTERO_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+
+    //timeout in seconds for each request to network-backed sources
+    #[clap(long, default_value_t = 30)]
+    timeout: u64,
+
+    //how often failed requests to network-backed sources are retried, with exponential backoff
+    #[clap(long, default_value_t = 3)]
+    retries: u32,
+
+    //proxy for network-backed sources, e.g. http://proxy:8080; defaults to HTTPS_PROXY / HTTP_PROXY
+    #[clap(long)]
+    proxy: Option<String>,
+
+    //how many requests and library loads run in parallel at most; defaults to one per CPU
+    #[clap(short, long, global = true)]
+    jobs: Option<NonZeroUsize>,
+
+    #[clap(short, long, conflicts_with_all = ["porcelain", "summary"])]
     verbose: bool,
 
+    //path to write the co-citation graph to, GraphML if it ends in .graphml, DOT otherwise; optional
+    #[clap(long)]
+    graph: Option<PathBuf>,
+
+    //WebAssembly plugin used instead of the built-in extractor to find citation keys
+    #[clap(long, conflicts_with = "extractor_cmd")]
+    plugin: Option<PathBuf>,
+
+    //shell command printing the cited keys of the document piped to it, one per line (or JSON objects with a key field)
+    #[clap(long)]
+    extractor_cmd: Option<String>,
+
+    //citation syntax of the document: markdown, latex, typst, org, asciidoc, notebook, multimarkdown, djot or aux; defaults to the file extension, else markdown (e.g. for stdin)
+    #[clap(long, conflicts_with_all = ["plugin", "extractor_cmd"])]
+    syntax: Option<Syntax>,
+
+    //report citations per 1000 words, overall and per section
+    #[clap(long, conflicts_with_all = ["porcelain", "summary"])]
+    density: bool,
+
+    //list uncited entries by issued year, flagging those older than --max-age
+    #[clap(long, conflicts_with_all = ["porcelain", "summary"])]
+    age: bool,
+
+    //only check the entries of this Zotero collection (Better BibTeX JSON or Web API libraries)
+    #[clap(long)]
+    collection: Option<String>,
+
+    //only check the entries found by this saved search, run by Zotero's local API (Zotero 7 running, API enabled)
+    #[clap(long)]
+    saved_search: Option<String>,
+
+    //table of cited/total entries per Zotero collection (Better BibTeX JSON or Web API libraries)
+    #[clap(long, conflicts_with_all = ["porcelain", "summary"])]
+    by_collection: bool,
+
+    //age in years from which uncited entries are flagged by --age
+    #[clap(long, default_value_t = 10)]
+    max_age: i32,
+
+    //only list uncited entries added (dateAdded, else accessed) within this time, e.g. 30d or 2w
+    #[clap(long, value_parser = parse_days)]
+    added_within: Option<i64>,
+
+    //order of the uncited list: added (oldest collected first) or key; defaults to the bibliography order
+    #[clap(long, value_enum)]
+    sort: Option<SortOrder>,
+
+    //only check coverage of legal cases from these jurisdictions (CSL jurisdiction, e.g. de or eu); other cases are not listed as uncited
+    #[clap(long, value_delimiter = ',')]
+    jurisdiction: Vec<String>,
+
+    //flag entries cited more than this many times (rule over-cited)
+    #[clap(long)]
+    max_cites_per_entry: Option<usize>,
+
+    //keys that must be cited (rule required, error by default): a file with one key per line, or
+    //collection:NAME for the entries of a Zotero collection
+    #[clap(long)]
+    required: Option<String>,
+
+    //only list uncited entries with a PDF attached (Better BibTeX JSON or Web API libraries)
+    #[clap(long)]
+    only_with_pdf: bool,
+
+    //list uncited entries as references in the document's csl style (with the key), formatted by pandoc
+    #[clap(long, conflicts_with_all = ["porcelain", "summary"])]
+    references: bool,
+
+    //German legal writing: uncited case law and commentaries listed separately, Rn. locators
+    #[clap(long)]
+    legal: bool,
+
+    //don't count citations in block quotes, e.g. a quoted footnote apparatus of another author
+    #[clap(long)]
+    exclude_blockquotes: bool,
+
+    //don't count citations in image alt text (figure captions) and figure attributes
+    #[clap(long)]
+    exclude_figures: bool,
+
+    //YAML header fields whose citations count, e.g. abstract,acknowledgements; the rest of the header is skipped
+    #[clap(long, value_delimiter = ',')]
+    include_metadata_fields: Vec<String>,
+
+    //before checking, have Better BibTeX in Zotero export the bibliography file afresh (pull_export in the config selects the collection)
+    #[clap(long)]
+    refresh_export: bool,
+
+    //scan the document memory-mapped in chunks instead of reading it whole, for very large text exports
+    #[clap(long, conflicts_with_all = ["graph", "density"])]
+    chunked: bool,
+
+    //stable tab-separated output for scripts instead of the report: status, key, file, line
+    #[clap(long, global = true)]
+    porcelain: bool,
+
+    //only print cited/total (percent) and the number of unknown keys on one line, e.g. for shell prompts
+    #[clap(long, global = true, conflicts_with = "porcelain")]
+    summary: bool,
+
+    //print how long reading, front matter parsing, bibliography loading, extraction and diffing took (to stderr)
+    #[clap(long, global = true)]
+    timings: bool,
+
+    //command run after the check with the JSON report on stdin, or with {json} replaced by the path of a temporary file holding it
+    #[clap(long, global = true)]
+    on_complete: Option<String>,
+
+    //treat every warning as an error: warn-level rules, skipped malformed bibliography entries and
+    //suppressed entries all fail the check, e.g. for the final check before submission
+    #[clap(long, global = true)]
+    strict: bool,
+
+    //write the coverage as shields.io endpoint JSON to this file, for a badge served from the repository
+    #[clap(long, global = true)]
+    badge_endpoint: Option<PathBuf>,
+
+    //language of the report, en or de; defaults to the locale (LC_ALL, LC_MESSAGES, LANG)
+    #[clap(long, global = true, value_enum, env = "ZOTEROCOVERAGE_LANG")]
+    lang: Option<messages::Lang>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+}
+
+impl Args {
+    fn exclusions(&self) -> exclude::Exclusions {
+        exclude::Exclusions {
+            blockquotes: self.exclude_blockquotes,
+            figures: self.exclude_figures,
+        }
+    }
+
+    // The cited keys of a document. With --include-metadata-fields, the YAML header only counts
+    // with the strings of these fields, which pandoc renders, and not as plain text.
+    fn extract(&self, extractor: &dyn Extractor, document: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if self.include_metadata_fields.is_empty() {
+            return extractor.extract(document);
+        }
+        let mut keys = Vec::new();
+        for text in front_matter::field_strings(document, &self.include_metadata_fields)? {
+            keys.extend(extractor.extract(&text)?);
+        }
+        keys.extend(extractor.extract(&exclude::without_metadata(document))?);
+        Ok(keys)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum SortOrder {
+    Added,
+    Key,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum StatsOutput {
+    Text,
+    Csv,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    // Print statistics about the document instead of the coverage report
+    Stats {
+        //also summarize the library: entries per year, type and language, cited vs uncited
+        #[clap(long)]
+        library: bool,
+
+        //text, or csv with per-section densities and per-key citation counts for spreadsheets
+        #[clap(long, value_enum, default_value_t = StatsOutput::Text)]
+        output: StatsOutput,
+
+        //bar charts of the citations per section and, with --library, of cited vs uncited entries per year
+        #[clap(long, conflicts_with = "output")]
+        histogram: bool,
+    },
+
+    // Explain why a key is reported as cited, uncited or unknown for the document
+    Explain {
+        key: String,
+    },
+
+    // Check every [[mappings]] entry of the project manifest (config file) against its own library
+    Project,
+
+    // Check independent projects of the config file ([projects.<name>]), each against its own library
+    Check {
+        //names of the projects to check
+        #[clap(required_unless_present = "all")]
+        projects: Vec<String>,
+
+        //check every project and print a combined summary
+        #[clap(long, conflicts_with = "projects")]
+        all: bool,
+    },
+
+    // Rename a citation key in the documents (and the bibliography); shows a unified diff unless --write is given
+    Rename {
+        old: String,
+        new: String,
+
+        //documents to rewrite
+        #[clap(required = true)]
+        documents: Vec<PathBuf>,
+
+        //also rename the entry in this CSL-JSON bibliography
+        #[clap(long)]
+        bibliography: Option<PathBuf>,
+
+        //write the changes instead of printing them
+        #[clap(long)]
+        write: bool,
+
+        //print the changes as a unified diff, the default without --write
+        #[clap(long, conflicts_with = "write")]
+        dry_run: bool,
+    },
+
+    // Rewrite stale keys in the documents using the [aliases] map of the config file; keeps a .bak backup
+    Fix {
+        //documents to fix; defaults to the documents of the project manifest
+        documents: Vec<PathBuf>,
+
+        //print the changes as a unified diff instead of writing them
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    // Print every location where a key is cited, with surrounding lines
+    Search {
+        key: String,
+
+        //documents to search; defaults to the documents of the project manifest
+        documents: Vec<PathBuf>,
+
+        //lines of context around each citation
+        #[clap(short = 'C', long, default_value_t = 1)]
+        context: usize,
+    },
+
+    // Compare the citations of two documents, e.g. two versions of a chapter: keys cited only in old (-) or only in new (+)
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+    },
+
+    // Combine JSON reports of separate runs (e.g. per-chapter CI jobs, written with --on-complete) into one;
+    // an entry counts as cited if any run cited it
+    Merge {
+        //JSON reports, e.g. results/*.json
+        #[clap(required = true)]
+        reports: Vec<PathBuf>,
+
+        //print the merged report as JSON instead
+        #[clap(long)]
+        json: bool,
+    },
+
+    // Write a copy of the document with the findings as <!-- ZC: ... --> comments at their lines and the
+    // uncited entries appended, e.g. for co-authors who don't run the check
+    Annotate {
+        //path of the annotated copy
+        #[clap(long)]
+        out: PathBuf,
+
+        //print the annotations as a unified diff against the document instead of writing the copy
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    // Print the JSON Schema of the JSON report (C API, WebAssembly)
+    OutputSchema,
+
+    // Replace this binary with the latest GitHub release, after verifying its SHA-256 checksum
+    SelfUpdate {
+        //only report whether a newer release is available
+        #[clap(long)]
+        check: bool,
+    },
+
+    // Manage the Zotero API key stored in the system keyring
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AuthAction {
+    // Store the API key (from --api-key / ZOTERO_API_KEY, or prompted for) in the keyring
+    Login,
+    // Remove the stored API key
+    Logout,
+}
+
+#[cfg(feature = "wasm-plugins")]
+fn load_plugin(path: &Path) -> Result<Box<dyn Extractor>, Box<dyn std::error::Error>> {
+    Ok(Box::new(zoterocoverage::extract::wasm_plugin::WasmExtractor::load(path)?))
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+fn load_plugin(path: &Path) -> Result<Box<dyn Extractor>, Box<dyn std::error::Error>> {
+    Err(format!("cannot load {}, built without the wasm-plugins feature", path.display()).into())
+}
+
+// Stand-in for scripting::Hooks without the scripting feature; a configured script is an error
+#[cfg(not(feature = "scripting"))]
+enum Hooks {}
+
+#[cfg(not(feature = "scripting"))]
+impl Hooks {
+    fn load(path: &Path) -> Result<Hooks, Box<dyn std::error::Error>> {
+        Err(format!("cannot run {}, built without the scripting feature", path.display()).into())
+    }
+
+    fn apply_keys(&self, _: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match *self {}
+    }
+
+    fn apply_bibliography(&self, _: Vec<Citations>) -> Result<Vec<Citations>, Box<dyn std::error::Error>> {
+        match *self {}
+    }
+
+    fn post_report(&self, _: CoverageReport) -> Result<CoverageReport, Box<dyn std::error::Error>> {
+        match *self {}
+    }
 }
 
-#[derive(Deserialize, Debug, Clone, Hash, PartialEq)]
-struct Citations {
-    #[serde(rename = "citation-key")]
-    pub citation_key: String,
+// Coverage of the cited keys against the bibliography, with the script hooks applied to the keys in place
+fn check_keys(
+    citations_document: &mut Vec<String>,
+    citations_bibliography: &mut Vec<Citations>,
+    hooks: Option<&Hooks>,
+) -> Result<CoverageReport, Box<dyn std::error::Error>> {
+    if let Some(hooks) = hooks {
+        *citations_bibliography = hooks.apply_bibliography(std::mem::take(citations_bibliography))?;
+        *citations_document = hooks.apply_keys(std::mem::take(citations_document))?;
+    }
+    let report = coverage_report(citations_document.iter().map(String::as_str).collect(),
+                                 citations_bibliography.clone())?;
+    match hooks {
+        Some(hooks) => hooks.post_report(report),
+        None => Ok(report),
+    }
 }
 
-// Get Metadata from markdown document for Library
-// Bibliography field contains path
-#[derive(Deserialize)]
-struct Metadata {
-    bibliography: String
+// How uncited entries are listed; the library entries are only read when needed
+struct Listing {
+    entries: Vec<library::LibraryEntry>,
+    // Case law, commentaries and literature separately (--legal)
+    legal: bool,
+    // The uncited entries as references rather than keys (--references), by key
+    references: BTreeMap<String, String>,
 }
 
-impl fmt::Display for Citations {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.citation_key)
+impl Listing {
+    fn new(
+        legal: bool,
+        style: Option<&citeproc::Style>,
+        bibliography_json: &str,
+        report: &CoverageReport,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Entries with a PDF attached are marked, if the bibliography has attachments at all
+        let entries = if legal || bibliography_json.contains("\"attachments\"") {
+            library::get_library_entries(bibliography_json)?
+        } else {
+            Vec::new()
+        };
+        let references = match style {
+            Some(style) => citeproc::render(bibliography_json, &report.uncited, style)?,
+            None => BTreeMap::new(),
+        };
+        Ok(Listing { entries, legal, references })
+    }
+
+    fn label(&self, key: &str) -> String {
+        let entry = self.entries.iter().find(|e| e.citation_key == key);
+        let label = match self.references.get(key) {
+            Some(reference) => format!("{} [{}]", reference, key),
+            None => key.to_string(),
+        };
+        match entry {
+            Some(entry) if entry.has_pdf() => msg!("uncited-with-pdf", entry = label),
+            _ => label,
+        }
+    }
+}
+
+// The csl style and lang of the document's front matter for --references. A relative style next
+// to the document is found from any working directory, others are left to pandoc (its data directory).
+fn reference_style(path: Option<&Path>, document: &str) -> citeproc::Style {
+    let mut style: citeproc::Style = front_matter::parse(document).unwrap_or_default();
+    if let Some(ref mut csl) = style.csl
+        && let Some(next_to_document) = path.and_then(Path::parent).map(|directory| directory.join(&*csl))
+        && Path::new(csl).is_relative()
+        && next_to_document.is_file()
+    {
+        *csl = next_to_document.display().to_string();
+    }
+    style
+}
+
+// The coverage report and the findings of all enabled rules; returns the number of error-level findings
+fn print_findings(report: &CoverageReport, findings: &[rules::Finding], rules: &rules::Rules, listing: &Listing) -> usize {
+    if rules.severity(rules::Rule::Uncited) != rules::Severity::Off {
+        print_uncited(report, listing);
+    }
+    for finding in findings {
+        let label = match rules.severity(finding.rule) {
+            rules::Severity::Off => continue,
+            rules::Severity::Warn => msg!("severity-warning"),
+            rules::Severity::Error => msg!("severity-error"),
+        };
+        // Uncited entries are already listed above
+        if finding.rule != rules::Rule::Uncited {
+            println!("{}[{}]: {}", label, finding.rule, messages::finding(finding));
+        }
     }
+    error_count(findings, rules)
 }
 
-fn get_citations_bibliography(
-    bibliography: &str,
-) -> Result<Vec<Citations>, Box<dyn std::error::Error>> {
-    let v: Vec<Citations> = serde_json::from_str(bibliography)?;
-    Ok(v)
+// Findings at error level, which make the run fail
+fn error_count(findings: &[rules::Finding], rules: &rules::Rules) -> usize {
+    findings.iter().filter(|finding| rules.severity(finding.rule) == rules::Severity::Error).count()
 }
 
-fn get_citations_document(document: &str) -> Result<Vec<&str>, Box<dyn std::error::Error>> {
-    let re = Regex::new(r"@(?<key>\w+\.\d{4}\w?)").unwrap();
-    let md_citations: Vec<&str> = re
-        .captures_iter(document)
-        .map(|caps| caps.name("key").unwrap().as_str())
+fn print_porcelain(report: &CoverageReport, suppressed: &[suppress::Suppression], documents: &[(String, &str)], bibliography: &str) {
+    for line in porcelain::lines(report, suppressed, documents, bibliography) {
+        println!("{}", line);
+    }
+}
+
+// The --badge-endpoint file; in project and check the coverage of all mappings together
+fn write_badge(path: Option<&Path>, report: &CoverageReport, suppressed: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+    fs::write(path, badge_json(report, suppressed)?).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+// Hand the JSON report to the --on-complete command; a failing command is only a warning,
+// the exit status stays that of the check
+fn on_complete(command: Option<&str>, report: &CoverageReport, citations: Option<&[search::Occurrence]>) {
+    let Some(command) = command else {
+        return;
+    };
+    let result = report_json(report, citations).map_err(Into::into).and_then(|json| on_complete::run(command, &json));
+    if let Err(e) = result {
+        eprintln!("{}", msg!("on-complete-failed", command = command, error = e.to_string()));
+    }
+}
+
+// Days since 1970-01-01 (UTC)
+fn today() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| (d.as_secs() / 86_400) as i64)
+}
+
+// Durations like 30d or 2w, in days
+fn parse_days(s: &str) -> Result<i64, String> {
+    let (number, unit) = match s.strip_suffix('d') {
+        Some(days) => (days, 1),
+        None => match s.strip_suffix('w') {
+            Some(weeks) => (weeks, 7),
+            None => (s, 1),
+        },
+    };
+    number
+        .parse::<i64>()
+        .map(|n| n * unit)
+        .map_err(|_| format!("invalid duration {}, expected e.g. 30d or 2w", s))
+}
+
+fn print_age(by_year: &std::collections::BTreeMap<Option<i32>, Vec<String>>, max_age: i32) {
+    let cutoff = library::year_from_days(today()) - max_age;
+    println!("{}", msg!("age-heading"));
+    let mut old = 0;
+    for (year, keys) in by_year {
+        let label = year.map_or(msg!("age-undated"), |y| y.to_string());
+        if year.is_some_and(|y| y < cutoff) {
+            old += keys.len();
+            println!("  {}", msg!("age-old-year", year = label, keys = keys.join(", "), max_age = max_age));
+        } else {
+            println!("  {}: {}", label, keys.join(", "));
+        }
+    }
+    if old > 0 {
+        println!("{}", msg!("age-old-total", count = old, max_age = max_age));
+    }
+}
+
+fn print_suppressed(suppressed: &[suppress::Suppression]) {
+    if suppressed.is_empty() {
+        return;
+    }
+    println!("{}", msg!("suppressed-heading", count = suppressed.len()));
+    for suppression in suppressed {
+        match suppression.reason {
+            Some(ref reason) => println!("{} ({})", suppression.key, reason),
+            None => println!("{}", suppression.key),
+        }
+    }
+}
+
+fn print_uncited(report: &CoverageReport, listing: &Listing) {
+    if report.uncited.is_empty() {
+        println!("{}", msg!("all-cited"));
+        return;
+    }
+    println!("{}", msg!("uncited-heading", count = report.uncited.len()));
+    if listing.legal {
+        for (group, keys) in stats::uncited_by_legal_group(&listing.entries, &report.uncited) {
+            println!("{}:", legal_group_label(&group));
+            for key in keys {
+                println!("  {}", listing.label(&key));
+            }
+        }
+    } else {
+        for key in &report.uncited {
+            println!("{}", listing.label(key));
+        }
+    }
+}
+
+fn legal_group_label(group: &stats::LegalGroup) -> String {
+    match group {
+        stats::LegalGroup::CaseLaw(Some(jurisdiction)) => msg!("legal-case-law-in", jurisdiction = jurisdiction.as_str()),
+        stats::LegalGroup::CaseLaw(None) => msg!("legal-case-law"),
+        stats::LegalGroup::Commentaries => msg!("legal-commentaries"),
+        stats::LegalGroup::Literature => msg!("legal-literature"),
+    }
+}
+
+// Outcome of checking a project manifest
+struct Checked {
+    // Number of error-level findings
+    errors: usize,
+    // Report and number of suppressed entries per mapping
+    reports: Vec<(String, CoverageReport, usize)>,
+}
+
+fn run_project(
+    config: &config::Config,
+    extractor: &dyn Extractor,
+    hooks: Option<&Hooks>,
+    profile: Option<(&str, &config::Profile)>,
+    network: &source::NetworkOptions,
+    args: &Args,
+    timings: &mut Timings,
+) -> Result<Checked, Box<dyn std::error::Error>> {
+    // All libraries load at once, which saves waiting on the Zotero API for each mapping
+    let libraries: Vec<source::BibliographySource> = config.mappings.iter().flat_map(|m| m.libraries.iter().cloned()).collect();
+    if args.verbose {
+        for library in &libraries {
+            println!("Reading bibliography from {}", library);
+        }
+    }
+    let api_key = api_key(args, profile, &libraries);
+    let bibliographies = timings.time("bibliographies".to_string(), || {
+        source::BibliographySource::read_all(&libraries, api_key.as_deref(), network, config.mods_key(), args.verbose)
+    })?;
+    let mut bibliographies = bibliographies.into_iter();
+    let bibliographies: Vec<Vec<source::Loaded>> = config.mappings.iter()
+        .map(|mapping| bibliographies.by_ref().take(mapping.libraries.len()).collect())
         .collect();
 
-    Ok(md_citations)
+    let key_pattern = config.key_pattern()?;
+    let mut errors = 0;
+    let mut reports = Vec::new();
+    let mut mapping_citations = Vec::new();
+    for (mapping, loaded) in config.mappings.iter().zip(bibliographies) {
+        let paths = mapping.document_paths()?;
+        if paths.is_empty() {
+            eprintln!("{}", msg!("project-no-documents", patterns = mapping.documents.join(", ")));
+        }
+        let mut citations_document = Vec::new();
+        let mut documents = Vec::new();
+        let mut citations = Vec::new();
+        let mut suppressions = suppress::from_config(&config.allow_uncited);
+        // Documents of the mapping that are also included by another one count once
+        let mut visited: HashSet<PathBuf> = paths.iter().map(|path| include::identity(path)).collect();
+        let registry = Registry::new(&config.extensions);
+        for path in &paths {
+            if args.verbose {
+                println!("Reading document from {}", path.display());
+            }
+            let document = timings.time(format!("reading {}", path.display()), || encoding::read_to_string(path))
+                .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+            let included = read_included(path, &document, syntax_for(path, &registry, args), &mut visited, args, &mut suppressions)?;
+            suppressions.extend(suppress::parse_suppressions(&document));
+            let document = args.exclusions().apply(suppress::without_directives(&document));
+            for (path, document) in std::iter::once((path.clone(), document)).chain(included) {
+                let extractor = extractor_for(&path, extractor, &registry, args);
+                citations_document.extend(timings.time(format!("extraction {}", path.display()), || args.extract(extractor, &document))?);
+                citations.extend(report_citations(Some(&path), &document, syntax_for(&path, &registry, args)));
+                documents.push((path, document));
+            }
+        }
+        let (bibliography_json, conflicts) = merge_loaded(&mapping.libraries, loaded)?;
+        let (bibliography_json, outside) = scoped(bibliography_json, args, network)?;
+        let (mut citations_bibliography, skipped) = get_citations_bibliography_skipping(&bibliography_json)?;
+        let mut report = timings.time(format!("diffing {}", mapping.label()), || {
+            check_keys(&mut citations_document, &mut citations_bibliography, hooks)
+        })?;
+        report.unknown.retain(|key| !outside.contains(key));
+        let suppressed = suppress::apply(&mut report, &suppressions);
+        filter_uncited(&mut report, &bibliography_json, args)?;
+        let mut findings = rules::check(&report, &citations_document, &citations_bibliography,
+                                        &bibliography_json, &config.aliases)?;
+        findings.extend(conflicts);
+
+        if !args.porcelain && !args.summary {
+            println!("{}", msg!("project-mapping", mapping = mapping.label(), documents = paths.len(),
+                                cited = report.cited.len(),
+                                total = report.cited.len() + report.uncited.len() + suppressed.len()));
+        }
+        for (path, document) in &documents {
+            let mut per_document = timings.time(format!("front matter checks {}", path.display()), || rules::check_metadata(document, Some(path)));
+            let repeated = pandoc_repeated(document, syntax_for(path, &registry, args), config.legal);
+            per_document.extend(rules::check_repeated_citations(&repeated));
+            for mut finding in per_document {
+                finding.document = Some(path.display().to_string());
+                findings.push(finding);
+            }
+        }
+        for library in &mapping.libraries {
+            if let source::BibliographySource::File(library) = library {
+                findings.extend(paths.iter().filter_map(|path| {
+                    rules::check_export_age(path, library, config.stale_export_window())
+                }));
+            }
+        }
+        if let Some(ref pattern) = key_pattern {
+            findings.extend(rules::check_key_pattern(&citations_bibliography, pattern));
+        }
+        if let Some(max) = args.max_cites_per_entry {
+            findings.extend(rules::check_citation_counts(&citations_document, max));
+        }
+        if let Some(ref required) = args.required {
+            findings.extend(rules::check_required(&required_keys(required, &bibliography_json)?, &citations_document));
+        }
+        if !config.require_locator.is_empty() {
+            let entries = library::get_library_entries(&bibliography_json)?;
+            for (path, document) in &documents {
+                let cites = pandoc_cites(document, syntax_for(path, &registry, args), config.legal);
+                for mut finding in rules::check_locators(&cites, &entries, &config.require_locator) {
+                    finding.document = Some(path.display().to_string());
+                    findings.push(finding);
+                }
+            }
+        }
+        rules::sort(&mut findings);
+        if args.summary {
+            println!("{}: {}", mapping.label(), report.summary(suppressed.len()));
+            errors += error_count(&findings, &config.rules);
+        } else if args.porcelain {
+            let documents: Vec<(String, &str)> = documents.iter()
+                .map(|(path, document)| (path.display().to_string(), document.as_str()))
+                .collect();
+            let libraries: Vec<String> = mapping.libraries.iter().map(ToString::to_string).collect();
+            print_porcelain(&report, &suppressed, &documents, &libraries.join(", "));
+            errors += error_count(&findings, &config.rules);
+        } else {
+            let style = args.references.then(|| {
+                documents.iter().map(|(path, document)| reference_style(Some(path), document))
+                    .find(|style| style.csl.is_some())
+                    .unwrap_or_default()
+            });
+            let listing = Listing::new(config.legal, style.as_ref(), &bibliography_json, &report)?;
+            errors += print_findings(&report, &findings, &config.rules, &listing);
+            print_suppressed(&suppressed);
+            // Density over the documents of the mapping as one text, chapter after chapter
+            let text = args.density.then(|| {
+                documents.iter()
+                    .map(|(path, document)| syntax_for(path, &registry, args).pandoc_text(document).map_or_else(|| document.clone(), Cow::into_owned))
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            });
+            print_statistics(&report, &bibliography_json, text.as_deref(), args)?;
+        }
+        errors += strict_errors(args.strict, skipped, suppressed.len());
+        reports.push((mapping.label(), report, suppressed.len()));
+        mapping_citations.push(citations);
+    }
+    // Once per mapping, after the whole report is printed
+    for ((_, report, _), citations) in reports.iter().zip(&mapping_citations) {
+        on_complete(args.on_complete.as_deref(), report, Some(citations));
+    }
+    Ok(Checked { errors, reports })
+}
+
+// One bibliography of the loaded sources, merged in order if there are several, and the
+// conflicting-entry findings of the merge
+fn merge_loaded(
+    libraries: &[source::BibliographySource],
+    loaded: Vec<source::Loaded>,
+) -> Result<(String, Vec<rules::Finding>), Box<dyn std::error::Error>> {
+    let bibliographies = loaded.into_iter().collect::<Result<Vec<_>, _>>()?;
+    match <[String; 1]>::try_from(bibliographies) {
+        Ok([bibliography]) => Ok((bibliography, Vec::new())),
+        Err(bibliographies) => {
+            let named: Vec<(String, &str)> = libraries.iter().map(ToString::to_string)
+                .zip(bibliographies.iter().map(String::as_str))
+                .collect();
+            Ok((library::merge_bibliographies(&bibliographies)?, rules::check_conflicting_entries(&named)?))
+        }
+    }
+}
+
+// Narrow and order the uncited entries as --added-within, --jurisdiction, --only-with-pdf and --sort ask
+fn filter_uncited(report: &mut CoverageReport, bibliography_json: &str, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(days) = args.added_within {
+        if args.verbose {
+            println!("Only listing uncited entries added in the last {} days", days);
+        }
+        let since = today() - days;
+        let entries = library::get_library_entries(bibliography_json)?;
+        report.uncited.retain(|key| {
+            entries.iter().any(|e| &e.citation_key == key && e.added_days().is_some_and(|added| added >= since))
+        });
+    }
+    if !args.jurisdiction.is_empty() {
+        let entries = library::get_library_entries(bibliography_json)?;
+        report.uncited.retain(|key| {
+            entries.iter().find(|e| &e.citation_key == key).is_none_or(|e| e.in_jurisdictions(&args.jurisdiction))
+        });
+    }
+    if args.only_with_pdf {
+        let entries = library::get_library_entries(bibliography_json)?;
+        // Otherwise every entry would be dropped without a hint why
+        if entries.iter().all(|e| e.attachments.is_empty()) {
+            return Err("--only-with-pdf: the bibliography has no attachments \
+                        (use a Better BibTeX JSON export or the Web API)".into());
+        }
+        report.uncited.retain(|key| entries.iter().any(|e| &e.citation_key == key && e.has_pdf()));
+    }
+    match args.sort {
+        Some(SortOrder::Key) => messages::sort(&mut report.uncited),
+        Some(SortOrder::Added) => {
+            let entries = library::get_library_entries(bibliography_json)?;
+            let added: HashMap<&str, i64> = entries.iter()
+                .filter_map(|e| Some((e.citation_key.as_str(), e.added_days()?)))
+                .collect();
+            // Undated entries last
+            report.uncited.sort_by_key(|key| added.get(key.as_str()).copied().unwrap_or(i64::MAX));
+        }
+        None => {}
+    }
+    Ok(())
 }
 
-fn get_citation_difference(
-    document: Vec<&str>,
-    json: Vec<Citations>,
-) -> Result<Vec<Citations>, Box<dyn std::error::Error>> {
-    let document_set: HashSet<&str> = HashSet::from_iter(document);
+// The statistics after the report: --age, --density (of this text) and --by-collection
+fn print_statistics(report: &CoverageReport, bibliography_json: &str, density_text: Option<&str>, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    if args.age {
+        let entries = library::get_library_entries(bibliography_json)?;
+        print_age(&stats::uncited_by_year(&entries, &report.uncited), args.max_age);
+    }
+    if let Some(text) = density_text {
+        print_density(text);
+    }
+    if args.by_collection {
+        let entries = library::get_library_entries(bibliography_json)?;
+        let cited: HashSet<&str> = report.cited.iter().map(String::as_str).collect();
+        print_collections(&stats::coverage_by_collection(&entries, &cited));
+    }
+    Ok(())
+}
+
+// Coverage per project and of all of them together; with --summary only the total
+fn print_workspace_summary(reports: &[(String, CoverageReport, usize)], summary_only: bool) {
+    if !summary_only {
+        println!("{}", msg!("workspace-summary"));
+        for (label, report, report_suppressed) in reports {
+            println!("  {}: {}", label, report.summary(*report_suppressed));
+        }
+    }
+    let (total, suppressed) = total_report(reports);
+    let indent = if summary_only { "" } else { "  " };
+    println!("{}{}: {}", indent, msg!("workspace-total"), total.summary(suppressed));
+}
+
+// The reports of several mappings or projects added up, and their suppressed entries
+fn total_report(reports: &[(String, CoverageReport, usize)]) -> (CoverageReport, usize) {
+    let mut total = CoverageReport { cited: Vec::new(), uncited: Vec::new(), unknown: Vec::new() };
+    let mut suppressed = 0;
+    for (_, report, report_suppressed) in reports {
+        total.cited.extend(report.cited.iter().cloned());
+        total.uncited.extend(report.uncited.iter().cloned());
+        total.unknown.extend(report.unknown.iter().cloned());
+        suppressed += report_suppressed;
+    }
+    (total, suppressed)
+}
+
+fn print_density(document: &str) {
+    let sections = stats::citation_density(document);
+    let total = stats::total_density(&sections);
+    println!("{}", msg!("density-total", density = messages::decimal(total.per_thousand_words()),
+                        citations = total.citations, words = total.words));
+    for section in sections.iter().filter(|s| !s.heading.is_empty()) {
+        println!("  {}", msg!("density-section", heading = section.heading.as_str(),
+                              density = messages::decimal(section.per_thousand_words()),
+                              citations = section.citations, words = section.words));
+    }
+}
 
-    let difference: Vec<_> = json
+fn print_clusters(document: &str) {
+    let clusters = stats::cluster_stats(document);
+    if clusters.clusters == 0 {
+        return;
+    }
+    println!("{}", msg!("clusters-total", clusters = clusters.clusters,
+                        average = messages::decimal(clusters.keys_per_cluster())));
+    if clusters.footnotes > 0 {
+        println!("{}", msg!("clusters-footnotes", footnotes = clusters.footnotes, single = clusters.single_source_footnotes));
+    }
+    if !clusters.largest.is_empty() {
+        println!("{}", msg!("clusters-largest"));
+        for (keys, line) in clusters.largest {
+            println!("  {}", msg!("clusters-largest-row", keys = keys, line = line));
+        }
+    }
+}
+
+// Sections without heading (text before the first one) are left out like in print_density
+fn print_section_chart(document: &str) {
+    let rows: Vec<(String, usize)> = stats::citation_density(document)
+        .into_iter()
+        .filter(|section| !section.heading.is_empty())
+        .map(|section| (section.heading, section.citations))
+        .collect();
+    if rows.is_empty() {
+        return;
+    }
+    println!("{}", msg!("chart-sections"));
+    for line in stats::bar_chart(&rows) {
+        println!("  {}", line);
+    }
+}
+
+fn print_library_stats(library: &stats::LibraryStats) {
+    println!("{}", msg!("library-total", total = library.total.total(),
+                        cited = library.total.cited, uncited = library.total.uncited));
+    for (title, buckets) in [
+        (msg!("library-by-year"), &library.by_year),
+        (msg!("library-by-type"), &library.by_type),
+        (msg!("library-by-language"), &library.by_language),
+        (msg!("library-by-jurisdiction"), &library.by_jurisdiction),
+    ] {
+        // Libraries without legal cases have no jurisdictions
+        if buckets.is_empty() {
+            continue;
+        }
+        println!("{}", title);
+        for (bucket, count) in buckets {
+            println!("  {}", msg!("library-bucket", bucket = bucket.as_str(), total = count.total(),
+                                  cited = count.cited, uncited = count.uncited));
+        }
+    }
+}
+
+fn print_collections(by_collection: &std::collections::BTreeMap<Option<String>, stats::BucketCount>) {
+    if by_collection.keys().all(Option::is_none) {
+        println!("{}", msg!("collection-unknown"));
+        return;
+    }
+    println!("{}", msg!("collection-heading"));
+    // Entries in no collection last
+    let names: Vec<(String, &stats::BucketCount)> = by_collection
         .iter()
-        .filter(|citation| !document_set.contains(&citation.citation_key[..]))
-        .cloned()
+        .filter(|(collection, _)| collection.is_some())
+        .chain(by_collection.iter().filter(|(collection, _)| collection.is_none()))
+        .map(|(collection, count)| (collection.clone().unwrap_or_else(|| msg!("collection-none")), count))
         .collect();
+    let width = names.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    for (name, count) in names {
+        let percent = count.cited * 100 / count.total().max(1);
+        println!("  {}", msg!("collection-row", collection = format!("{:<width$}", name, width = width),
+                              cited = count.cited, total = count.total(), percent = percent));
+    }
+}
 
-    Ok(difference)
+fn run_auth(
+    action: &AuthAction,
+    profile: Option<&str>,
+    api_key: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        AuthAction::Login => {
+            let api_key = match api_key {
+                Some(key) => key.to_string(),
+                None => rpassword::prompt_password(format!("{} ", msg!("auth-prompt")))?,
+            };
+            if api_key.trim().is_empty() {
+                return Err("no API key given".into());
+            }
+            credentials::store_api_key(profile, api_key.trim())?;
+            println!("{}", msg!("auth-stored"));
+        }
+        AuthAction::Logout => {
+            if credentials::delete_api_key(profile)? {
+                println!("{}", msg!("auth-removed"));
+            } else {
+                println!("{}", msg!("auth-none"));
+            }
+        }
+    }
+    Ok(())
 }
 
-fn get_bibliography_path(document: &str) -> Result<String, Box<dyn std::error::Error>> {
-    // Extract the path of the bibliography given in the yaml header of the passed md file
-    let metadata= YamlFrontMatter::parse::<Metadata>(&document)
-        .unwrap()
-        .metadata;
-    Ok(metadata.bibliography)
+// Preview a rename in one file as a diff, or apply it through safe_write in the encoding the file
+// was read in (BOM included), optionally keeping the original as <path>.bak
+fn rename_file(
+    path: &Path,
+    rename: impl Fn(&str) -> Result<(String, usize), Box<dyn std::error::Error>>,
+    write: bool,
+    backup: bool,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let (before, detected) = encoding::read_with_encoding(path)
+        .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let (after, count) = rename(&before)?;
+    if count == 0 {
+        return Ok(0);
+    }
+    if write {
+        let bytes = encoding::encode(&after, detected).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        safe_write::write(path, &bytes, backup)?;
+        println!("{}", msg!("rename-done", path = path.display().to_string(), count = count));
+    } else {
+        print_diff(&path.display().to_string(), &path.display().to_string(), &before, &after);
+    }
+    Ok(count)
 }
 
-fn main() -> io::Result<()> {
-    let args = Args::parse();
+// What a mutating subcommand would change, as a unified diff with three lines of context (--dry-run)
+fn print_diff(old_name: &str, new_name: &str, before: &str, after: &str) {
+    let diff = similar::TextDiff::from_lines(before, after);
+    print!("{}", diff.unified_diff().context_radius(3).missing_newline_hint(false).header(old_name, new_name));
+}
 
-    // Read in the provided md document
-    let mut document_md_input = args.document.lock();
-    let mut document_md: String = String::new();
+fn run_rename(
+    old: &str,
+    new: &str,
+    documents: &[PathBuf],
+    bibliography: Option<&Path>,
+    write: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if new.is_empty() || new.contains(char::is_whitespace) {
+        return Err(format!("invalid citation key {:?}", new).into());
+    }
+    let mut count = 0;
+    for document in documents {
+        count += rename_file(document, |text| rename::rename_in_document(text, old, new), write, false)?;
+    }
+    if let Some(bibliography) = bibliography
+        && rename_file(bibliography, |text| rename::rename_in_bibliography(text, old, new), write, false)? == 0
+    {
+        eprintln!("{}", msg!("rename-not-in-bibliography", key = old, bibliography = bibliography.display().to_string()));
+    }
+    if count == 0 {
+        println!("{}", msg!("rename-none", key = old));
+    } else if !write {
+        println!("{}", msg!("rename-pending", count = count));
+    }
+    Ok(())
+}
 
-    // Read the document into a string
-    if args.verbose {
-        println!("Reading document from {}", document_md_input.path()
-            .unwrap()
-            .display()
-        );
+// The document as explain sees it: the text the check reads, the text before exclusions blanked
+// parts of it and the suppressions of the document, its included documents and the config
+struct Explained<'a> {
+    text: &'a str,
+    unexcluded: &'a str,
+    exclusions: exclude::Exclusions,
+    suppressions: &'a [suppress::Suppression],
+}
+
+// Everything the check does with one key, step by step
+fn explain_key(
+    key: &str,
+    document: &Explained,
+    extractor: &dyn Extractor,
+    citations_bibliography: &[Citations],
+    zotero_lib: &str,
+    config: &config::Config,
+    hooks: Option<&Hooks>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}:", key);
+
+    let mut citations_document = extractor.extract(document.text)?;
+    let extracted = citations_document.iter().filter(|k| *k == key).count();
+    let lines: Vec<String> = search::find_citations(document.text, key)?
+        .iter()
+        .map(|location| location.line.to_string())
+        .collect();
+    let excluded: Vec<String> = search::citation_spans(document.unexcluded, key)?
+        .into_iter()
+        .filter_map(|span| {
+            let option = document.exclusions.excluded_by(document.unexcluded, span.start)?;
+            Some(msg!("explain-excluded", key = key, line = search::location(document.unexcluded, span.start).line, option = option.to_string()))
+        })
+        .collect();
+    match (extracted, lines.is_empty()) {
+        (0, true) if !excluded.is_empty() => {}
+        (0, true) => println!("  {}", msg!("explain-not-cited")),
+        (0, false) => println!("  {}", msg!("explain-not-recognized", key = key, lines = lines.join(", "), extractor = extractor.name())),
+        (n, true) => println!("  {}", msg!("explain-found", count = n, extractor = extractor.name())),
+        (n, false) => println!("  {}", msg!("explain-found-lines", count = n, extractor = extractor.name(), lines = lines.join(", "))),
+    }
+    for citation in excluded {
+        println!("  {}", citation);
     }
-    document_md_input.read_to_string(&mut document_md)?;
 
-    let mut bibliography_json: String = String::new();
-    // Get bibliography either from CLI oder from header in document
-    match args.zotero_lib {
-        Some(ref zotero_lib) => {
-            // If found, read in the json based on the CLI
+    let locators: Vec<String> = cluster::parse_cites(document.text, config.legal)
+        .into_iter()
+        .filter(|cite| cite.key == key)
+        .map(|cite| match cite.locator {
+            Some(locator) => msg!("explain-locator", locator = locator.to_string(), line = cite.location.line),
+            None => msg!("explain-no-locator", line = cite.location.line),
+        })
+        .collect();
+    if !locators.is_empty() {
+        println!("  {}", msg!("explain-locators", locators = locators.join(", ")));
+    }
 
-            if args.verbose {
-                println!("Reading bibliography from {}", zotero_lib.path()
-                    .unwrap()
-                    .display()
-                );
+    if citations_bibliography.iter().any(|c| c.citation_key == key) {
+        println!("  {}", msg!("explain-entry", bibliography = zotero_lib));
+    } else if let Some(similar) = citations_bibliography.iter().find(|c| c.citation_key.eq_ignore_ascii_case(key)) {
+        println!("  {}", msg!("explain-entry-case", bibliography = zotero_lib, similar = similar.to_string()));
+    } else {
+        println!("  {}", msg!("explain-no-entry", bibliography = zotero_lib));
+    }
+
+    if let Some(current) = config.aliases.get(key) {
+        println!("  {}", msg!("explain-stale", current = current.as_str()));
+    }
+    let stale: Vec<&str> = config.aliases.iter().filter(|(_, current)| *current == key).map(|(stale, _)| stale.as_str()).collect();
+    if !stale.is_empty() {
+        println!("  {}", msg!("explain-current", stale = stale.join(", ")));
+    }
+
+    let mut reported = key.to_string();
+    if let Some(hooks) = hooks {
+        match hooks.apply_keys(vec![key.to_string()])?.pop() {
+            None => println!("  {}", msg!("explain-dropped")),
+            Some(normalized) if normalized != key => {
+                println!("  {}", msg!("explain-normalized", key = normalized.as_str()));
+                reported = normalized;
             }
+            Some(_) => println!("  {}", msg!("explain-kept")),
+        }
+    }
 
-            let bibliography_json_input = args.zotero_lib;
-            bibliography_json_input.unwrap().read_to_string(&mut bibliography_json)?;
+    if let Some(suppression) = document.suppressions.iter().find(|s| s.key == reported) {
+        match suppression.reason {
+            Some(ref reason) => println!("  {}", msg!("explain-suppressed-reason", reason = reason.as_str())),
+            None => println!("  {}", msg!("explain-suppressed")),
         }
-        None => {
-            // Get bibliography path as input
+    }
 
-            // YAML does not accept tabs, but two or four spaces instead
-            let clean_doc = &document_md.replace("\t", "  ");
-            // Let's leave this a bit cursed
-            let bibliography_path = get_bibliography_path(&clean_doc).unwrap();
-            let bp = shellexpand::tilde(&bibliography_path);
-            // read from path
+    let mut report = check_keys(&mut citations_document, &mut citations_bibliography.to_vec(), hooks)?;
+    let suppressed = suppress::apply(&mut report, document.suppressions);
+    let result = if report.cited.contains(&reported) {
+        msg!("explain-result-cited")
+    } else if suppressed.iter().any(|s| s.key == reported) {
+        msg!("explain-result-suppressed")
+    } else if report.uncited.contains(&reported) {
+        msg!("explain-result-uncited")
+    } else if report.unknown.contains(&reported) {
+        msg!("explain-result-unknown")
+    } else {
+        msg!("explain-result-missing")
+    };
+    println!("  {}", result);
+    Ok(())
+}
 
-            if args.verbose {
-                println!("Reading bibliography from path in document, {}", bp.as_ref())
+// The keys of --required: a list file, or collection:NAME for the entries of that collection
+fn required_keys(required: &str, bibliography_json: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let Some(collection) = required.strip_prefix("collection:") else {
+        let list = encoding::read_to_string(Path::new(required)).map_err(|e| format!("could not read {}: {}", required, e))?;
+        return Ok(rules::parse_required(&list));
+    };
+    let keys: Vec<String> = library::get_library_entries(bibliography_json)?
+        .into_iter()
+        .filter(|entry| entry.collections.iter().any(|name| name == collection))
+        .map(|entry| entry.citation_key)
+        .collect();
+    if keys.is_empty() {
+        return Err(format!("--required: no entries in collection {} (collections come with Better BibTeX JSON \
+                            exports and Web API libraries)", collection).into());
+    }
+    Ok(keys)
+}
+
+// With --strict, malformed bibliography entries (skipped while reading) and suppressed entries
+// count as errors too; warn-level rules are already at error level then
+fn strict_errors(strict: bool, skipped: usize, suppressed: usize) -> usize {
+    if !strict {
+        return 0;
+    }
+    if skipped > 0 {
+        eprintln!("{}", msg!("strict-skipped", count = skipped));
+    }
+    if suppressed > 0 {
+        eprintln!("{}", msg!("strict-suppressed", count = suppressed));
+    }
+    skipped + suppressed
+}
+
+// The key for the Zotero Web API: --api-key, the profile's or the one stored by auth login. The
+// keyring is only asked if one of the libraries is read from the Web API.
+fn api_key(args: &Args, profile: Option<(&str, &config::Profile)>, libraries: &[source::BibliographySource]) -> Option<String> {
+    let web_api = libraries.iter().any(|library| matches!(library, source::BibliographySource::ZoteroApi(_)));
+    args.api_key.clone()
+        .or_else(|| profile.and_then(|(_, p)| p.api_key.clone()))
+        .or_else(|| web_api.then(|| credentials::load_api_key(profile.map(|(name, _)| name))).flatten())
+}
+
+// Linter-style exit status: 1 if any rule at error level had findings
+fn exit_on_errors(errors: usize) {
+    if errors > 0 {
+        eprintln!("{}", msg!("error-count", count = errors));
+        std::process::exit(1);
+    }
+}
+
+// The given documents, or all documents of the project manifest
+fn documents_or_project(
+    config: &config::Config,
+    documents: &[PathBuf],
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    if !documents.is_empty() {
+        return Ok(documents.to_vec());
+    }
+    let mut documents = Vec::new();
+    for mapping in &config.mappings {
+        documents.extend(mapping.document_paths()?);
+    }
+    documents.sort();
+    documents.dedup();
+    if documents.is_empty() {
+        return Err("no documents given and none in the project manifest".into());
+    }
+    Ok(documents)
+}
+
+fn run_search(
+    config: &config::Config,
+    key: &str,
+    documents: &[PathBuf],
+    context: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut count = 0;
+    for path in &documents_or_project(config, documents)? {
+        let document = encoding::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        let lines: Vec<&str> = document.lines().collect();
+        let mut cited_lines: Vec<usize> = search::find_citations(&document, key)?
+            .into_iter()
+            .map(|location| location.line)
+            .collect();
+        cited_lines.dedup();
+        count += cited_lines.len();
+
+        // grep -C style: path:line: for matches, path-line- for context, -- between groups
+        let mut printed = 0;
+        for &line in &cited_lines {
+            let first = line.saturating_sub(context).max(printed + 1);
+            if printed > 0 && first > printed + 1 {
+                println!("--");
             }
+            for number in first..=(line + context).min(lines.len()) {
+                let separator = if cited_lines.contains(&number) { ':' } else { '-' };
+                println!("{}{}{}{}{}", path.display(), separator, number, separator, lines[number - 1]);
+                printed = number;
+            }
+        }
+    }
+    if count == 0 {
+        println!("{}", msg!("search-none", key = key));
+    }
+    Ok(())
+}
+
+// The syntax of each document goes by its extension like in the coverage check; suppression
+// directives don't count as citations
+fn run_diff(config: &config::Config, old: &Path, new: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let registry = Registry::new(&config.extensions);
+    let keys = |path: &Path| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let document = encoding::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        let syntax = registry.get(path).copied().unwrap_or(Syntax::Markdown);
+        Ok(syntax.cited_keys(&suppress::without_directives(&document)))
+    };
+    let (only_old, only_new) = compare_citations(&keys(old)?, &keys(new)?);
+    if only_old.is_empty() && only_new.is_empty() {
+        println!("{}", msg!("diff-same", old = old.display().to_string(), new = new.display().to_string()));
+    }
+    for key in only_old {
+        println!("-{}", key);
+    }
+    for key in only_new {
+        println!("+{}", key);
+    }
+    Ok(())
+}
+
+// The verdict follows the uncited and unknown rules like for a single run
+fn run_merge(config: &config::Config, args: &Args, paths: &[PathBuf], json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if args.porcelain {
+        return Err("merge has no --porcelain output, use --summary or merge --json".into());
+    }
+    let mut reports = Vec::new();
+    for path in paths {
+        let text = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        reports.push(parse_report_json(&text).map_err(|e| format!("{}: {}", path.display(), e))?);
+    }
+    let report = merge_reports(&reports);
+    let findings = rules::check_report(&report);
+    if json {
+        println!("{}", report_json(&report, None)?);
+    } else if args.summary {
+        println!("{}", report.summary(0));
+    } else {
+        println!("{}", msg!("merge-heading", reports = reports.len(), cited = report.cited.len(),
+                            total = report.cited.len() + report.uncited.len()));
+        let listing = Listing { entries: Vec::new(), legal: false, references: BTreeMap::new() };
+        print_findings(&report, &findings, &config.rules, &listing);
+    }
+    write_badge(args.badge_endpoint.as_deref(), &report, 0)?;
+    on_complete(args.on_complete.as_deref(), &report, None);
+    exit_on_errors(error_count(&findings, &config.rules));
+    Ok(())
+}
 
-            bibliography_json = fs::read_to_string(bp.into_owned())?
+// The original document with notes at the citations of unknown and stale keys, found in the
+// document as checked, and the uncited entries appended
+fn annotate_document(
+    original: &str,
+    document: &str,
+    report: &CoverageReport,
+    citations_document: &[String],
+    bibliography_json: &str,
+    aliases: &std::collections::BTreeMap<String, String>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let entries = library::get_library_entries(bibliography_json)?;
+    let mut notes = Vec::new();
+    // Stale keys are unknown too, their note names the current key
+    for key in report.unknown.iter().filter(|key| !aliases.contains_key(*key)) {
+        let message = match keys::suggest(key, entries.iter().map(|e| e.citation_key.as_str())) {
+            Some(similar) => msg!("annotate-unknown-similar", key = key.as_str(), similar = similar),
+            None => msg!("annotate-unknown", key = key.as_str()),
+        };
+        for location in search::find_citations(document, key)? {
+            notes.push(annotate::Note { line: location.line, message: message.clone() });
+        }
+    }
+    for (stale, current) in aliases.iter().filter(|(stale, _)| citations_document.contains(stale)) {
+        for location in search::find_citations(document, stale)? {
+            notes.push(annotate::Note { line: location.line, message: msg!("annotate-stale", key = stale.as_str(), current = current.as_str()) });
         }
     }
+    notes.sort_by_key(|note| note.line);
+    let uncited: Vec<(String, Option<String>)> = report.uncited.iter()
+        .map(|key| (key.clone(), entries.iter().find(|e| &e.citation_key == key).and_then(|e| e.reference())))
+        .collect();
+    Ok(annotate::annotate(original, &notes, &uncited))
+}
 
-    let citations_bibliography = get_citations_bibliography(&bibliography_json)
-        .unwrap();
+fn run_fix(
+    config: &config::Config,
+    documents: &[PathBuf],
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if config.aliases.is_empty() {
+        return Err("no [aliases] in the config file".into());
+    }
+    let mut count = 0;
+    for document in &documents_or_project(config, documents)? {
+        count += rename_file(document, |text| rename::apply_aliases(text, &config.aliases), !dry_run, true)?;
+    }
+    if count == 0 {
+        println!("{}", msg!("fix-none"));
+    } else if dry_run {
+        println!("{}", msg!("fix-pending", count = count));
+    }
+    Ok(())
+}
 
-    if args.verbose {
-        println!("Found {} sources in bibliography", citations_bibliography.len());
+// How long each stage of a check took, printed to stderr with --timings
+struct Timings {
+    enabled: bool,
+    stages: Vec<(String, Duration)>,
+}
+
+impl Timings {
+    fn new(enabled: bool) -> Self {
+        Timings { enabled, stages: Vec::new() }
+    }
+
+    // Run one stage, e.g. "extraction thesis.md", and record how long it took
+    fn time<T>(&mut self, stage: String, run: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = run();
+        if self.enabled {
+            self.stages.push((stage, start.elapsed()));
+        }
+        result
+    }
+
+    fn print(&self) {
+        if !self.enabled {
+            return;
+        }
+        let width = self.stages.iter().map(|(stage, _)| stage.chars().count()).max().unwrap_or(0);
+        eprintln!("Timings:");
+        for (stage, duration) in &self.stages {
+            eprintln!("  {:<width$}  {:>10.1} ms", stage, duration.as_secs_f64() * 1000.0, width = width);
+        }
+        let total: Duration = self.stages.iter().map(|(_, duration)| *duration).sum();
+        eprintln!("  {:<width$}  {:>10.1} ms", "total", total.as_secs_f64() * 1000.0, width = width);
+    }
+}
+
+// The bibliography restricted to --collection and --saved-search, and the keys of the entries left
+// out, which are not unknown when cited. A collection without entries is an error, its name is
+// most likely misspelled.
+fn scoped(
+    mut bibliography_json: String,
+    args: &Args,
+    network: &source::NetworkOptions,
+) -> Result<(String, HashSet<String>), Box<dyn std::error::Error>> {
+    if args.collection.is_none() && args.saved_search.is_none() {
+        return Ok((bibliography_json, HashSet::new()));
+    }
+    let mut outside: HashSet<String> = library::get_library_entries(&bibliography_json)?
+        .into_iter()
+        .map(|entry| entry.citation_key)
+        .collect();
+    if let Some(ref collection) = args.collection {
+        let entries = library::get_library_entries(&bibliography_json)?;
+        if !entries.iter().any(|entry| entry.collections.contains(collection)) {
+            let mut names: Vec<&str> = entries.iter().flat_map(|entry| entry.collections.iter().map(String::as_str)).collect();
+            names.sort();
+            names.dedup();
+            if names.is_empty() {
+                return Err(format!("cannot select collection {}, the bibliography has no collections \
+                                    (use a Better BibTeX JSON export or the Web API)", collection).into());
+            }
+            return Err(format!("no entries in collection {}, collections: {}", collection, names.join(", ")).into());
+        }
+        bibliography_json = library::retain_entries(&bibliography_json, |entry| entry.collections.contains(collection))?;
+    }
+    if let Some(ref search) = args.saved_search {
+        let keys = zotero_api::local_api::saved_search_keys(search, network)?;
+        bibliography_json = library::retain_entries(&bibliography_json, |entry| keys.contains(&entry.citation_key))?;
+    }
+    for entry in library::get_library_entries(&bibliography_json)? {
+        outside.remove(&entry.citation_key);
+    }
+    Ok((bibliography_json, outside))
+}
+
+// The extractor for one document: the built-in one for its file extension, unless --syntax,
+// --plugin or --extractor-cmd chose the extractor of every document
+fn extractor_for<'a>(path: &Path, extractor: &'a dyn Extractor, registry: &'a Registry, args: &Args) -> &'a dyn Extractor {
+    let chosen = args.syntax.is_some() || args.plugin.is_some() || args.extractor_cmd.is_some();
+    match registry.get(path) {
+        Some(syntax) if !chosen => syntax,
+        _ => extractor,
+    }
+}
+
+// The syntax of a document: --syntax, else by its extension
+fn syntax_for(path: &Path, registry: &Registry, args: &Args) -> Syntax {
+    args.syntax.or_else(|| registry.get(path).copied()).unwrap_or(Syntax::Markdown)
+}
+
+// The documents included by the document (!include chapter2.md, \@input{chapter2.aux} in .aux files),
+// recursively, with their suppressions collected and directives and exclusions blanked out like for
+// the document
+fn read_included(
+    path: &Path,
+    document: &str,
+    syntax: Syntax,
+    visited: &mut HashSet<PathBuf>,
+    args: &Args,
+    suppressions: &mut Vec<suppress::Suppression>,
+) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let mut included = include::resolve(path, document, syntax, visited)?;
+    for (path, document) in &mut included {
+        if args.verbose {
+            println!("Reading included document from {}", path.display());
+        }
+        suppressions.extend(suppress::parse_suppressions(document));
+        *document = args.exclusions().apply(suppress::without_directives(document));
+    }
+    Ok(included)
+}
+
+// The citations of a document for the JSON report, with the path of the document if given. Only
+// for syntaxes whose pandoc text keeps the byte offsets of the document, not a notebook's cells
+fn report_citations(path: Option<&Path>, document: &str, syntax: Syntax) -> Vec<search::Occurrence> {
+    let text = match syntax {
+        Syntax::Markdown | Syntax::Djot => syntax.pandoc_text(document).unwrap_or_default(),
+        _ => return Vec::new(),
+    };
+    let mut citations = search::occurrences(&text);
+    for citation in &mut citations {
+        citation.document = path.map(|path| path.display().to_string());
+    }
+    citations
+}
+
+// The cites of a document with their locators, none in syntaxes without pandoc citations
+fn pandoc_cites(document: &str, syntax: Syntax, legal: bool) -> Vec<cluster::Cite> {
+    syntax.pandoc_text(document).map(|text| cluster::parse_cites(&text, legal)).unwrap_or_default()
+}
+
+// Citations repeating a key in the same footnote or sentence, none in syntaxes without pandoc citations
+fn pandoc_repeated(document: &str, syntax: Syntax, legal: bool) -> Vec<cluster::Cite> {
+    syntax.pandoc_text(document).map(|text| cluster::repeated_cites(&text, legal)).unwrap_or_default()
+}
+
+// Keys and cites of a document scanned chunk by chunk
+struct Scanned {
+    keys: Vec<String>,
+    cites: Vec<cluster::Cite>,
+    // Citations repeating a key in the same footnote or sentence
+    repeated: Vec<cluster::Cite>,
+}
+
+// Scan a document too large to read whole. The first chunk is returned in place of the document,
+// it holds the YAML header. The keys of included documents are scanned too, the documents are
+// added to included.
+fn scan_chunked(
+    path: &Path,
+    extractor: &dyn Extractor,
+    syntax: Syntax,
+    config: &config::Config,
+    args: &Args,
+    suppressions: &mut Vec<suppress::Suppression>,
+    included: &mut Vec<(PathBuf, String)>,
+) -> Result<(String, Scanned), Box<dyn std::error::Error>> {
+    let document = chunks::MappedDocument::open(path)?;
+    let mut visited = HashSet::new();
+    let mut head = None;
+    let mut scanned = Scanned { keys: Vec::new(), cites: Vec::new(), repeated: Vec::new() };
+    let (legal, locators) = (config.legal, !config.require_locator.is_empty());
+    for chunk in document.chunks(chunks::CHUNK_SIZE) {
+        let chunk = chunk?;
+        for (path, document) in read_included(path, &chunk.text, syntax, &mut visited, args, suppressions)? {
+            scanned.keys.extend(args.extract(extractor, &document)?);
+            included.push((path, document));
+        }
+        suppressions.extend(suppress::parse_suppressions(&chunk.text));
+        let text = args.exclusions().apply(suppress::without_directives(&chunk.text));
+        scanned.keys.extend(args.extract(extractor, &text)?);
+        if let Some(pandoc_text) = syntax.pandoc_text(&text) {
+            if locators {
+                for mut cite in cluster::parse_cites(&pandoc_text, legal) {
+                    cite.location.line += chunk.first_line - 1;
+                    scanned.cites.push(cite);
+                }
+            }
+            // Chunks end at blank lines, so no sentence or inline footnote spans two
+            for mut cite in cluster::repeated_cites(&pandoc_text, legal) {
+                cite.location.line += chunk.first_line - 1;
+                scanned.repeated.push(cite);
+            }
+        }
+        head.get_or_insert(text);
+    }
+    Ok((head.unwrap_or_default(), scanned))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    messages::init(args.lang.unwrap_or_else(messages::Lang::from_env))?;
+    let mut timings = Timings::new(args.timings);
+
+    if let Some(Command::OutputSchema) = args.command {
+        print!("{}", REPORT_SCHEMA);
+        return Ok(());
+    }
+
+    let mut config = config::Config::load(args.config.as_deref())?;
+    config.legal |= args.legal;
+    if args.strict {
+        config.rules = config.rules.strict();
+    }
+    let profile = config.profile(args.profile.as_deref())?;
+    let profile_name = profile.map(|(name, _)| name);
+
+    if let Some(Command::Auth { ref action }) = args.command {
+        return run_auth(action, profile_name, args.api_key.as_deref());
+    }
+
+    if let Some(Command::Rename { ref old, ref new, ref documents, ref bibliography, write, .. }) = args.command {
+        return run_rename(old, new, documents, bibliography.as_deref(), write);
+    }
+
+    if let Some(Command::Fix { ref documents, dry_run }) = args.command {
+        return run_fix(&config, documents, dry_run);
+    }
+
+    if let Some(Command::Search { ref key, ref documents, context }) = args.command {
+        return run_search(&config, key, documents, context);
+    }
+
+    if let Some(Command::Diff { ref old, ref new }) = args.command {
+        return run_diff(&config, old, new);
+    }
+
+    if let Some(Command::Merge { ref reports, json }) = args.command {
+        return run_merge(&config, &args, reports, json);
     }
 
-    let citations_document = get_citations_document(&document_md)
-        .unwrap();
+    let network = source::NetworkOptions {
+        timeout: Duration::from_secs(args.timeout),
+        retries: args.retries,
+        proxy: args.proxy.clone(),
+        jobs: args.jobs,
+    };
+    if let Some(Command::SelfUpdate { check }) = args.command {
+        return update::self_update(&network, check);
+    }
+    let hooks = config.script.as_deref().map(Hooks::load).transpose()?;
+    let key_pattern = config.key_pattern()?;
 
+    let registry = Registry::new(&config.extensions);
+    let extractor: Box<dyn Extractor> = match (&args.plugin, &args.extractor_cmd) {
+        (Some(plugin), _) => load_plugin(plugin)?,
+        (None, Some(command)) => Box::new(CommandExtractor::new(command)),
+        (None, None) => {
+            let syntax = args.syntax.or_else(|| {
+                args.document.as_ref().and_then(|document| document.lock().path().and_then(|path| registry.get(path).copied()))
+            });
+            Box::new(syntax.unwrap_or(Syntax::Markdown))
+        }
+    };
     if args.verbose {
-        println!("Found {} citations in document", citations_document.len());
+        println!("Extracting citations with {}", extractor.name());
+    }
+
+    if let Some(Command::Project) = args.command {
+        if config.mappings.is_empty() {
+            return Err("no [[mappings]] in the config file".into());
+        }
+        let checked = run_project(&config, extractor.as_ref(), hooks.as_ref(),
+                                  profile, &network, &args, &mut timings)?;
+        let (total, suppressed) = total_report(&checked.reports);
+        write_badge(args.badge_endpoint.as_deref(), &total, suppressed)?;
+        timings.print();
+        exit_on_errors(checked.errors);
+        return Ok(());
+    }
+
+    if let Some(Command::Check { ref projects, all }) = args.command {
+        let names: Vec<&String> = if all { config.projects.keys().collect() } else { projects.iter().collect() };
+        if names.is_empty() {
+            return Err("no [projects] in the config file".into());
+        }
+        let mut errors = 0;
+        let mut reports = Vec::new();
+        // Projects are independent, each is checked on its own
+        for name in names {
+            let checked = run_project(&config.project(name)?, extractor.as_ref(), hooks.as_ref(),
+                                      profile, &network, &args, &mut timings)?;
+            errors += checked.errors;
+            reports.extend(checked.reports);
+        }
+        if !args.porcelain && reports.len() > 1 {
+            print_workspace_summary(&reports, args.summary);
+        }
+        let (total, suppressed) = total_report(&reports);
+        write_badge(args.badge_endpoint.as_deref(), &total, suppressed)?;
+        timings.print();
+        exit_on_errors(errors);
+        return Ok(());
     }
 
+    let Some(ref document) = args.document else {
+        Args::command()
+            .error(ErrorKind::MissingRequiredArgument, "the following required arguments were not provided: --document <DOCUMENT>")
+            .exit();
+    };
+
+    // Read in the provided md document
+    let mut document_md_input = document.lock();
+    let mut document_bytes = Vec::new();
+
+    // Read the document into a string
     if args.verbose {
-        println!("Comparing sources in bibliography and cited sources");
+        println!("Reading document from {}", document_md_input.path()
+            .unwrap()
+            .display()
+        );
     }
 
-    let differences = get_citation_difference(citations_document,
-                                              citations_bibliography).unwrap();
+    let document_name = document_md_input.path().map_or("stdin".to_string(), |path| path.display().to_string());
 
+    // Suppression comments are only directives, their keys must not count as citations
+    let mut suppressions = suppress::from_config(&config.allow_uncited);
+    let mut included = Vec::new();
+    let syntax = syntax_for(document_md_input.path().unwrap_or(Path::new("-")), &registry, &args);
+    let (document_md, scanned) = if args.chunked {
+        if matches!(args.command, Some(Command::Stats { .. } | Command::Explain { .. } | Command::Annotate { .. })) {
+            return Err("stats, explain and annotate need the whole document, they can't be used with --chunked".into());
+        }
+        let path = document_md_input.path().ok_or("--chunked needs a document file")?;
+        let (head, scanned) = timings.time(format!("reading and extraction {}", document_name), || {
+            scan_chunked(path, extractor.as_ref(), syntax, &config, &args, &mut suppressions, &mut included)
+        })?;
+        (head, Some(scanned))
+    } else {
+        let document_md = timings.time(format!("reading {}", document_name), || {
+            document_md_input.read_to_end(&mut document_bytes)?;
+            encoding::decode(&document_bytes)
+        })?;
+        // Included documents of stdin are relative to the working directory
+        let path = document_md_input.path().unwrap_or(Path::new("-"));
+        included = read_included(path, &document_md, syntax, &mut HashSet::new(), &args, &mut suppressions)?;
+        suppressions.extend(suppress::parse_suppressions(&document_md));
+        (args.exclusions().apply(suppress::without_directives(&document_md)), None)
+    };
 
-    if differences.is_empty() {
-        println!("All sources cited");
+    // Get bibliography either from CLI, the selected profile oder from header in document
+    let mut libraries = args.zotero_lib.clone();
+    if libraries.is_empty() {
+        libraries.extend(profile.and_then(|(_, p)| p.library.clone()));
+    }
+    let zotero_lib = if libraries.is_empty() {
+        let library = timings.time(format!("front matter {}", document_name), || {
+            source::BibliographySource::from_front_matter(&document_md)
+        })?;
+        if args.verbose {
+            println!("Reading bibliography from path in document, {}", library)
+        }
+        libraries.push(library);
+        libraries[0].to_string()
     } else {
-        println!("{} Sources not cited:", differences.len());
-        for d in differences {
-            println!("{}", d);
+        let zotero_lib = libraries.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        if args.verbose {
+            println!("Reading bibliography from {}", zotero_lib);
+        }
+        zotero_lib
+    };
+
+    if args.refresh_export {
+        let [source::BibliographySource::File(path)] = libraries.as_slice() else {
+            return Err(format!("--refresh-export needs one bibliography file, not {}", zotero_lib).into());
+        };
+        // Not the whole library by default, it would replace a collection's export
+        let export = config.pull_export.as_deref()
+            .ok_or("--refresh-export needs pull_export in the config file, e.g. pull_export = \"collection?/1/Thesis\"")?;
+        if args.verbose {
+            println!("Exporting {} to {}", better_bibtex::pull_export::url(export), path.display());
         }
+        timings.time(format!("export {}", path.display()), || better_bibtex::pull_export::refresh(path, export, &network))?;
     }
 
-    Ok(())
-}
+    let api_key = api_key(&args, profile, &libraries);
+    let (bibliography_json, outside, (citations_bibliography, skipped), conflicts) = timings.time(format!("bibliography {}", zotero_lib), || {
+        let (bibliography_json, conflicts) = match libraries.as_slice() {
+            [library] => (library.read(api_key.as_deref(), &network, config.mods_key(), args.verbose)?, Vec::new()),
+            libraries => {
+                merge_loaded(libraries, source::BibliographySource::read_all(libraries, api_key.as_deref(), &network, config.mods_key(), args.verbose)?)?
+            }
+        };
+        let (bibliography_json, outside) = scoped(bibliography_json, &args, &network)?;
+        let citations_bibliography = get_citations_bibliography_skipping(&bibliography_json)?;
+        Ok::<_, Box<dyn std::error::Error>>((bibliography_json, outside, citations_bibliography, conflicts))
+    })?;
+
+    if args.verbose {
+        println!("Found {} sources in bibliography", citations_bibliography.len());
+    }
+
+    if let Some(Command::Explain { ref key }) = args.command {
+        // Lines and offsets of the excluded text match those of document_md
+        let unexcluded = suppress::without_directives(&encoding::decode(&document_bytes)?);
+        let document = Explained { text: &document_md, unexcluded: &unexcluded, exclusions: args.exclusions(), suppressions: &suppressions };
+        return explain_key(key, &document, extractor.as_ref(), &citations_bibliography,
+                           &zotero_lib, &config, hooks.as_ref());
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        Citations, get_citation_difference, get_citations_bibliography, get_citations_document, get_bibliography_path,
+    let (citations_document, cites, repeated) = match scanned {
+        Some(scanned) => (scanned.keys, Some(scanned.cites), Some(scanned.repeated)),
+        None => {
+            let mut keys = timings.time(format!("extraction {}", document_name), || args.extract(extractor.as_ref(), &document_md))?;
+            for (path, document) in &included {
+                let extractor = extractor_for(path, extractor.as_ref(), &registry, &args);
+                keys.extend(timings.time(format!("extraction {}", path.display()), || args.extract(extractor, document))?);
+            }
+            (keys, None, None)
+        }
     };
 
-    #[test]
-    fn test_get_citations_bibliography() {
-        let testdata_json = r#"
-    [
-  {
-    "id": ".2024",
-    "accessed": {
-      "date-parts": [
-        [
-          "2025",
-          1,
-          29
-        ]
-      ]
-    },
-    "citation-key": ".2024",
-    "container-title": "JuristenZeitung",
-    "container-title-short": "JZ",
-    "DOI": "10.1628/jz-2024-0306",
-    "ISSN": "0022-6882",
-    "issue": "22",
-    "issued": {
-      "date-parts": [
-        [
-          "2024"
-        ]
-      ]
-    },
-    "language": "de",
-    "page": "1007",
-    "source": "DOI.org (Crossref)",
-    "title": "Potenzial und Grenzen eines Einsatzes von Large Language Models in der öffentlichen Verwaltung",
-    "type": "article-journal",
-    "URL": "https://www.mohrsiebeck.com/10.1628/jz-2024-0306",
-    "volume": "79"
-  },
-  {
-    "id": ".2024a",
-    "accessed": {
-      "date-parts": [
-        [
-          "2025",
-          1,
-          29
-        ]
-      ]
-    },
-    "citation-key": ".2024a",
-    "container-title": "Archiv für die civilistische Praxis",
-    "container-title-short": "AcP",
-    "DOI": "10.1628/acp-2024-0020",
-    "ISSN": "0003-8997",
-    "issue": "4-5",
-    "issued": {
-      "date-parts": [
-        [
-          "2024"
-        ]
-      ]
-    },
-    "language": "de",
-    "page": "477",
-    "source": "DOI.org (Crossref)",
-    "title": "Kryptowerte als Sachen",
-    "type": "article-journal",
-    "URL": "https://www.mohrsiebeck.com/10.1628/acp-2024-0020",
-    "volume": "224"
-  },
-  {
-    "id": "AGGelnhausen.2024",
-    "authority": "AG Gelnhausen",
-    "citation-key": "AGGelnhausen.2024",
-    "genre": "Urt.",
-    "issued": {
-      "date-parts": [
-        [
-          "2024",
-          3,
-          4
-        ]
-      ]
-    },
-    "jurisdiction": "de",
-    "number": "52 C 76/24",
-    "title": "AG Gelnhausen, 04.03.2024 - 52 C 76/24",
-    "type": "legal_case"
-  },
-  {
-    "id": "Alexander.2024",
-    "author": [
-      {
-        "family": "Alexander",
-        "given": ""
-      }
-    ],
-    "citation-key": "Alexander.2024",
-    "container-title": "UWG",
-    "edition": "42",
-    "editor": [
-      {
-        "family": "Köhler",
-        "given": ""
-      },
-      {
-        "family": "Bornkamm",
-        "given": ""
-      },
-      {
-        "family": "Feddersen",
-        "given": ""
-      }
-    ],
-    "issued": {
-      "date-parts": [
-        [
-          "2024"
-        ]
-      ]
-    },
-    "source": "beck-online",
-    "title": "§ 2 GeschGehG",
-    "type": "entry-encyclopedia"
-  },
-  {
-    "id": "Alexander.2024a",
-    "author": [
-      {
-        "family": "Alexander",
-        "given": ""
-      }
-    ],
-    "citation-key": "Alexander.2024a",
-    "container-title": "UWG",
-    "edition": "42",
-    "editor": [
-      {
-        "family": "Köhler",
-        "given": ""
-      },
-      {
-        "family": "Bornkamm",
-        "given": ""
-      },
-      {
-        "family": "Feddersen",
-        "given": ""
-      }
-    ],
-    "issued": {
-      "date-parts": [
-        [
-          "2024"
-        ]
-      ]
-    },
-    "source": "beck-online",
-    "title": "§ 6 GeschGehG",
-    "type": "entry-encyclopedia"
-  }
-  ]
- "#;
-        let out = vec![
-            Citations {
-                citation_key: ".2024".to_string(),
-            },
-            Citations {
-                citation_key: ".2024a".to_string(),
-            },
-            Citations {
-                citation_key: "AGGelnhausen.2024".to_string(),
-            },
-            Citations {
-                citation_key: "Alexander.2024".to_string(),
-            },
-            Citations {
-                citation_key: "Alexander.2024a".to_string(),
-            },
-        ];
-        assert_eq!(get_citations_bibliography(testdata_json).unwrap(), out);
-    }
-
-    #[test]
-    fn test_get_citations_bibliography_missing_field() {
-        let testdata_json = r#"[{}]"#; // Missing citation-key
-        let result = get_citations_bibliography(testdata_json);
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_get_citations_document() {
-        let testdata_md = r#"Gemeinsame Voraussetzung beider Schranken ist zunächst, dass der
-Zugang zu den Daten rechtmäßig erfolgt.[@Bomhard.2024b Rn. 15] Dieser
-kann etwa auf einer dahingegenden Lizenz beruhen (welche aber etwa
-kein TDM zulässt) oder auch auf einer Einwilligung der:des
-Berechtigten. Eine solche kann sich (konkludent) durch öffentliche
-Zugänglichmachung im Internet ergeben.[@BGH.2024 Rn. 45--47;
-@BGH.2010c Rn. 36; so auch @LGHamburg.2024 Rn. 86] Das wird meist der
-Fall sein, zumindest, was den Zugang zu den Daten betrifft. @Alexander.2024; @Alexander.2024a
-"#;
-        assert_eq!(
-            get_citations_document(testdata_md).unwrap(),
-            vec![
-                "Bomhard.2024b",
-                "BGH.2024",
-                "BGH.2010c",
-                "LGHamburg.2024",
-                "Alexander.2024",
-                "Alexander.2024a"
-            ]
-        );
+    // Statistics and the graph go by the text holding the pandoc citations, e.g. a notebook's markdown cells
+    let pandoc_text = syntax.pandoc_text(&document_md).unwrap_or(Cow::Borrowed(&document_md));
+    if let Some(ref graph_path) = args.graph {
+        let graph = graph::CoCitationGraph::from_document(&pandoc_text);
+        if args.verbose {
+            println!("Writing co-citation graph with {} keys and {} edges to {}",
+                     graph.nodes.len(), graph.edges.len(), graph_path.display());
+        }
+        fs::write(graph_path, graph.render_for(graph_path))?;
     }
 
-    #[test]
-    fn test_get_citations_document_invalid_format() {
-        let testdata_md = "Here is a citation @key.1991 and another @key.2002. Invalid @key.";
-        let result = get_citations_document(testdata_md).unwrap();
-        assert_eq!(result.len(), 2); // Should still extract key1 and key2
-        assert_eq!(result[0], "key.1991");
-        assert_eq!(result[1], "key.2002");
-    }
-
-    #[test]
-    fn test_get_citation_difference() {
-        let json: Vec<Citations> = vec![
-            Citations {
-                citation_key: ".2024".to_string(),
-            },
-            Citations {
-                citation_key: ".2024a".to_string(),
-            },
-            Citations {
-                citation_key: "AGGelnhausen.2024".to_string(),
-            },
-            Citations {
-                citation_key: "Alexander.2024".to_string(),
-            },
-            Citations {
-                citation_key: "Alexander.2024a".to_string(),
-            },
-        ];
-        let md = vec![
-            "Bomhard.2024b",
-            "BGH.2024",
-            "BGH.2010c",
-            "LGHamburg.2024",
-            "Alexander.2024",
-            "Alexander.2024a",
-        ];
-        let out = vec![
-            Citations {
-                citation_key: ".2024".to_string(),
-            },
-            Citations {
-                citation_key: ".2024a".to_string(),
-            },
-            Citations {
-                citation_key: "AGGelnhausen.2024".to_string(),
-            },
-        ];
-        // Expected output
-        // inputs (steal from the prints)
-        assert_eq!(get_citation_difference(md, json).unwrap(), out)
-    }
-    #[test]
-    fn test_get_citation_difference_empty() {
-        let document_citations: Vec<&str> = vec![];
-        let json_citations = vec![
-            Citations {
-                citation_key: "key1".to_string(),
-            },
-            Citations {
-                citation_key: "key2".to_string(),
-            },
-        ];
-
-        let result = get_citation_difference(document_citations, json_citations).unwrap();
-
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].citation_key, "key1");
-        assert_eq!(result[1].citation_key, "key2");
-    }
-
-    #[test]
-    fn test_get_citation_difference_all_match() {
-        let document_citations = vec!["key1", "key2"];
-        let json_citations = vec![
-            Citations {
-                citation_key: "key1".to_string(),
-            },
-            Citations {
-                citation_key: "key2".to_string(),
-            },
-        ];
-
-        let result = get_citation_difference(document_citations, json_citations).unwrap();
-        assert_eq!(result.len(), 0); // No differences
-    }
-
-    #[test]
-    fn test_get_citation_difference_duplicates_in_document() {
-        let document_citations = vec!["key1", "key1", "key3"];
-        let json_citations = vec![
-            Citations {
-                citation_key: "key1".to_string(),
-            },
-            Citations {
-                citation_key: "key2".to_string(),
-            },
-            Citations {
-                citation_key: "key3".to_string(),
-            },
-        ];
-
-        let result = get_citation_difference(document_citations, json_citations).unwrap();
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].citation_key, "key2"); // key2 should still be the only difference
-    }
-
-    #[test]
-    fn test_get_citation_difference_empty_document() {
-        let document_citations: Vec<&str> = vec![];
-        let json_citations = vec![
-            Citations {
-                citation_key: "key1".to_string(),
-            },
-            Citations {
-                citation_key: "key2".to_string(),
-            },
-        ];
-
-        let result = get_citation_difference(document_citations, json_citations).unwrap();
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].citation_key, "key1");
-    }
-    #[test]
-    fn test_get_bibliography_path() {
-        let header = r#"---
-        bibliography: a/b/c
-        ---
-        "#;
-        assert_eq!(get_bibliography_path(header).unwrap(), "a/b/c");
-    }
-
-    #[test]
-    fn test_get_bibliography_path_tab() {
-        let header = r#"---
-        foo:
-        \tbar: baz
-        bibliography: a/b/c
-        "#;
-        assert_eq!(get_bibliography_path(header).unwrap(), "a/b/c");
+    if args.verbose {
+        println!("Found {} citations in document", citations_document.len());
     }
+
+    if let Some(Command::Stats { library, output, histogram }) = args.command {
+        if output == StatsOutput::Csv {
+            if library {
+                return Err("stats --library has no CSV output".into());
+            }
+            let citations_document = match hooks {
+                Some(ref hooks) => hooks.apply_keys(citations_document)?,
+                None => citations_document,
+            };
+            print!("{}", stats::to_csv(&stats::citation_density(&pandoc_text), &citations_document));
+            return Ok(());
+        }
+        print_density(&pandoc_text);
+        print_clusters(&pandoc_text);
+        if histogram {
+            print_section_chart(&pandoc_text);
+        }
+        if library {
+            let citations_document = match hooks {
+                Some(ref hooks) => hooks.apply_keys(citations_document)?,
+                None => citations_document,
+            };
+            let entries = library::get_library_entries(&bibliography_json)?;
+            let cited: HashSet<&str> = citations_document.iter().map(String::as_str).collect();
+            let library_stats = stats::library_stats(&entries, &cited);
+            print_library_stats(&library_stats);
+            if histogram {
+                println!("{}", msg!("chart-years"));
+                let years: Vec<_> = library_stats.by_year.into_iter().collect();
+                for line in stats::coverage_chart(&years) {
+                    println!("  {}", line);
+                }
+            }
+        }
+        timings.print();
+        return Ok(());
+    }
+
+    if args.verbose {
+        println!("Comparing sources in bibliography and cited sources");
+    }
+
+    let mut citations_document = citations_document;
+    let mut citations_bibliography = citations_bibliography;
+    let mut report = timings.time("diffing".to_string(), || {
+        check_keys(&mut citations_document, &mut citations_bibliography, hooks.as_ref())
+    })?;
+    report.unknown.retain(|key| !outside.contains(key));
+    let suppressed = suppress::apply(&mut report, &suppressions);
+    filter_uncited(&mut report, &bibliography_json, &args)?;
+    if let Some(Command::Annotate { ref out, dry_run }) = args.command {
+        // Exclusions blank the document in place, so its lines are those of the original
+        let original = encoding::decode(&document_bytes)?;
+        let annotated = annotate_document(&original, &document_md, &report,
+                                          &citations_document, &bibliography_json, &config.aliases)?;
+        if dry_run {
+            print_diff(&document_name, &out.display().to_string(), &original, &annotated);
+            return Ok(());
+        }
+        safe_write::write(out, annotated.as_bytes(), false)?;
+        println!("{}", msg!("annotate-written", path = out.display().to_string(), unknown = report.unknown.len(), uncited = report.uncited.len()));
+        return Ok(());
+    }
+    let mut findings = rules::check(&report, &citations_document, &citations_bibliography,
+                                    &bibliography_json, &config.aliases)?;
+    findings.extend(conflicts);
+    findings.extend(timings.time(format!("front matter checks {}", document_name), || {
+        rules::check_metadata(&document_md, document_md_input.path())
+    }));
+    if let Some(path) = document_md_input.path() {
+        for library in &libraries {
+            if let source::BibliographySource::File(library) = library {
+                findings.extend(rules::check_export_age(path, library, config.stale_export_window()));
+            }
+        }
+    }
+    if let Some(ref pattern) = key_pattern {
+        findings.extend(rules::check_key_pattern(&citations_bibliography, pattern));
+    }
+    if let Some(max) = args.max_cites_per_entry {
+        findings.extend(rules::check_citation_counts(&citations_document, max));
+    }
+    if let Some(ref required) = args.required {
+        findings.extend(rules::check_required(&required_keys(required, &bibliography_json)?, &citations_document));
+    }
+    if !config.require_locator.is_empty() {
+        let entries = library::get_library_entries(&bibliography_json)?;
+        let cites = cites.unwrap_or_else(|| pandoc_cites(&document_md, syntax, config.legal));
+        findings.extend(rules::check_locators(&cites, &entries, &config.require_locator));
+        for (path, document) in &included {
+            let cites = pandoc_cites(document, syntax_for(path, &registry, &args), config.legal);
+            for mut finding in rules::check_locators(&cites, &entries, &config.require_locator) {
+                finding.document = Some(path.display().to_string());
+                findings.push(finding);
+            }
+        }
+    }
+    let repeated = repeated.unwrap_or_else(|| pandoc_repeated(&document_md, syntax, config.legal));
+    findings.extend(rules::check_repeated_citations(&repeated));
+    for (path, document) in &included {
+        let repeated = pandoc_repeated(document, syntax_for(path, &registry, &args), config.legal);
+        for mut finding in rules::check_repeated_citations(&repeated) {
+            finding.document = Some(path.display().to_string());
+            findings.push(finding);
+        }
+    }
+    rules::sort(&mut findings);
+    // A document read in chunks isn't at hand for the citations of the --on-complete report
+    let citations = (!args.chunked).then(|| {
+        let mut citations = report_citations(None, &document_md, syntax);
+        for (path, document) in &included {
+            citations.extend(report_citations(Some(path), document, syntax_for(path, &registry, &args)));
+        }
+        citations
+    });
+    if args.porcelain || args.summary {
+        if args.summary {
+            println!("{}", report.summary(suppressed.len()));
+        } else {
+            let documents: Vec<(String, &str)> = std::iter::once((document_name, document_md.as_str()))
+                .chain(included.iter().map(|(path, document)| (path.display().to_string(), document.as_str())))
+                .collect();
+            print_porcelain(&report, &suppressed, &documents, &zotero_lib);
+        }
+        write_badge(args.badge_endpoint.as_deref(), &report, suppressed.len())?;
+        on_complete(args.on_complete.as_deref(), &report, citations.as_deref());
+        timings.print();
+        exit_on_errors(error_count(&findings, &config.rules) + strict_errors(args.strict, skipped, suppressed.len()));
+        return Ok(());
+    }
+    let style = args.references.then(|| reference_style(document_md_input.path(), &document_md));
+    let listing = Listing::new(config.legal, style.as_ref(), &bibliography_json, &report)?;
+    let errors = print_findings(&report, &findings, &config.rules, &listing);
+    print_suppressed(&suppressed);
+    let errors = errors + strict_errors(args.strict, skipped, suppressed.len());
+    print_statistics(&report, &bibliography_json, args.density.then_some(&pandoc_text), &args)?;
+
+    write_badge(args.badge_endpoint.as_deref(), &report, suppressed.len())?;
+    on_complete(args.on_complete.as_deref(), &report, citations.as_deref());
+    timings.print();
+    exit_on_errors(errors);
+    Ok(())
 }
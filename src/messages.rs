@@ -0,0 +1,165 @@
+use clap::ValueEnum;
+use icu_collator::{Collator, CollatorOptions};
+use icu_locid::Locale;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+use zoterocoverage::rules::Finding;
+
+// Languages of the human-readable report, with their catalogs in locales/
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Lang {
+    En,
+    De,
+}
+
+impl Lang {
+    // From the locale variables in POSIX order of precedence, e.g. LANG=de_DE.UTF-8
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|variable| std::env::var(variable).ok())
+            .find(|locale| !locale.is_empty())
+            .map_or(Lang::En, |locale| Lang::from_locale(&locale))
+    }
+
+    // English for languages without a catalog
+    fn from_locale(locale: &str) -> Self {
+        let language = locale.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        if language.eq_ignore_ascii_case("de") { Lang::De } else { Lang::En }
+    }
+
+    fn catalog(self) -> (&'static str, &'static str) {
+        match self {
+            Lang::En => ("en", include_str!("../locales/en.ftl")),
+            Lang::De => ("de", include_str!("../locales/de.ftl")),
+        }
+    }
+}
+
+struct Messages {
+    lang: Lang,
+    bundle: FluentBundle<FluentResource>,
+}
+
+static MESSAGES: OnceLock<Messages> = OnceLock::new();
+
+fn bundle(lang: Lang) -> Result<FluentBundle<FluentResource>, String> {
+    let (id, source) = lang.catalog();
+    let langid: LanguageIdentifier = id.parse().map_err(|e| format!("{}: {}", id, e))?;
+    let resource = FluentResource::try_new(source.to_string())
+        .map_err(|(_, errors)| format!("invalid {}.ftl: {:?}", id, errors))?;
+    let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+    // No Unicode isolation marks around arguments, they end up in terminals and text files
+    bundle.set_use_isolating(false);
+    bundle.add_resource(resource).map_err(|errors| format!("invalid {}.ftl: {:?}", id, errors))?;
+    Ok(bundle)
+}
+
+// Select the language once, before anything is printed
+pub fn init(lang: Lang) -> Result<(), Box<dyn std::error::Error>> {
+    let _ = MESSAGES.set(Messages { lang, bundle: bundle(lang)? });
+    Ok(())
+}
+
+fn messages() -> &'static Messages {
+    MESSAGES.get_or_init(|| Messages { lang: Lang::En, bundle: bundle(Lang::En).unwrap() })
+}
+
+// The message with this id; the id itself if the catalog lacks it
+pub fn text(id: &str, args: Option<&FluentArgs>) -> String {
+    let bundle = &messages().bundle;
+    let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+        return id.to_string();
+    };
+    let mut errors = Vec::new();
+    bundle.format_pattern(pattern, args, &mut errors).into_owned()
+}
+
+// The message of a finding, after the document it is about if there are several
+pub fn finding(finding: &Finding) -> String {
+    let mut args = FluentArgs::new();
+    for (name, value) in &finding.args {
+        args.set(*name, value.as_str());
+    }
+    let message = text(finding.message, Some(&args));
+    let Some(ref document) = finding.document else {
+        return message;
+    };
+    let mut args = FluentArgs::new();
+    args.set("document", document.as_str());
+    args.set("message", message);
+    text("finding-in-document", Some(&args))
+}
+
+// A number with one decimal, with a decimal comma in German
+pub fn decimal(value: f64) -> String {
+    let formatted = format!("{:.1}", value);
+    match messages().lang {
+        Lang::De => formatted.replace('.', ","),
+        Lang::En => formatted,
+    }
+}
+
+// Sort keys alphabetically by the collation rules of the report language, so Köhler comes before
+// Zimmermann and case doesn't split the list; byte order if the collation data is unavailable
+pub fn sort(keys: &mut [String]) {
+    let (id, _) = messages().lang.catalog();
+    let collator = id
+        .parse::<Locale>()
+        .ok()
+        .and_then(|locale| Collator::try_new(&(&locale).into(), CollatorOptions::new()).ok());
+    match collator {
+        Some(collator) => keys.sort_by(|a, b| collator.compare(a, b)),
+        None => keys.sort(),
+    }
+}
+
+// msg!("uncited-heading", count = 3)
+macro_rules! msg {
+    ($id:literal) => {
+        $crate::messages::text($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::messages::text($id, Some(&args))
+    }};
+}
+pub(crate) use msg;
+
+#[cfg(test)]
+mod tests {
+    use super::{Lang, bundle, sort};
+
+    #[test]
+    fn test_catalogs() {
+        // Message ids start a line: id = ...
+        let ids = |lang: Lang| -> Vec<&str> {
+            let mut ids: Vec<&str> = lang
+                .catalog()
+                .1
+                .lines()
+                .filter(|line| line.starts_with(|c: char| c.is_ascii_lowercase()))
+                .filter_map(|line| line.split_once(" =").map(|(id, _)| id))
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert!(bundle(Lang::De).is_ok());
+        assert_eq!(ids(Lang::En), ids(Lang::De));
+
+        assert_eq!(Lang::from_locale("de_DE.UTF-8"), Lang::De);
+        assert_eq!(Lang::from_locale("de-AT"), Lang::De);
+        assert_eq!(Lang::from_locale("fr_FR"), Lang::En);
+        assert_eq!(Lang::from_locale("C"), Lang::En);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut keys: Vec<String> = ["Zimmermann.2020", "Öztürk.2019", "köhler.2021", "Köhler.2020"].map(String::from).into();
+        sort(&mut keys);
+        assert_eq!(keys, vec!["Köhler.2020", "köhler.2021", "Öztürk.2019", "Zimmermann.2020"]);
+    }
+}
@@ -0,0 +1,296 @@
+use regex::Regex;
+use roxmltree::{Document, Node};
+use serde_json::{Map, Value, json};
+
+// MODS XML (Metadata Object Description Schema), as exported by library catalogs and institutional
+// repositories: a <modsCollection> of <mods> records, or a single <mods> record.
+
+// Field holding the citation key when the config names none, see key_field
+pub const DEFAULT_KEY_FIELD: &str = "recordIdentifier";
+
+pub fn is_mods(bibliography: &str) -> bool {
+    let text = bibliography.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with('<') && (text.contains("<mods") || text.contains(":mods"))
+}
+
+// The element naming the citation key: an element name, e.g. recordIdentifier, optionally with the
+// value of its type attribute, e.g. identifier[citekey] for <identifier type="citekey">
+fn key_field(field: &str) -> (&str, Option<&str>) {
+    match field.strip_suffix(']').and_then(|f| f.split_once('[')) {
+        Some((name, kind)) => (name.trim(), Some(kind.trim())),
+        None => (field.trim(), None),
+    }
+}
+
+// The records as CSL-JSON entries keyed by key_field. Records without it keep their ID attribute
+// as id, so the usual id fallback and warnings apply.
+pub fn to_csl_json(bibliography: &str, key_field_name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let document = Document::parse(bibliography.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("invalid MODS XML: {}", e))?;
+    let root = document.root_element();
+    let records: Vec<Node> = if root.tag_name().name() == "mods" {
+        vec![root]
+    } else {
+        children(root, "mods").collect()
+    };
+    let (name, kind) = key_field(key_field_name);
+    let entries: Vec<Value> = records
+        .iter()
+        .enumerate()
+        .map(|(position, record)| record_to_csl(*record, position, name, kind))
+        .collect();
+    Ok(serde_json::to_string(&entries)?)
+}
+
+fn children<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn text(node: Node) -> Option<String> {
+    let text: String = node.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+// Elements of the record itself; those of related items (the journal of an article) don't count
+fn own_elements<'a, 'input>(record: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    record.descendants().filter(move |node| {
+        node.is_element()
+            && node.tag_name().name() == name
+            && !node.ancestors().take_while(|a| *a != record).any(|a| a.tag_name().name() == "relatedItem")
+    })
+}
+
+fn record_to_csl(record: Node, position: usize, key_name: &str, key_type: Option<&str>) -> Value {
+    let mut entry = Map::new();
+    let id = record.attribute("ID").map_or_else(|| format!("mods-{}", position + 1), str::to_string);
+    entry.insert("id".into(), Value::from(id));
+    let key = own_elements(record, key_name)
+        .filter(|node| key_type.is_none_or(|kind| node.attribute("type") == Some(kind)))
+        .find_map(text);
+    if let Some(key) = key {
+        entry.insert("citation-key".into(), Value::from(key));
+    }
+    if let Some(kind) = own_elements(record, "genre").find_map(text).and_then(|genre| csl_type(&genre)) {
+        entry.insert("type".into(), Value::from(kind));
+    }
+    if let Some(title) = title(record) {
+        entry.insert("title".into(), Value::from(title));
+    }
+
+    let mut authors = Vec::new();
+    let mut editors = Vec::new();
+    for name in children(record, "name") {
+        let role = own_elements(name, "roleTerm").find_map(text).map(|role| role.to_lowercase());
+        match role.as_deref() {
+            None | Some("aut" | "author" | "cre" | "creator") => authors.push(csl_name(name)),
+            Some("edt" | "editor" | "hrsg." | "herausgeber") => editors.push(csl_name(name)),
+            Some(_) => {}
+        }
+    }
+    for (field, names) in [("author", authors), ("editor", editors)] {
+        if !names.is_empty() {
+            entry.insert(field.into(), Value::from(names));
+        }
+    }
+
+    if let Some(origin) = child(record, "originInfo") {
+        if let Some(issued) = child(origin, "dateIssued").and_then(text).and_then(|date| csl_date(&date)) {
+            entry.insert("issued".into(), issued);
+        }
+        if let Some(publisher) = child(origin, "publisher").and_then(text) {
+            entry.insert("publisher".into(), Value::from(publisher));
+        }
+        if let Some(place) = own_elements(origin, "placeTerm").find_map(text) {
+            entry.insert("publisher-place".into(), Value::from(place));
+        }
+    }
+    if let Some(language) = own_elements(record, "languageTerm").find_map(text) {
+        entry.insert("language".into(), Value::from(language_code(&language)));
+    }
+    for identifier in children(record, "identifier") {
+        let field = match identifier.attribute("type").map(str::to_lowercase).as_deref() {
+            Some("doi") => "DOI",
+            Some("isbn") => "ISBN",
+            Some("issn") => "ISSN",
+            Some("uri" | "url") => "URL",
+            _ => continue,
+        };
+        if let Some(value) = text(identifier) {
+            entry.entry(field).or_insert(Value::from(value));
+        }
+    }
+
+    let host = children(record, "relatedItem").find(|item| item.attribute("type") == Some("host"));
+    if let Some(host) = host {
+        if let Some(container) = title(host) {
+            entry.insert("container-title".into(), Value::from(container));
+        }
+        let part = child(record, "part").or_else(|| child(host, "part"));
+        if let Some(part) = part {
+            for detail in children(part, "detail") {
+                let field = match detail.attribute("type") {
+                    Some("volume") => "volume",
+                    Some("issue") => "issue",
+                    _ => continue,
+                };
+                if let Some(number) = child(detail, "number").and_then(text) {
+                    entry.insert(field.into(), Value::from(number));
+                }
+            }
+            if let Some(extent) = child(part, "extent") {
+                let pages = match (child(extent, "start").and_then(text), child(extent, "end").and_then(text)) {
+                    (Some(start), Some(end)) => Some(format!("{}-{}", start, end)),
+                    (start, _) => start,
+                };
+                if let Some(pages) = pages {
+                    entry.insert("page".into(), Value::from(pages));
+                }
+            }
+            if !entry.contains_key("issued")
+                && let Some(issued) = child(part, "date").and_then(text).and_then(|date| csl_date(&date))
+            {
+                entry.insert("issued".into(), issued);
+            }
+        }
+    }
+    Value::Object(entry)
+}
+
+// The main title with its subtitle; titleInfo with a type are alternative, abbreviated or translated titles
+fn title(node: Node) -> Option<String> {
+    let info = children(node, "titleInfo")
+        .find(|info| info.attribute("type").is_none())
+        .or_else(|| child(node, "titleInfo"))?;
+    let title = child(info, "title").and_then(text)?;
+    let title = match child(info, "nonSort").and_then(text) {
+        Some(article) => format!("{} {}", article, title),
+        None => title,
+    };
+    Some(match child(info, "subTitle").and_then(text) {
+        Some(subtitle) => format!("{}: {}", title, subtitle),
+        None => title,
+    })
+}
+
+// namePart type="family" and "given", else the whole name as literal (institutions, "Müller, Anna")
+fn csl_name(name: Node) -> Value {
+    let part = |kind: &str| children(name, "namePart").find(|p| p.attribute("type") == Some(kind)).and_then(text);
+    match (part("family"), part("given")) {
+        (Some(family), given) => json!({"family": family, "given": given}),
+        (None, _) => {
+            let parts: Vec<String> = children(name, "namePart")
+                .filter(|p| p.attribute("type").is_none())
+                .filter_map(text)
+                .collect();
+            json!({"literal": parts.join(" ")})
+        }
+    }
+}
+
+// Dates like 2023, 2023-05-10, [2023] or c2023
+fn csl_date(date: &str) -> Option<Value> {
+    let re = Regex::new(r"(\d{4})(?:-(\d{2}))?(?:-(\d{2}))?").unwrap();
+    let caps = re.captures(date)?;
+    let parts: Vec<Value> = caps
+        .iter()
+        .skip(1)
+        .map_while(|part| part.and_then(|p| p.as_str().parse::<i64>().ok()))
+        .map(Value::from)
+        .collect();
+    Some(json!({"date-parts": [parts]}))
+}
+
+// MODS genres (MARC genre terms or DINI document types like doc-type:doctoralThesis) as CSL types
+fn csl_type(genre: &str) -> Option<&'static str> {
+    let genre = genre.strip_prefix("doc-type:").unwrap_or(genre).to_lowercase().replace([' ', '_', '-'], "");
+    let kind = match genre.as_str() {
+        "article" | "journalarticle" | "contributiontoperiodical" | "periodicalpart" => "article-journal",
+        "book" | "monograph" => "book",
+        "bookpart" | "chapter" | "booksection" => "chapter",
+        "thesis" | "doctoralthesis" | "masterthesis" | "bachelorthesis" | "habilitation" => "thesis",
+        "report" | "technicalreport" | "workingpaper" => "report",
+        "conferenceobject" | "conferencepublication" | "conferencepaper" => "paper-conference",
+        _ => return None,
+    };
+    Some(kind)
+}
+
+// ISO 639-2 codes of MODS languageTerm as the two-letter codes of CSL; others are kept
+fn language_code(language: &str) -> String {
+    match language.to_lowercase().as_str() {
+        "ger" | "deu" => "de".to_string(),
+        "eng" => "en".to_string(),
+        "fre" | "fra" => "fr".to_string(),
+        "ita" => "it".to_string(),
+        "spa" => "es".to_string(),
+        _ => language.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_KEY_FIELD, is_mods, to_csl_json};
+    use serde_json::{Value, json};
+
+    const COLLECTION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<modsCollection xmlns="http://www.loc.gov/mods/v3">
+  <mods ID="repo-17">
+    <titleInfo><title>Künstliche Intelligenz</title><subTitle>Haftung und Regulierung</subTitle></titleInfo>
+    <titleInfo type="translated"><title>Artificial Intelligence</title></titleInfo>
+    <name type="personal">
+      <namePart type="family">Müller</namePart><namePart type="given">Anna</namePart>
+      <role><roleTerm type="code" authority="marcrelator">aut</roleTerm></role>
+    </name>
+    <genre authority="dini">doc-type:article</genre>
+    <originInfo><dateIssued encoding="w3cdtf">2023-05</dateIssued></originInfo>
+    <language><languageTerm type="code" authority="iso639-2b">ger</languageTerm></language>
+    <identifier type="doi">10.1000/xyz</identifier>
+    <identifier type="citekey">Mueller.2023</identifier>
+    <relatedItem type="host">
+      <titleInfo><title>Zeitschrift für Digitalrecht</title></titleInfo>
+      <identifier type="issn">1234-5678</identifier>
+      <part><detail type="volume"><number>3</number></detail><extent unit="pages"><start>10</start><end>25</end></extent></part>
+    </relatedItem>
+    <recordInfo><recordIdentifier>urn:nbn:de:17</recordIdentifier></recordInfo>
+  </mods>
+  <mods>
+    <titleInfo><title>Festschrift</title></titleInfo>
+    <name><namePart>Bundesamt für Justiz</namePart></name>
+    <name><namePart type="family">Köhler</namePart><role><roleTerm type="text">editor</roleTerm></role></name>
+    <genre>book</genre>
+  </mods>
+</modsCollection>"#;
+
+    #[test]
+    fn test_to_csl_json() {
+        assert!(is_mods(COLLECTION));
+        assert!(!is_mods(r#"[{"citation-key": "A"}]"#));
+
+        let entries: Vec<Value> = serde_json::from_str(&to_csl_json(COLLECTION, "recordIdentifier").unwrap()).unwrap();
+        assert_eq!(entries[0]["citation-key"], "urn:nbn:de:17");
+        assert_eq!(entries[0]["id"], "repo-17");
+        assert_eq!(entries[0]["type"], "article-journal");
+        assert_eq!(entries[0]["title"], "Künstliche Intelligenz: Haftung und Regulierung");
+        assert_eq!(entries[0]["author"], json!([{"family": "Müller", "given": "Anna"}]));
+        assert_eq!(entries[0]["issued"], json!({"date-parts": [[2023, 5]]}));
+        assert_eq!(entries[0]["language"], "de");
+        assert_eq!(entries[0]["DOI"], "10.1000/xyz");
+        assert_eq!(entries[0].get("ISSN"), None);
+        assert_eq!(entries[0]["container-title"], "Zeitschrift für Digitalrecht");
+        assert_eq!((&entries[0]["volume"], &entries[0]["page"]), (&json!("3"), &json!("10-25")));
+
+        assert_eq!(entries[1].get("citation-key"), None);
+        assert_eq!(entries[1]["id"], "mods-2");
+        assert_eq!(entries[1]["author"], json!([{"literal": "Bundesamt für Justiz"}]));
+        assert_eq!(entries[1]["editor"], json!([{"family": "Köhler", "given": null}]));
+
+        let entries: Vec<Value> = serde_json::from_str(&to_csl_json(COLLECTION, "identifier[citekey]").unwrap()).unwrap();
+        assert_eq!(entries[0]["citation-key"], "Mueller.2023");
+        assert!(to_csl_json("<mods><titleInfo>", DEFAULT_KEY_FIELD).is_err());
+    }
+}
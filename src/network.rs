@@ -0,0 +1,168 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ureq::Body;
+use ureq::http::Response;
+
+use crate::source::NetworkOptions;
+
+// Longest we wait between two attempts unless the server asks for more
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// Delay before retry number `attempt` (starting at 0): what the server asked for via
+// Retry-After / Backoff, otherwise exponential backoff starting at one second
+pub fn retry_delay(attempt: u32, requested: Option<Duration>) -> Duration {
+    requested.unwrap_or_else(|| Duration::from_secs(1u64 << attempt.min(6)).min(MAX_BACKOFF))
+}
+
+fn seconds_header(response: &Response<Body>, name: &str) -> Option<Duration> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+// Runtime for loading network-backed sources concurrently. Requests are blocking ureq calls
+// run on its blocking thread pool, limited to `jobs` threads; async code only coordinates them.
+pub fn runtime(jobs: Option<NonZeroUsize>) -> Result<tokio::runtime::Runtime, Box<dyn std::error::Error>> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if let Some(jobs) = jobs {
+        builder.worker_threads(jobs.get()).max_blocking_threads(jobs.get());
+    }
+    Ok(builder.build()?)
+}
+
+// Blocking HTTP client retrying transient failures and honoring the Zotero API's
+// Backoff (pause before the next request) and Retry-After (retry later) headers.
+// It can be shared between threads, a Backoff then pauses all of them.
+pub struct HttpClient {
+    agent: ureq::Agent,
+    retries: u32,
+    not_before: Mutex<Option<Instant>>,
+}
+
+impl HttpClient {
+    pub fn new(options: &NetworkOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = ureq::Agent::config_builder()
+            .timeout_global(Some(options.timeout))
+            .http_status_as_error(false);
+        // ureq picks up the proxy environment variables by default
+        if let Some(ref proxy) = options.proxy {
+            let proxy = ureq::Proxy::new(proxy).map_err(|e| format!("invalid proxy {}: {}", proxy, e))?;
+            config = config.proxy(Some(proxy));
+        }
+        Ok(HttpClient::with_agent(ureq::Agent::new_with_config(config.build()), options))
+    }
+
+    // For servers on this computer, Zotero's local API and Better BibTeX, which a proxy can't
+    // reach; neither --proxy (not subject to NO_PROXY) nor the proxy environment variables apply
+    pub fn local(options: &NetworkOptions) -> Self {
+        let config = ureq::Agent::config_builder()
+            .timeout_global(Some(options.timeout))
+            .http_status_as_error(false)
+            .proxy(None);
+        HttpClient::with_agent(ureq::Agent::new_with_config(config.build()), options)
+    }
+
+    fn with_agent(agent: ureq::Agent, options: &NetworkOptions) -> Self {
+        HttpClient {
+            agent,
+            retries: options.retries,
+            not_before: Mutex::new(None),
+        }
+    }
+
+    fn wait_for_backoff(&self) {
+        let not_before = *self.not_before.lock().unwrap();
+        if let Some(not_before) = not_before {
+            let now = Instant::now();
+            if not_before > now {
+                thread::sleep(not_before - now);
+            }
+        }
+    }
+
+    // GET with the given headers; 2xx and 304 responses are returned, 429 / 5xx and
+    // transport errors are retried, other statuses fail immediately
+    pub fn get(&self, url: &str, headers: &[(&str, String)]) -> Result<Response<Body>, Box<dyn std::error::Error>> {
+        let mut attempt = 0;
+        loop {
+            self.wait_for_backoff();
+
+            let mut request = self.agent.get(url);
+            for (name, value) in headers {
+                request = request.header(*name, value);
+            }
+
+            let (error, requested): (Box<dyn std::error::Error>, _) = match request.call() {
+                Ok(response) => {
+                    if let Some(backoff) = seconds_header(&response, "Backoff") {
+                        *self.not_before.lock().unwrap() = Some(Instant::now() + backoff);
+                    }
+                    let status = response.status().as_u16();
+                    if status < 400 {
+                        return Ok(response);
+                    }
+                    let error = format!("HTTP {} for {}", status, url).into();
+                    if status != 429 && status < 500 {
+                        return Err(error);
+                    }
+                    (error, seconds_header(&response, "Retry-After"))
+                }
+                Err(e) => (e.into(), None),
+            };
+
+            if attempt >= self.retries {
+                return Err(error);
+            }
+            let delay = retry_delay(attempt, requested);
+            eprintln!("Warning: request failed ({}), retrying in {}s", error, delay.as_secs());
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpClient, retry_delay};
+    use crate::source::NetworkOptions;
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(0, None), Duration::from_secs(1));
+        assert_eq!(retry_delay(3, None), Duration::from_secs(8));
+        assert_eq!(retry_delay(20, None), Duration::from_secs(60));
+        assert_eq!(retry_delay(0, Some(Duration::from_secs(120))), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_http_client_proxy() {
+        let mut options = NetworkOptions {
+            timeout: Duration::from_secs(1),
+            retries: 0,
+            proxy: Some("http://proxy.example.org:8080".to_string()),
+            jobs: None,
+        };
+        assert!(HttpClient::new(&options).is_ok());
+        options.proxy = Some("ftp://proxy.example.org".to_string());
+        assert!(HttpClient::new(&options).is_err());
+    }
+
+    #[test]
+    fn test_local_client_without_proxy() {
+        let options = NetworkOptions {
+            timeout: Duration::from_secs(1),
+            retries: 0,
+            proxy: Some("http://proxy.example.org:8080".to_string()),
+            jobs: None,
+        };
+        assert!(HttpClient::new(&options).unwrap().agent.config().proxy().is_some());
+        assert!(HttpClient::local(&options).agent.config().proxy().is_none());
+    }
+}
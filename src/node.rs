@@ -0,0 +1,42 @@
+use napi_derive::napi;
+use std::path::Path;
+
+use crate::CoverageReport;
+
+// Coverage report as a plain JavaScript object
+#[napi(object, js_name = "CoverageReport")]
+pub struct JsCoverageReport {
+    pub cited: Vec<String>,
+    pub uncited: Vec<String>,
+    pub unknown: Vec<String>,
+}
+
+impl From<CoverageReport> for JsCoverageReport {
+    fn from(report: CoverageReport) -> Self {
+        JsCoverageReport {
+            cited: report.cited,
+            uncited: report.uncited,
+            unknown: report.unknown,
+        }
+    }
+}
+
+fn to_napi_err(e: Box<dyn std::error::Error>) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
+
+// Compare a document with a CSL-JSON bibliography, both given as strings
+#[napi]
+pub fn check_coverage(document: String, bibliography: String) -> napi::Result<JsCoverageReport> {
+    let report = crate::check_coverage(&document, &bibliography).map_err(to_napi_err)?;
+    Ok(report.into())
+}
+
+// Same as checkCoverage, but reading files; without a bibliography the one named
+// in the document's yaml header is used
+#[napi]
+pub fn check_files(document: String, bibliography: Option<String>) -> napi::Result<JsCoverageReport> {
+    let report =
+        crate::check_files(Path::new(&document), bibliography.as_deref().map(Path::new)).map_err(to_napi_err)?;
+    Ok(report.into())
+}
@@ -0,0 +1,96 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zoterocoverage::extract::shell;
+
+// Run the --on-complete command with the JSON report on stdin, or, if the command names {json},
+// with the path of a temporary file holding it
+pub fn run(command: &str, json: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let status = if command.contains("{json}") {
+        let path = report_file(json)?;
+        let status = shell(&command.replace("{json}", &quote(&path.display().to_string()))).status();
+        let _ = fs::remove_file(&path);
+        status?
+    } else {
+        let mut child = shell(command).stdin(Stdio::piped()).spawn()?;
+        // Commands may ignore the report on stdin
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(json.as_bytes());
+        }
+        child.wait()?
+    };
+    if !status.success() {
+        return Err(status.to_string().into());
+    }
+    Ok(())
+}
+
+// A new file in the temporary directory, readable by the user only. Names aren't predictable and
+// create_new neither follows a symlink someone else put there nor opens an existing file.
+fn report_file(json: &str) -> std::io::Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+    for attempt in 0..100u32 {
+        let name = format!("zoterocoverage-{}-{:08x}.json", std::process::id(), nanos.wrapping_add(attempt.wrapping_mul(0x9e37_79b9)));
+        let path = std::env::temp_dir().join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(json.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(std::io::Error::new(ErrorKind::AlreadyExists, "no unused name for the report file"))
+}
+
+// The path as one word for sh -c or cmd /C, whatever characters the temporary directory has
+fn quote(path: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote, run};
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        let directory = std::env::temp_dir().join("zoterocoverage-on-complete");
+        std::fs::create_dir_all(&directory).unwrap();
+        let json = r#"{"schema_version": 1}"#;
+
+        let stdin = directory.join("stdin.json");
+        run(&format!("cat > {}", quote(&stdin.display().to_string())), json).unwrap();
+        assert_eq!(std::fs::read_to_string(&stdin).unwrap(), json);
+
+        // The file is gone after the command, so it is copied
+        let copy = directory.join("copy.json");
+        run(&format!("cp {{json}} {}", quote(&copy.display().to_string())), json).unwrap();
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), json);
+        run("test ! -e {json} && exit 1; exit 0", json).unwrap();
+
+        assert_eq!(run("exit 3", json).unwrap_err().to_string(), "exit status: 3");
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_quote() {
+        if cfg!(windows) {
+            assert_eq!(quote(r"C:\Users\A B\tmp.json"), r#""C:\Users\A B\tmp.json""#);
+        } else {
+            assert_eq!(quote("/tmp/it's here.json"), r"'/tmp/it'\''s here.json'");
+        }
+    }
+}
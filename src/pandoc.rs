@@ -0,0 +1,514 @@
+use std::ops::Range;
+
+// How a citation is written, following pandoc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CitationMode {
+    // In a bracketed cluster: [see @Smith.2009, p. 3]
+    Normal,
+    // In a cluster with the author suppressed: [-@Smith.2009]
+    SuppressAuthor,
+    // In the text: @Smith.2009 says, optionally with a suffix in brackets: @Smith.2009 [p. 3]
+    AuthorInText,
+}
+
+// One citation with its prefix and suffix (locator and comments) as written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub key: String,
+    pub mode: CitationMode,
+    pub prefix: String,
+    pub suffix: String,
+    // Byte range of the key including @ and braces
+    pub span: Range<usize>,
+    // Inside an inline footnote ^[...] or a footnote definition [^1]: ...
+    pub footnote: bool,
+    // Byte range of the bracketed cluster [...] the citation is part of, None for citations in the text
+    pub cluster: Option<Range<usize>>,
+}
+
+// Characters allowed inside a key when followed by a letter, digit or _
+const INTERNAL_PUNCTUATION: &str = ":.#$%&-+?<>~/";
+
+fn is_key_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// The document with code blocks, code spans, HTML comments and escaped characters blanked out,
+// keeping byte offsets and line breaks, since pandoc finds no citations there
+pub(crate) fn mask(document: &str) -> String {
+    let mut masked = document.as_bytes().to_vec();
+    let blank = |masked: &mut [u8], range: Range<usize>| {
+        for byte in &mut masked[range] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    };
+
+    // Fenced code blocks: ``` or ~~~ up to a closing fence at least as long
+    let mut fence: Option<(u8, usize)> = None;
+    let mut offset = 0;
+    for line in document.split_inclusive('\n') {
+        let trimmed = line.trim_start_matches(' ');
+        let indented = line.len() - trimmed.len() >= 4;
+        let run = |c: u8| trimmed.bytes().take_while(|&b| b == c).count();
+        match fence {
+            Some((c, length)) => {
+                blank(&mut masked, offset..offset + line.len());
+                if !indented && run(c) >= length && trimmed[run(c)..].trim().is_empty() {
+                    fence = None;
+                }
+            }
+            None => {
+                if let Some(c) = [b'`', b'~'].into_iter().find(|&c| run(c) >= 3)
+                    && !indented
+                {
+                    fence = Some((c, run(c)));
+                    blank(&mut masked, offset..offset + line.len());
+                }
+            }
+        }
+        offset += line.len();
+    }
+
+    let mut i = 0;
+    while i < masked.len() {
+        match masked[i] {
+            b'\\' if masked.get(i + 1).is_some_and(u8::is_ascii_punctuation) => {
+                blank(&mut masked, i..i + 2);
+                i += 2;
+            }
+            b'<' if masked[i..].starts_with(b"<!--") => {
+                let end = find(&masked, b"-->", i + 4).map_or(masked.len(), |end| end + 3);
+                blank(&mut masked, i..end);
+                i = end;
+            }
+            b'`' => {
+                // A code span closes with a backtick run of the same length, otherwise the backticks are literal
+                let length = masked[i..].iter().take_while(|&&b| b == b'`').count();
+                let mut j = i + length;
+                let mut close = None;
+                while let Some(start) = masked[j..].iter().position(|&b| b == b'`').map(|p| p + j) {
+                    let run = masked[start..].iter().take_while(|&&b| b == b'`').count();
+                    if run == length {
+                        close = Some(start + run);
+                        break;
+                    }
+                    j = start + run;
+                }
+                match close {
+                    Some(end) => {
+                        blank(&mut masked, i..end);
+                        i = end;
+                    }
+                    None => i += length,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    // Only whole characters were replaced by spaces
+    String::from_utf8(masked).unwrap()
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack[from..].windows(needle.len()).position(|w| w == needle).map(|p| p + from)
+}
+
+// The key of a citation starting with the @ at `at`, and the end of the citation:
+// @{any key} or a letter, digit or _ followed by those and single internal punctuation
+fn parse_key(text: &str, at: usize) -> Option<(String, usize)> {
+    if text[..at].chars().next_back().is_some_and(is_key_char) {
+        // e-mail addresses
+        return None;
+    }
+    let rest = &text[at + 1..];
+    if let Some(braced) = rest.strip_prefix('{') {
+        let close = braced.find('}')?;
+        let key = braced[..close].trim();
+        return (!key.is_empty()).then(|| (key.to_string(), at + close + 3));
+    }
+    let mut chars = rest.char_indices().peekable();
+    let mut end = 0;
+    while let Some((i, c)) = chars.next() {
+        if is_key_char(c) {
+            end = i + c.len_utf8();
+        } else if end > 0
+            && INTERNAL_PUNCTUATION.contains(c)
+            && chars.peek().is_some_and(|&(_, next)| is_key_char(next))
+        {
+            continue;
+        } else {
+            break;
+        }
+    }
+    (end > 0).then(|| (rest[..end].to_string(), at + 1 + end))
+}
+
+// Byte ranges of inline footnotes ^[...] and of footnote definitions: [^label]: up to the next blank
+// line that isn't followed by an indented continuation
+fn footnote_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = text
+        .match_indices("^[")
+        .filter_map(|(caret, _)| matching_bracket(text, caret + 1).map(|close| caret..close + 1))
+        .collect();
+
+    let mut definition: Option<usize> = None;
+    let mut blank = false;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let is_definition = line.starts_with("[^") && line.find("]:").is_some_and(|end| !line[2..end].contains(' '));
+        if let Some(start) = definition {
+            let continued = !blank || line.starts_with("    ") || line.starts_with('\t') || line.trim().is_empty();
+            if is_definition || !continued {
+                ranges.push(start..offset);
+                definition = None;
+            }
+        }
+        if is_definition {
+            definition = Some(offset);
+        }
+        blank = line.trim().is_empty();
+        offset += line.len();
+    }
+    if let Some(start) = definition {
+        ranges.push(start..offset);
+    }
+    ranges
+}
+
+// The ], ) or } matching the bracket at `open`. Brackets may wrap across lines (hard-wrapped
+// markdown) but not across a blank line, which ends the paragraph.
+pub(crate) fn matching_bracket(text: &str, open: usize) -> Option<usize> {
+    let (opening, closing) = match text[open..].chars().next()? {
+        '(' => ('(', ')'),
+        '{' => ('{', '}'),
+        _ => ('[', ']'),
+    };
+    let mut depth = 0;
+    // Only whitespace since the last line break
+    let mut blank = false;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            c if c == opening => depth += 1,
+            c if c == closing => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            '\n' if blank => return None,
+            '\n' => {
+                blank = true;
+                continue;
+            }
+            _ => {}
+        }
+        if !c.is_whitespace() {
+            blank = false;
+        }
+    }
+    None
+}
+
+// Prefixes and suffixes with line breaks and indentation as single spaces, like pandoc reads them
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// The citations of the bracketed cluster at `open` if every part separated by ; has a key.
+// Not clusters: inline footnotes ^[...] and links [...](...).
+fn parse_cluster(text: &str, open: usize, claimed: &[bool]) -> Option<(Vec<Citation>, usize)> {
+    if text[..open].ends_with('^') {
+        return None;
+    }
+    let close = matching_bracket(text, open)?;
+    if text[close + 1..].starts_with('(') {
+        return None;
+    }
+    let mut citations = Vec::new();
+    let mut start = open + 1;
+    let mut depth = 0;
+    for (i, c) in text[open + 1..=close].char_indices().map(|(i, c)| (open + 1 + i, c)) {
+        match c {
+            '[' => depth += 1,
+            ']' if depth > 0 => depth -= 1,
+            ';' | ']' if depth == 0 => {
+                let at = text[start..i]
+                    .match_indices('@')
+                    .map(|(p, _)| start + p)
+                    .find(|at| !claimed[*at] && parse_key(text, *at).is_some())?;
+                let (key, end) = parse_key(text, at).filter(|(_, end)| *end <= i)?;
+                let mut prefix = text[start..at].trim_end();
+                let mode = match prefix.strip_suffix('-') {
+                    Some(stripped) => {
+                        prefix = stripped;
+                        CitationMode::SuppressAuthor
+                    }
+                    None => CitationMode::Normal,
+                };
+                citations.push(Citation {
+                    key,
+                    mode,
+                    prefix: collapse_whitespace(prefix),
+                    suffix: collapse_whitespace(&text[end..i]),
+                    span: at..end,
+                    footnote: false,
+                    cluster: Some(open..close + 1),
+                });
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    Some((citations, close))
+}
+
+// All citations in document order
+pub fn parse_citations(document: &str) -> Vec<Citation> {
+    let text = mask(document);
+    let mut citations: Vec<Citation> = Vec::new();
+
+    // Clusters first, innermost (last opened) first so nested brackets don't swallow their keys.
+    // Bytes of the citations found so far are claimed, their @ isn't read again.
+    let mut claimed = vec![false; text.len()];
+    let opens: Vec<usize> = text.match_indices('[').map(|(i, _)| i).collect();
+    for &open in opens.iter().rev() {
+        if let Some((cluster, _)) = parse_cluster(&text, open, &claimed) {
+            for citation in &cluster {
+                claimed[citation.span.clone()].fill(true);
+            }
+            citations.extend(cluster);
+        }
+    }
+
+    // Everything else is cited in the text, with an optional suffix in brackets right after the key
+    for (at, _) in text.match_indices('@') {
+        if claimed[at] {
+            continue;
+        }
+        let Some((key, end)) = parse_key(&text, at) else {
+            continue;
+        };
+        let after = &text[end..];
+        let bracket_start = end + after.len() - after.trim_start_matches(' ').len();
+        let suffix = text[bracket_start..]
+            .starts_with('[')
+            .then(|| matching_bracket(&text, bracket_start))
+            .flatten()
+            .map(|close| &text[bracket_start + 1..close])
+            .filter(|suffix| !suffix.contains('@') && bracket_start - end <= 1);
+        citations.push(Citation {
+            key,
+            mode: CitationMode::AuthorInText,
+            prefix: String::new(),
+            suffix: collapse_whitespace(suffix.unwrap_or_default()),
+            span: at..end,
+            footnote: false,
+            cluster: None,
+        });
+    }
+
+    let mut in_footnote = vec![false; text.len()];
+    for footnote in footnote_ranges(&text) {
+        in_footnote[footnote].fill(true);
+    }
+    for citation in &mut citations {
+        citation.footnote = in_footnote[citation.span.start];
+    }
+    citations.sort_by_key(|c| c.span.start);
+    citations
+}
+
+// Citations of a key already cited in the same footnote or sentence, like [@BGH.2024; @BGH.2024 Rn. 12].
+// Inline footnotes and footnote definitions are units of their own; elsewhere sentences end at
+// . ! or ? before whitespace and at blank lines, outside brackets. Abbreviations like vgl. end a
+// sentence too, which can only hide repetitions.
+pub fn repeated_citations(document: &str) -> Vec<Citation> {
+    let text = mask(document);
+    let footnotes = footnote_ranges(&text);
+    let mut sentence_ends = Vec::new();
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if let Some(footnote) = footnotes.iter().find(|footnote| footnote.start == i) {
+            while chars.next_if(|&(j, _)| j < footnote.end).is_some() {}
+            continue;
+        }
+        let next = chars.peek().map(|&(_, next)| next);
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            '.' | '!' | '?' if depth == 0 && next.is_none_or(char::is_whitespace) => sentence_ends.push(i + 1),
+            '\n' if text[i + 1..].trim_start_matches([' ', '\t']).starts_with('\n') => {
+                sentence_ends.push(i);
+                depth = 0;
+            }
+            _ => {}
+        }
+    }
+
+    // Innermost footnote, else the number of the sentence
+    let unit = |start: usize| -> (Option<Range<usize>>, usize) {
+        let footnote = footnotes.iter().filter(|footnote| footnote.contains(&start)).min_by_key(|footnote| footnote.len());
+        match footnote {
+            Some(footnote) => (Some(footnote.clone()), 0),
+            None => (None, sentence_ends.partition_point(|&end| end <= start)),
+        }
+    };
+    let mut seen = std::collections::HashSet::new();
+    parse_citations(document)
+        .into_iter()
+        .filter(|citation| !seen.insert((unit(citation.span.start), citation.key.clone())))
+        .collect()
+}
+
+// Citations grouped the way a reader sees them: all citations of a footnote (the innermost inline
+// footnote or footnote definition), else those of a bracketed cluster, else a citation in the text
+// on its own. In legal writing the footnote is the unit, e.g. ^[vgl. @BGH.2024 Rn. 3; @Alexander.2024].
+pub fn citation_clusters(document: &str) -> Vec<Vec<Citation>> {
+    let footnotes = footnote_ranges(&mask(document));
+    let unit = |citation: &Citation| -> Range<usize> {
+        footnotes
+            .iter()
+            .filter(|footnote| footnote.contains(&citation.span.start))
+            .min_by_key(|footnote| footnote.len())
+            .or(citation.cluster.as_ref())
+            .unwrap_or(&citation.span)
+            .clone()
+    };
+    let mut clusters: Vec<(Range<usize>, Vec<Citation>)> = Vec::new();
+    for citation in parse_citations(document) {
+        let unit = unit(&citation);
+        match clusters.last_mut() {
+            Some((last, citations)) if *last == unit => citations.push(citation),
+            _ => clusters.push((unit, vec![citation])),
+        }
+    }
+    clusters.into_iter().map(|(_, citations)| citations).collect()
+}
+
+// The cited keys in document order, including repetitions
+pub fn cited_keys(document: &str) -> Vec<String> {
+    parse_citations(document).into_iter().map(|c| c.key).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CitationMode, citation_clusters, cited_keys, parse_citations, repeated_citations};
+
+    #[test]
+    fn test_repeated_citations() {
+        let document = "See [@BGH.2024; @BGH.2024 Rn. 12]. Then @BGH.2024 again, and @Smith.2009 [p. 3] and \
+                        @Smith.2009 [p. 4]!\n\nNew paragraph @Smith.2009^[Vgl. @Smith.2009; @Jones.2010, p. 3; \
+                        @Jones.2010, p. 5.] and [vgl. @Doe.2011, S. 3; auch @Doe.2011 S. 5]\n\n\
+                        [^1]: @Jones.2010. Again @Jones.2010.\n";
+        let repeated: Vec<(String, usize)> = repeated_citations(document)
+            .into_iter()
+            .map(|citation| (citation.key, citation.span.start))
+            .collect();
+        let at = |needle: &str| document.match_indices(needle).map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(
+            repeated,
+            vec![
+                ("BGH.2024".to_string(), at("@BGH.2024")[1]),
+                ("Smith.2009".to_string(), at("@Smith.2009")[1]),
+                ("Jones.2010".to_string(), at("@Jones.2010")[1]),
+                ("Doe.2011".to_string(), at("@Doe.2011")[1]),
+                ("Jones.2010".to_string(), at("@Jones.2010")[3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_citations() {
+        let document = "See [vgl. @BGH.2024 Rn. 45--47; -@Smith:2009a, p. 3] and @Doe_2011 [chap. 2] says.\n\
+                        Mail me@example.org, `@Code.2021`, \\@Escaped, <!-- @Comment.2020 --> @{Weird key}.\n\
+                        ```\n@Fenced.2020\n```\n\
+                        @VGHBaden-Württemberg.2024: [link @Link.2020](https://example.org)^[@Footnote.2022, S. 3.]";
+        let citations = parse_citations(document);
+        let keys: Vec<&str> = citations.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec!["BGH.2024", "Smith:2009a", "Doe_2011", "Weird key", "VGHBaden-Württemberg.2024", "Link.2020", "Footnote.2022"]
+        );
+        assert_eq!(citations[0].mode, CitationMode::Normal);
+        assert_eq!((citations[0].prefix.as_str(), citations[0].suffix.as_str()), ("vgl.", "Rn. 45--47"));
+        assert_eq!((citations[1].mode, citations[1].suffix.as_str()), (CitationMode::SuppressAuthor, ", p. 3"));
+        assert_eq!((citations[2].mode, citations[2].suffix.as_str()), (CitationMode::AuthorInText, "chap. 2"));
+        assert_eq!(&document[citations[3].span.clone()], "@{Weird key}");
+
+        // A bracket with a part without key is no cluster, its keys are cited in the text
+        assert_eq!(cited_keys("[see the appendix; @A.2020] [@B.2021; [@C.2022]]"), vec!["A.2020", "B.2021", "C.2022"]);
+        assert_eq!(parse_citations("[see the appendix; @A.2020]")[0].mode, CitationMode::AuthorInText);
+    }
+
+    #[test]
+    fn test_wrapped_clusters() {
+        let document = "As argued before [vgl. @BGH.2024 Rn. 45--47; -@Smith:2009a,\n    p. 3; siehe auch\n@Jones.2010, S.\n12] and\n\n[@A.2020;\n\n@B.2021]\n";
+        let citations = parse_citations(document);
+        let keys: Vec<(&str, CitationMode)> = citations.iter().map(|c| (c.key.as_str(), c.mode)).collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("BGH.2024", CitationMode::Normal),
+                ("Smith:2009a", CitationMode::SuppressAuthor),
+                ("Jones.2010", CitationMode::Normal),
+                ("A.2020", CitationMode::AuthorInText),
+                ("B.2021", CitationMode::AuthorInText),
+            ]
+        );
+        assert_eq!(citations[1].suffix, ", p. 3");
+        assert_eq!((citations[2].prefix.as_str(), citations[2].suffix.as_str()), ("siehe auch", ", S. 12"));
+    }
+
+    #[test]
+    fn test_footnotes() {
+        let document = "Text @A.2020.^[vgl. @BGH.2010c Rn. 36; see [@C.2022, p. 3].] More @D.2023.[^1]\n\n\
+                        [^1]: Also @E.2024\n    and @F.2025.\n\n    Still @G.2026.\n\n\
+                        Body @H.2027 and ^[`@Code.2020`]\n";
+        let citations = parse_citations(document);
+        let keys: Vec<(&str, bool)> = citations.iter().map(|c| (c.key.as_str(), c.footnote)).collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("A.2020", false),
+                ("BGH.2010c", true),
+                ("C.2022", true),
+                ("D.2023", false),
+                ("E.2024", true),
+                ("F.2025", true),
+                ("G.2026", true),
+                ("H.2027", false),
+            ]
+        );
+        assert_eq!((citations[1].mode, citations[2].mode), (CitationMode::AuthorInText, CitationMode::Normal));
+        assert_eq!(citations[2].suffix, ", p. 3");
+
+        let clusters: Vec<Vec<String>> = citation_clusters(document)
+            .into_iter()
+            .map(|cluster| cluster.into_iter().map(|citation| citation.key).collect())
+            .collect();
+        assert_eq!(
+            clusters,
+            vec![vec!["A.2020"], vec!["BGH.2010c", "C.2022"], vec!["D.2023"], vec!["E.2024", "F.2025", "G.2026"], vec!["H.2027"]]
+        );
+        let clusters = citation_clusters("[@A.2020; @B.2021] [@C.2022] @D.2023 @E.2024");
+        let sizes: Vec<usize> = clusters.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_large_document() {
+        // About 1.5 MB, which took seconds while every bracket rescanned the citations found so far
+        let document: String = (0..20_000)
+            .map(|i| format!("Text [vgl. @A.{i}; @B.{i} Rn. 3] and @C.{i} [p. 4].^[See @D.{i}.]\n\n"))
+            .collect();
+        let citations = parse_citations(&document);
+        assert_eq!(citations.len(), 80_000);
+        assert_eq!(citations.iter().filter(|c| c.footnote).count(), 20_000);
+        let last: Vec<&str> = citations[79_996..].iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(last, vec!["A.19999", "B.19999", "C.19999", "D.19999"]);
+        assert_eq!(citations[79_998].suffix, "p. 4");
+    }
+}
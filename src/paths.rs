@@ -0,0 +1,51 @@
+// Paths written on another platform, e.g. a bibliography field or project glob set by a co-author
+// on Windows (chapters\*.md) and read on Linux, or //server/share/refs.json read on Windows.
+// Every separator becomes the native one; drive letters and UNC prefixes are left to std::path.
+pub fn native(path: &str) -> String {
+    if cfg!(windows) {
+        path.replace('/', "\\")
+    } else {
+        path.replace('\\', "/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::native;
+    use crate::source::BibliographySource;
+    use std::path::PathBuf;
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_native_unix() {
+        assert_eq!(native(r"refs\main.json"), "refs/main.json");
+        assert_eq!(native(r"chapters\0[2-5]-*.md"), "chapters/0[2-5]-*.md");
+        assert_eq!(native("/home/me/refs.json"), "/home/me/refs.json");
+        assert_eq!(
+            r"..\refs\main.json".parse::<BibliographySource>().unwrap(),
+            BibliographySource::File(PathBuf::from("../refs/main.json"))
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_native_windows() {
+        use std::path::{Component, Path, Prefix};
+
+        assert_eq!(native("C:/Users/me/refs.json"), r"C:\Users\me\refs.json");
+        assert_eq!(native(r"chapters/0[2-5]\*.md"), r"chapters\0[2-5]\*.md");
+        let unc = native("//server/share/refs.json");
+        assert!(matches!(
+            Path::new(&unc).components().next(),
+            Some(Component::Prefix(prefix)) if matches!(prefix.kind(), Prefix::UNC(..))
+        ));
+        let BibliographySource::File(path) = r"C:\Users\me\refs.json".parse::<BibliographySource>().unwrap() else {
+            panic!("not a file");
+        };
+        assert!(path.is_absolute());
+        assert_eq!(
+            "D:/refs.json".parse::<BibliographySource>().unwrap(),
+            BibliographySource::File(PathBuf::from(r"D:\refs.json"))
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use crate::CoverageReport;
+use crate::search::{citation_spans, location};
+use crate::suppress::Suppression;
+
+// Output for scripts (--porcelain), one tab-separated line per key:
+//   status  key  file  line
+// status is cited, uncited, unknown or suppressed. cited and unknown keys come with the document
+// and line of their first citation, uncited and suppressed ones with the bibliography and no line;
+// fields that can't be determined are empty. Kept stable regardless of the human-readable report,
+// later versions may only add statuses.
+pub fn lines(
+    report: &CoverageReport,
+    suppressed: &[Suppression],
+    documents: &[(String, &str)],
+    bibliography: &str,
+) -> Vec<String> {
+    let first_citation = |key: &str| -> (String, String) {
+        for (file, document) in documents {
+            if let Some(span) = citation_spans(document, key).ok().and_then(|spans| spans.into_iter().next()) {
+                return (file.clone(), location(document, span.start).line.to_string());
+            }
+        }
+        (String::new(), String::new())
+    };
+    let line = |status: &str, key: &str, file: &str, line: &str| {
+        [status, key, file, line].map(field).join("\t")
+    };
+
+    let mut lines = Vec::new();
+    for (status, keys) in [("cited", &report.cited), ("unknown", &report.unknown)] {
+        for key in keys {
+            let (file, number) = first_citation(key);
+            lines.push(line(status, key, &file, &number));
+        }
+    }
+    for key in &report.uncited {
+        lines.push(line("uncited", key, bibliography, ""));
+    }
+    for suppression in suppressed {
+        lines.push(line("suppressed", &suppression.key, bibliography, ""));
+    }
+    lines
+}
+
+// Tabs and line breaks would shift the columns
+fn field(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lines;
+    use crate::CoverageReport;
+    use crate::suppress::Suppression;
+
+    #[test]
+    fn test_lines() {
+        let report = CoverageReport {
+            cited: vec!["A.2020".into()],
+            uncited: vec!["B.2021".into()],
+            unknown: vec!["C.2022".into()],
+        };
+        let suppressed = vec![Suppression { key: "D.2023".into(), reason: Some("appendix".into()) }];
+        let documents = vec![
+            ("intro.md".to_string(), "No citations"),
+            ("main.md".to_string(), "Text\n[@A.2020]\n@C.2022 and @A.2020"),
+        ];
+        assert_eq!(
+            lines(&report, &suppressed, &documents, "refs\tnew.json"),
+            vec![
+                "cited\tA.2020\tmain.md\t2",
+                "unknown\tC.2022\tmain.md\t3",
+                "uncited\tB.2021\trefs new.json\t",
+                "suppressed\tD.2023\trefs new.json\t",
+            ]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::{include, paths};
+use crate::source::BibliographySource;
+
+// Entry of the project manifest ([[mappings]] in the config file): documents matching
+// the globs are checked together against one library, e.g. chapters 2-5 against refs-main.json,
+// or against several merged like repeated --zotero-lib
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Mapping {
+    // Label in the report, defaults to the library
+    #[serde(default)]
+    pub name: Option<String>,
+
+    // Glob patterns, e.g. chapters/0[2-5]-*.md
+    pub documents: Vec<String>,
+
+    // Bibliography sources, same syntax as --zotero-lib: library = "refs.json" or a list
+    #[serde(rename = "library", deserialize_with = "libraries")]
+    pub libraries: Vec<BibliographySource>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMore {
+    One(String),
+    More(Vec<String>),
+}
+
+// Parsed one by one, so an invalid source is named in the error
+fn libraries<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<BibliographySource>, D::Error> {
+    let libraries = match OneOrMore::deserialize(deserializer)? {
+        OneOrMore::One(library) => vec![library],
+        OneOrMore::More(libraries) if libraries.is_empty() => return Err(serde::de::Error::custom("no library given")),
+        OneOrMore::More(libraries) => libraries,
+    };
+    libraries.iter().map(|library| library.parse().map_err(serde::de::Error::custom)).collect()
+}
+
+impl Mapping {
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.libraries.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))
+    }
+
+    // The documents matched by any of the globs, sorted and without duplicates; of paths naming the
+    // same file, e.g. through a symlink, the first is kept
+    pub fn document_paths(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let mut paths = Vec::new();
+        for pattern in &self.documents {
+            let pattern = paths::native(&shellexpand::tilde(pattern));
+            for path in glob::glob(&pattern).map_err(|e| format!("invalid glob {}: {}", pattern, e))? {
+                paths.push(path?);
+            }
+        }
+        paths.sort();
+        let mut seen = HashSet::new();
+        paths.retain(|path| seen.insert(include::identity(path)));
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mapping;
+    use crate::config::Config;
+    use std::fs;
+
+    #[test]
+    fn test_mapping_document_paths() {
+        let dir = std::env::temp_dir().join(format!("zoterocoverage-project-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for chapter in ["01-intro.md", "02-method.md", "03-results.md", "notes.txt"] {
+            fs::write(dir.join(chapter), "").unwrap();
+        }
+        // Matched by 01-*.md too, but the same document as 01-intro.md
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("01-intro.md"), dir.join("01-latest.md")).unwrap();
+
+        let config = Config::parse(&format!(
+            r#"
+[[mappings]]
+documents = ["{0}/01-*.md"]
+library = "refs-intro.json"
+
+[[mappings]]
+name = "main part"
+documents = ["{0}/0[2-3]-*.md", "{0}/03-*.md"]
+library = "refs-main.json"
+
+[[mappings]]
+documents = ["{0}/03-*.md"]
+library = ["refs-main.json", "refs-group.json"]
+"#,
+            dir.display()
+        ))
+        .unwrap();
+        let mappings: &[Mapping] = &config.mappings;
+
+        assert_eq!(mappings[0].label(), "refs-intro.json");
+        assert_eq!(mappings[0].document_paths().unwrap(), vec![dir.join("01-intro.md")]);
+        assert_eq!(mappings[1].label(), "main part");
+        assert_eq!(
+            mappings[1].document_paths().unwrap(),
+            vec![dir.join("02-method.md"), dir.join("03-results.md")]
+        );
+        assert_eq!(mappings[2].label(), "refs-main.json, refs-group.json");
+        assert!(Config::parse("[[mappings]]\ndocuments = []\nlibrary = []").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
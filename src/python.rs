@@ -0,0 +1,42 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::path::Path;
+
+use crate::CoverageReport;
+
+fn report_to_dict<'py>(py: Python<'py>, report: CoverageReport) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("cited", report.cited)?;
+    dict.set_item("uncited", report.uncited)?;
+    dict.set_item("unknown", report.unknown)?;
+    Ok(dict)
+}
+
+fn to_py_err(e: Box<dyn std::error::Error>) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+// Compare a document with a CSL-JSON bibliography, both given as strings;
+// returns {"cited": [...], "uncited": [...], "unknown": [...]}
+#[pyfunction]
+fn check_coverage<'py>(py: Python<'py>, document: &str, bibliography: &str) -> PyResult<Bound<'py, PyDict>> {
+    let report = crate::check_coverage(document, bibliography).map_err(to_py_err)?;
+    report_to_dict(py, report)
+}
+
+// Same as check_coverage, but reading files; without a bibliography the one named
+// in the document's yaml header is used
+#[pyfunction]
+#[pyo3(signature = (document, bibliography = None))]
+fn check_files<'py>(py: Python<'py>, document: &str, bibliography: Option<&str>) -> PyResult<Bound<'py, PyDict>> {
+    let report = crate::check_files(Path::new(document), bibliography.map(Path::new)).map_err(to_py_err)?;
+    report_to_dict(py, report)
+}
+
+#[pymodule]
+fn zoterocoverage(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(check_coverage, m)?)?;
+    m.add_function(wrap_pyfunction!(check_files, m)?)?;
+    Ok(())
+}
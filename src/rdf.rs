@@ -0,0 +1,173 @@
+use roxmltree::{Document, Node};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+
+use crate::better_bibtex::{csl_date, csl_type};
+
+// Namespaces of Zotero RDF
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const ZOTERO: &str = "http://www.zotero.org/namespaces/export#";
+const DC: &str = "http://purl.org/dc/elements/1.1/";
+const DCTERMS: &str = "http://purl.org/dc/terms/";
+const BIB: &str = "http://purl.org/net/biblio#";
+const FOAF: &str = "http://xmlns.com/foaf/0.1/";
+const LINK: &str = "http://purl.org/rss/1.0/modules/link/";
+
+// Zotero RDF, the format of Zotero's own export with files: items as RDF/XML elements with a
+// z:itemType, their attachments and notes as elements of their own, linked by rdf:about ids
+pub fn is_zotero_rdf(bibliography: &str) -> bool {
+    bibliography.trim_start_matches('\u{feff}').trim_start().starts_with('<') && bibliography.contains(ZOTERO)
+}
+
+// The items as CSL-JSON entries. Zotero RDF has no citation key field, the extra field (Citation
+// Key: ... pinned by Better BibTeX) becomes the note like in Zotero's CSL-JSON export; items without
+// keep their rdf:about id, so the usual id fallback and warnings apply.
+pub fn to_csl_json(bibliography: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let document = Document::parse(bibliography.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("invalid Zotero RDF: {}", e))?;
+    let elements: Vec<Node> = document.root_element().children().filter(Node::is_element).collect();
+    let by_id: HashMap<&str, Node> = elements.iter().filter_map(|node| Some((node.attribute((RDF, "about"))?, *node))).collect();
+
+    let mut collections: HashMap<&str, Vec<String>> = HashMap::new();
+    for collection in elements.iter().filter(|node| node.has_tag_name((ZOTERO, "Collection"))) {
+        let name = child_text(*collection, DC, "title").unwrap_or_default();
+        for part in children(*collection, DCTERMS, "hasPart").filter_map(|part| part.attribute((RDF, "resource"))) {
+            collections.entry(part).or_default().push(name.clone());
+        }
+    }
+
+    let entries: Vec<Value> = elements
+        .iter()
+        .filter_map(|item| item_to_csl(*item, &by_id, &collections))
+        .collect();
+    Ok(serde_json::to_string(&entries)?)
+}
+
+fn item_to_csl(item: Node, by_id: &HashMap<&str, Node>, collections: &HashMap<&str, Vec<String>>) -> Option<Value> {
+    let item_type = child_text(item, ZOTERO, "itemType")?;
+    if matches!(item_type.as_str(), "note" | "attachment" | "annotation") {
+        return None;
+    }
+    let id = item.attribute((RDF, "about")).unwrap_or_default();
+
+    let mut entry = Map::new();
+    entry.insert("id".into(), Value::from(id.trim_start_matches('#')));
+    entry.insert("type".into(), Value::from(csl_type(&item_type)));
+    if let Some(title) = child_text(item, DC, "title") {
+        entry.insert("title".into(), Value::from(title));
+    }
+    for (list, role) in [("authors", "author"), ("editors", "editor")] {
+        let names: Vec<Value> = children(item, BIB, list)
+            .flat_map(|list| list.descendants().filter(|node| node.has_tag_name((FOAF, "Person"))))
+            .map(|person| match child_text(person, FOAF, "givenName") {
+                Some(given) => json!({"family": child_text(person, FOAF, "surname"), "given": given}),
+                None => json!({"literal": child_text(person, FOAF, "surname")}),
+            })
+            .collect();
+        if !names.is_empty() {
+            entry.insert(role.into(), Value::from(names));
+        }
+    }
+    if let Some(issued) = child_text(item, DC, "date").as_deref().and_then(csl_date) {
+        entry.insert("issued".into(), issued);
+    }
+    if let Some(language) = child_text(item, ZOTERO, "language") {
+        entry.insert("language".into(), Value::from(language));
+    }
+    if let Some(extra) = child_text(item, DC, "description") {
+        entry.insert("note".into(), Value::from(extra));
+    }
+
+    // Not part of CSL-JSON, kept for sorting, grouping and reading lists like in Better BibTeX JSON
+    if let Some(added) = child_text(item, DCTERMS, "dateSubmitted") {
+        // Zotero writes 2024-03-04 10:00:00 in UTC
+        entry.insert("dateAdded".into(), Value::from(format!("{}Z", added.replacen(' ', "T", 1))));
+    }
+    if let Some(names) = collections.get(id) {
+        entry.insert("collections".into(), Value::from(names.clone()));
+    }
+    let attachments: Vec<Value> = children(item, LINK, "link")
+        .filter_map(|link| by_id.get(link.attribute((RDF, "resource"))?))
+        .filter(|attachment| child_text(**attachment, ZOTERO, "itemType").as_deref() == Some("attachment"))
+        .map(|attachment| {
+            let path = children(*attachment, RDF, "resource").find_map(|resource| resource.attribute((RDF, "resource")));
+            json!({"title": child_text(*attachment, DC, "title"), "path": path, "contentType": child_text(*attachment, LINK, "type")})
+        })
+        .collect();
+    if !attachments.is_empty() {
+        entry.insert("attachments".into(), Value::from(attachments));
+    }
+    Some(Value::Object(entry))
+}
+
+fn children<'a, 'input: 'a>(node: Node<'a, 'input>, namespace: &'a str, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.has_tag_name((namespace, name)))
+}
+
+fn child_text(node: Node, namespace: &str, name: &str) -> Option<String> {
+    let text = children(node, namespace, name).next()?.text()?.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_zotero_rdf, to_csl_json};
+    use crate::entry_key;
+    use crate::library::get_library_entries;
+    use serde_json::Value;
+
+    #[test]
+    fn test_to_csl_json() {
+        let rdf = r##"<rdf:RDF
+ xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+ xmlns:z="http://www.zotero.org/namespaces/export#"
+ xmlns:dc="http://purl.org/dc/elements/1.1/"
+ xmlns:dcterms="http://purl.org/dc/terms/"
+ xmlns:bib="http://purl.org/net/biblio#"
+ xmlns:foaf="http://xmlns.com/foaf/0.1/"
+ xmlns:link="http://purl.org/rss/1.0/modules/link/">
+    <bib:Book rdf:about="#item_12">
+        <z:itemType>book</z:itemType>
+        <bib:authors><rdf:Seq><rdf:li><foaf:Person>
+            <foaf:surname>Alexander</foaf:surname><foaf:givenName>Christian</foaf:givenName>
+        </foaf:Person></rdf:li></rdf:Seq></bib:authors>
+        <link:link rdf:resource="#item_14"/>
+        <dc:title>UWG</dc:title>
+        <dc:date>2024-03</dc:date>
+        <dcterms:dateSubmitted>2024-03-04 10:00:00</dcterms:dateSubmitted>
+        <dc:description>Citation Key: Alexander.2024</dc:description>
+    </bib:Book>
+    <z:Attachment rdf:about="#item_14">
+        <z:itemType>attachment</z:itemType>
+        <rdf:resource rdf:resource="files/14/uwg.pdf"/>
+        <dc:title>Full Text PDF</dc:title>
+        <link:type>application/pdf</link:type>
+    </z:Attachment>
+    <bib:Document rdf:about="#item_15">
+        <z:itemType>case</z:itemType>
+        <dc:title>Fluggastrechte</dc:title>
+    </bib:Document>
+    <z:Collection rdf:about="#collection_3">
+        <dc:title>Thesis</dc:title>
+        <dcterms:hasPart rdf:resource="#item_12"/>
+    </z:Collection>
+</rdf:RDF>"##;
+        assert!(is_zotero_rdf(rdf));
+        assert!(!is_zotero_rdf("<modsCollection/>"));
+
+        let csl = to_csl_json(rdf).unwrap();
+        let entries: Vec<Value> = serde_json::from_str(&csl).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entry_key(&entries[0]), Some("Alexander.2024"));
+        assert_eq!(entries[0]["type"], "book");
+        assert_eq!(entries[0]["author"][0]["family"], "Alexander");
+        assert_eq!(entries[0]["collections"][0], "Thesis");
+        assert_eq!(entries[1]["type"], "legal_case");
+        assert_eq!(entry_key(&entries[1]), Some("item_15"));
+
+        let library = get_library_entries(&csl).unwrap();
+        assert_eq!(library[0].year(), Some(2024));
+        assert!(library[0].has_pdf());
+        assert!(library[0].added_days().is_some());
+    }
+}
@@ -0,0 +1,106 @@
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::search::citation_spans;
+
+// Rename the citation key old to new in a document, returns the new text and the number of
+// replaced citations. Only citations (see citation_spans) are touched, not the key in plain prose.
+pub fn rename_in_document(document: &str, old: &str, new: &str) -> Result<(String, usize), Box<dyn std::error::Error>> {
+    let citations = citation_spans(document, old)?;
+    let mut renamed = String::with_capacity(document.len());
+    let mut last = 0;
+    for span in &citations {
+        renamed.push_str(&document[last..span.start]);
+        if document[span.clone()].starts_with("@{") {
+            renamed.push_str(&format!("@{{{}}}", new));
+        } else {
+            renamed.push_str(&format!("@{}", new));
+        }
+        last = span.end;
+    }
+    renamed.push_str(&document[last..]);
+    Ok((renamed, citations.len()))
+}
+
+// Rewrite every stale key of the alias map (stale key -> current key) in a document
+pub fn apply_aliases(document: &str, aliases: &BTreeMap<String, String>) -> Result<(String, usize), Box<dyn std::error::Error>> {
+    let mut fixed = document.to_string();
+    let mut count = 0;
+    for (stale, current) in aliases {
+        let (renamed, renamed_count) = rename_in_document(&fixed, stale, current)?;
+        fixed = renamed;
+        count += renamed_count;
+    }
+    Ok((fixed, count))
+}
+
+// Rename the entry with id / citation-key old in a CSL-JSON bibliography (citationKey in Better BibTeX JSON),
+// returns the new JSON and the number of replaced fields. Only those values are rewritten, the formatting is kept.
+pub fn rename_in_bibliography(bibliography: &str, old: &str, new: &str) -> Result<(String, usize), Box<dyn std::error::Error>> {
+    serde_json::from_str::<Value>(bibliography)?;
+    let re = Regex::new(&format!(
+        r#"("(?:id|citation-key|citationKey)"\s*:\s*){}"#,
+        regex::escape(&serde_json::to_string(old)?)
+    ))?;
+    let count = re.find_iter(bibliography).count();
+    let new = serde_json::to_string(new)?;
+    let renamed = re.replace_all(bibliography, |caps: &Captures| format!("{}{}", &caps[1], new));
+    Ok((renamed.into_owned(), count))
+}
+
+// Changed lines as (line number, before, after); renaming never adds or removes lines
+pub fn changed_lines<'a>(before: &'a str, after: &'a str) -> Vec<(usize, &'a str, &'a str)> {
+    before
+        .lines()
+        .zip(after.lines())
+        .enumerate()
+        .filter(|(_, (b, a))| b != a)
+        .map(|(i, (b, a))| (i + 1, b, a))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_aliases, changed_lines, rename_in_bibliography, rename_in_document};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_rename_in_document() {
+        let document = "[@BGH.2024; @BGH.2024a, Rn. 3] @BGH.2024@BGH.2024\nBGH.2024 mail@BGH.2024 -@{BGH.2024}.";
+        let (renamed, count) = rename_in_document(document, "BGH.2024", "BGH.2024x").unwrap();
+        assert_eq!(
+            renamed,
+            "[@BGH.2024x; @BGH.2024a, Rn. 3] @BGH.2024x@BGH.2024\nBGH.2024 mail@BGH.2024 -@{BGH.2024x}."
+        );
+        assert_eq!(count, 3);
+        assert_eq!(
+            changed_lines(document, &renamed),
+            vec![(1, document.lines().next().unwrap(), renamed.lines().next().unwrap()),
+                 (2, document.lines().nth(1).unwrap(), renamed.lines().nth(1).unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_rename_in_bibliography() {
+        let bibliography = r#"[{"id": "BGH.2024", "citation-key":"BGH.2024", "title": "BGH.2024"}, {"id": "BGH.2024a"}]"#;
+        let (renamed, count) = rename_in_bibliography(bibliography, "BGH.2024", "BGH.2024x").unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            renamed,
+            r#"[{"id": "BGH.2024x", "citation-key":"BGH.2024x", "title": "BGH.2024"}, {"id": "BGH.2024a"}]"#
+        );
+        assert!(rename_in_bibliography("not json", "a", "b").is_err());
+    }
+
+    #[test]
+    fn test_apply_aliases() {
+        let aliases = BTreeMap::from([
+            ("smith2009".to_string(), "Smith.2009".to_string()),
+            ("jones2010".to_string(), "Jones.2010".to_string()),
+        ]);
+        let (fixed, count) = apply_aliases("[@smith2009; @jones2010] and @smith2009a", &aliases).unwrap();
+        assert_eq!(fixed, "[@Smith.2009; @Jones.2010] and @smith2009a");
+        assert_eq!(count, 2);
+    }
+}
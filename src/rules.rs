@@ -0,0 +1,693 @@
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::cluster::Cite;
+use crate::front_matter;
+use crate::library::{LibraryEntry, get_library_entries};
+use crate::{Citations, CoverageReport};
+
+// Fields every cited entry should have, checked by missing-fields
+pub const REQUIRED_FIELDS: [&str; 3] = ["type", "title", "issued"];
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Warn,
+    Error,
+}
+
+// Named checks, configured as [rules] name = "off" | "warn" | "error"
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    // Bibliography entry never cited
+    Uncited,
+    // Cited key without bibliography entry
+    Unknown,
+    // Key of the required list (--required) the document doesn't cite
+    Required,
+    // Cited key matching a bibliography key only when ignoring case (@BGH.2024 vs bgh.2024)
+    KeyCase,
+    // Two bibliography entries with the same key
+    DuplicateKey,
+    // Key of differing entries in several libraries (repeated --zotero-lib), only the first is used
+    ConflictingEntry,
+    // Cited entry without one of REQUIRED_FIELDS
+    MissingFields,
+    // Cited key listed as stale in [aliases]
+    StaleKey,
+    // Year in the key (Alexander.2024) differs from the issued year
+    KeyYear,
+    // Bibliography key not matching key_pattern, e.g. a pinned legacy key Smith2009a in a Name.2024 library
+    KeyPattern,
+    // Citation without the locator its item type requires, see [require_locator]
+    MissingLocator,
+    // Invalid pandoc citation settings in the YAML header: csl, bibliography, link-citations, ...
+    Metadata,
+    // Document changed long after the bibliography file was exported, e.g. a lagging auto-export
+    StaleExport,
+    // Entry cited more often than --max-cites-per-entry, e.g. one commentary carrying the argument
+    OverCited,
+    // Key cited twice in one footnote or sentence, e.g. [@BGH.2024; @BGH.2024 Rn. 12]
+    RepeatedCitation,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 15] = [
+        Rule::Uncited,
+        Rule::Unknown,
+        Rule::Required,
+        Rule::KeyCase,
+        Rule::DuplicateKey,
+        Rule::ConflictingEntry,
+        Rule::MissingFields,
+        Rule::StaleKey,
+        Rule::KeyYear,
+        Rule::KeyPattern,
+        Rule::MissingLocator,
+        Rule::Metadata,
+        Rule::StaleExport,
+        Rule::OverCited,
+        Rule::RepeatedCitation,
+    ];
+
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::Uncited
+            | Rule::Unknown
+            | Rule::KeyCase
+            | Rule::DuplicateKey
+            | Rule::ConflictingEntry
+            | Rule::StaleKey
+            | Rule::KeyYear
+            | Rule::KeyPattern
+            | Rule::MissingLocator
+            | Rule::Metadata
+            | Rule::StaleExport
+            | Rule::OverCited
+            | Rule::RepeatedCitation => Severity::Warn,
+            Rule::Required => Severity::Error,
+            Rule::MissingFields => Severity::Off,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Rule::Uncited => "uncited",
+            Rule::Unknown => "unknown",
+            Rule::Required => "required",
+            Rule::KeyCase => "key-case",
+            Rule::DuplicateKey => "duplicate-key",
+            Rule::ConflictingEntry => "conflicting-entry",
+            Rule::MissingFields => "missing-fields",
+            Rule::StaleKey => "stale-key",
+            Rule::KeyYear => "key-year",
+            Rule::KeyPattern => "key-pattern",
+            Rule::MissingLocator => "missing-locator",
+            Rule::Metadata => "metadata",
+            Rule::StaleExport => "stale-export",
+            Rule::OverCited => "over-cited",
+            Rule::RepeatedCitation => "repeated-citation",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Severities from the config, rules not mentioned keep their default
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Rules(BTreeMap<Rule, Severity>);
+
+impl Rules {
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.0.get(&rule).copied().unwrap_or_else(|| rule.default_severity())
+    }
+
+    // Every warn-level rule (configured or by default) at error level; rules that are off stay off
+    pub fn strict(&self) -> Rules {
+        Rules(Rule::ALL.iter().map(|&rule| match self.severity(rule) {
+            Severity::Warn => (rule, Severity::Error),
+            severity => (rule, severity),
+        }).collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub key: String,
+    // Id of the message in locales/*.ftl, e.g. finding-uncited, and its arguments by name
+    pub message: &'static str,
+    pub args: Vec<(&'static str, String)>,
+    // The document of findings about one of several documents (projects, included files)
+    pub document: Option<String>,
+}
+
+// Put findings in report order: grouped by rule in the order of the Rule enum (the order of
+// the [rules] list in the README), within a rule in the order they were found, which follows
+// the document and the bibliography. The report is the same however loading was scheduled.
+pub fn sort(findings: &mut [Finding]) {
+    findings.sort_by_key(|finding| finding.rule);
+}
+
+impl Finding {
+    fn new(rule: Rule, key: &str, message: &'static str, args: Vec<(&'static str, String)>) -> Self {
+        Finding {
+            rule,
+            key: key.to_string(),
+            message,
+            args,
+            document: None,
+        }
+    }
+}
+
+// The uncited and unknown findings, all a report without bibliography and documents gives (merge)
+pub fn check_report(report: &CoverageReport) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = Vec::new();
+    for key in &report.uncited {
+        findings.push(Finding::new(Rule::Uncited, key, "finding-uncited", vec![("key", key.clone())]));
+    }
+    for key in &report.unknown {
+        findings.push(Finding::new(Rule::Unknown, key, "finding-unknown", vec![("key", key.clone())]));
+    }
+    findings
+}
+
+// All findings of all rules, whatever their severity
+pub fn check(
+    report: &CoverageReport,
+    citations_document: &[String],
+    citations_bibliography: &[Citations],
+    bibliography_json: &str,
+    aliases: &BTreeMap<String, String>,
+) -> Result<Vec<Finding>, Box<dyn std::error::Error>> {
+    let mut findings = check_report(report);
+    // Almost always the same source under two key formats, not two sources
+    for key in &report.unknown {
+        let lowercase = key.to_lowercase();
+        let mut matches: Vec<&str> = citations_bibliography
+            .iter()
+            .map(|c| c.citation_key.as_str())
+            .filter(|bibliography_key| bibliography_key.to_lowercase() == lowercase)
+            .collect();
+        matches.sort_unstable();
+        matches.dedup();
+        if !matches.is_empty() {
+            findings.push(Finding::new(Rule::KeyCase, key, "finding-key-case",
+                vec![("key", key.clone()), ("matches", matches.join(", "))]));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut duplicates = HashSet::new();
+    for citation in citations_bibliography {
+        let key = citation.citation_key.as_str();
+        if !seen.insert(key) && duplicates.insert(key) {
+            findings.push(Finding::new(Rule::DuplicateKey, key, "finding-duplicate-key", vec![("key", key.to_string())]));
+        }
+    }
+
+    let entries: Vec<Value> = serde_json::from_str(bibliography_json)?;
+    for entry in &entries {
+        let Some(key) = crate::entry_key(entry) else {
+            continue;
+        };
+        if !report.cited.iter().any(|k| k == key) {
+            continue;
+        }
+        let missing: Vec<&str> = REQUIRED_FIELDS.into_iter().filter(|f| entry.get(f).is_none()).collect();
+        if !missing.is_empty() {
+            findings.push(Finding::new(Rule::MissingFields, key, "finding-missing-fields",
+                vec![("key", key.to_string()), ("fields", missing.join(", "))]));
+        }
+    }
+
+    // Keys following the Name.2024 convention, optionally with a letter suffix
+    let key_year = Regex::new(r"\.(\d{4})[a-z]?$").unwrap();
+    for entry in get_library_entries(bibliography_json)? {
+        let key = entry.citation_key.as_str();
+        if let Some(caps) = key_year.captures(key)
+            && let Some(issued) = entry.year()
+            && caps[1].parse::<i32>() != Ok(issued)
+        {
+            findings.push(Finding::new(Rule::KeyYear, key, "finding-key-year", vec![("key", key.to_string()), ("year", issued.to_string())]));
+        }
+    }
+
+    let mut stale_seen = HashSet::new();
+    for key in citations_document {
+        if let Some(current) = aliases.get(key)
+            && stale_seen.insert(key)
+        {
+            findings.push(Finding::new(Rule::StaleKey, key, "finding-stale-key", vec![("key", key.clone()), ("current", current.clone())]));
+        }
+    }
+    Ok(findings)
+}
+
+// Bibliography keys not matching the configured key_pattern. Document-side patterns like the regex
+// extractor's miss them silently, so these entries look uncited however often they are cited.
+pub fn check_key_pattern(citations_bibliography: &[Citations], pattern: &Regex) -> Vec<Finding> {
+    let mut seen = HashSet::new();
+    citations_bibliography
+        .iter()
+        .map(|citation| citation.citation_key.as_str())
+        .filter(|key| !pattern.is_match(key) && seen.insert(*key))
+        .map(|key| Finding::new(Rule::KeyPattern, key, "finding-key-pattern", vec![("key", key.to_string())]))
+        .collect()
+}
+
+// The keys of a required list, e.g. a seminar's mandatory reading: one key per line, optionally with
+// @, blank lines and lines starting with # are skipped, text after " #" is a comment
+pub fn parse_required(list: &str) -> Vec<String> {
+    list.lines()
+        .map(|line| line.split(" #").next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|key| key.trim_start_matches('@').to_string())
+        .collect()
+}
+
+// Required keys the document doesn't cite, each once in the order of the list
+pub fn check_required(required: &[String], citations_document: &[String]) -> Vec<Finding> {
+    let cited: HashSet<&str> = citations_document.iter().map(String::as_str).collect();
+    let mut seen = HashSet::new();
+    required
+        .iter()
+        .filter(|key| !cited.contains(key.as_str()) && seen.insert(key.as_str()))
+        .map(|key| Finding::new(Rule::Required, key, "finding-required", vec![("key", key.clone())]))
+        .collect()
+}
+
+// Fields that differ between exports of the same item without changing the reference
+const EXPORT_FIELDS: [&str; 5] = ["id", "dateAdded", "dateModified", "attachments", "collections"];
+
+// Keys of entries in several of the named bibliographies whose metadata differs. Merged, the entry
+// of the first bibliography is used, so a citation may refer to another item than intended.
+pub fn check_conflicting_entries(bibliographies: &[(String, &str)]) -> Result<Vec<Finding>, Box<dyn std::error::Error>> {
+    let mut findings = Vec::new();
+    let mut first: BTreeMap<String, (&str, Value)> = BTreeMap::new();
+    let mut conflicting = HashSet::new();
+    for (name, bibliography) in bibliographies {
+        let entries: Vec<Value> = serde_json::from_str(bibliography)?;
+        for mut entry in entries {
+            let Some(key) = crate::entry_key(&entry).map(str::to_string) else {
+                continue;
+            };
+            if let Some(fields) = entry.as_object_mut() {
+                fields.retain(|field, _| !EXPORT_FIELDS.contains(&field.as_str()));
+            }
+            match first.get(&key) {
+                None => {
+                    first.insert(key, (name, entry));
+                }
+                Some((first_name, first_entry)) => {
+                    if first_name != name && *first_entry != entry && conflicting.insert(key.clone()) {
+                        findings.push(Finding::new(Rule::ConflictingEntry, &key, "finding-conflicting-entry",
+                            vec![("key", key.clone()), ("first", first_name.to_string()), ("other", name.clone())]));
+                    }
+                }
+            }
+        }
+    }
+    Ok(findings)
+}
+
+// Citations of item types that require a locator but have none, or none with an accepted label.
+// required maps a CSL type to the accepted labels, an empty list accepts any locator.
+pub fn check_locators(cites: &[Cite], entries: &[LibraryEntry], required: &BTreeMap<String, Vec<String>>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for cite in cites {
+        let Some(item_type) = entries.iter().find(|e| e.citation_key == cite.key).and_then(|e| e.item_type.as_deref()) else {
+            continue;
+        };
+        let Some(labels) = required.get(item_type) else {
+            continue;
+        };
+        let accepted = cite
+            .locator
+            .as_ref()
+            .is_some_and(|locator| labels.is_empty() || labels.contains(&locator.label));
+        if !accepted {
+            let mut args = vec![
+                ("line", cite.location.line.to_string()),
+                ("column", cite.location.column.to_string()),
+                ("key", cite.key.clone()),
+                ("type", item_type.to_string()),
+            ];
+            let message = if labels.is_empty() {
+                "finding-missing-locator"
+            } else {
+                args.push(("labels", labels.join(" / ")));
+                "finding-missing-locator-label"
+            };
+            findings.push(Finding::new(Rule::MissingLocator, &cite.key, message, args));
+        }
+    }
+    findings
+}
+
+// A finding per citation repeating a key in the same footnote or sentence (cluster::repeated_cites)
+pub fn check_repeated_citations(repeated: &[Cite]) -> Vec<Finding> {
+    repeated
+        .iter()
+        .map(|cite| {
+            let citation = match cite.locator {
+                Some(ref locator) => format!("{} {}", cite.key, locator),
+                None => cite.key.clone(),
+            };
+            Finding::new(
+                Rule::RepeatedCitation,
+                &cite.key,
+                "finding-repeated-citation",
+                vec![("line", cite.location.line.to_string()), ("column", cite.location.column.to_string()), ("citation", citation)],
+            )
+        })
+        .collect()
+}
+
+// Keys cited more than max times, in the order of their first citation
+pub fn check_citation_counts(citations_document: &[String], max: usize) -> Vec<Finding> {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for key in citations_document {
+        match counts.iter_mut().find(|(k, _)| k == key) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((key, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > max)
+        .map(|(key, count)| {
+            Finding::new(Rule::OverCited, key, "finding-over-cited",
+                         vec![("key", key.to_string()), ("count", count.to_string()), ("max", max.to_string())])
+        })
+        .collect()
+}
+
+// The bibliography file if the document was modified more than window after it, going by mtimes.
+// Missing files and filesystems without mtimes give no finding.
+pub fn check_export_age(document: &Path, bibliography: &Path, window: Duration) -> Option<Finding> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let lag = modified(document)?.duration_since(modified(bibliography)?).ok()?;
+    (lag > window).then(|| {
+        let key = bibliography.display().to_string();
+        let (amount, unit) = lag_in_units(lag);
+        let args = vec![("bibliography", key.clone()), ("amount", amount.to_string()), ("unit", unit.to_string()),
+                        ("document", document.display().to_string())];
+        Finding::new(Rule::StaleExport, &key, "finding-stale-export", args)
+    })
+}
+
+// The lag in minutes, hours or days, whichever reads best
+fn lag_in_units(lag: Duration) -> (u64, &'static str) {
+    let minutes = lag.as_secs() / 60;
+    match minutes {
+        0..120 => (minutes, "minutes"),
+        120..2880 => (minutes / 60, "hours"),
+        _ => (minutes / 1440, "days"),
+    }
+}
+
+// Pandoc settings that only take true or false
+const BOOLEAN_SETTINGS: [&str; 3] = ["link-citations", "link-bibliography", "suppress-bibliography"];
+
+// All problems with the pandoc citation settings of the document's YAML header. Like pandoc, files
+// are relative to the directory of the document at path, to the current directory for stdin (None).
+// Documents without header have none.
+pub fn check_metadata(document: &str, path: Option<&Path>) -> Vec<Finding> {
+    let base = path.and_then(Path::parent).unwrap_or(Path::new(""));
+    let mut findings = Vec::new();
+    if front_matter::blocks(document).is_empty() {
+        return findings;
+    }
+    let metadata: serde_yaml::Mapping = match front_matter::parse(document) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            findings.push(Finding::new(Rule::Metadata, "", "finding-metadata-invalid", vec![("error", e.to_string())]));
+            return findings;
+        }
+    };
+    let mut push = |field: &str, message: &'static str, value: String| {
+        findings.push(Finding::new(Rule::Metadata, field, message, vec![("field", field.to_string()), ("value", value)]))
+    };
+
+    // One path or a list of them
+    match metadata.get("bibliography") {
+        None => {}
+        Some(serde_yaml::Value::String(path)) => check_file("bibliography", path, base, &mut push),
+        Some(serde_yaml::Value::Sequence(paths)) => {
+            for path in paths {
+                match path.as_str() {
+                    Some(path) => check_file("bibliography", path, base, &mut push),
+                    None => push("bibliography", "finding-metadata-not-a-path", show(path)),
+                }
+            }
+        }
+        Some(other) => push("bibliography", "finding-metadata-paths", show(other)),
+    }
+
+    match metadata.get("csl") {
+        None => {}
+        // Pandoc downloads styles given as URL
+        Some(serde_yaml::Value::String(url)) if url.starts_with("http://") || url.starts_with("https://") => {}
+        Some(serde_yaml::Value::String(style)) => {
+            // Pandoc adds the .csl extension if it is missing
+            let style = if Path::new(style).extension().is_none() { format!("{}.csl", style) } else { style.clone() };
+            check_file("csl", &style, base, &mut push);
+        }
+        Some(other) => push("csl", "finding-metadata-csl", show(other)),
+    }
+
+    for setting in BOOLEAN_SETTINGS {
+        if let Some(value) = metadata.get(setting)
+            && !value.is_bool()
+        {
+            push(setting, "finding-metadata-boolean", show(value));
+        }
+    }
+    findings
+}
+
+// A YAML value as written in the header
+fn show(value: &serde_yaml::Value) -> String {
+    serde_yaml::to_string(value).map_or_else(|_| format!("{:?}", value), |yaml| yaml.trim().to_string())
+}
+
+fn check_file(field: &str, path: &str, base: &Path, push: &mut impl FnMut(&str, &'static str, String)) {
+    if !base.join(path).exists() {
+        push(field, "finding-metadata-missing-file", path.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Finding, Rule, Rules, Severity, check, check_citation_counts, check_conflicting_entries, check_export_age, check_key_pattern, check_locators,
+        check_metadata, check_required, parse_required, sort,
+    };
+    use crate::cluster::parse_cites;
+    use crate::library::get_library_entries;
+    use crate::{Citations, CoverageReport, get_citations_bibliography};
+    use regex::Regex;
+    use std::collections::BTreeMap;
+    use std::time::{Duration, SystemTime};
+
+    fn arg<'a>(finding: &'a Finding, name: &str) -> &'a str {
+        finding.args.iter().find(|(n, _)| *n == name).map_or("", |(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn test_rules_severity() {
+        let rules: Rules = serde_json::from_str(r#"{"uncited": "error", "stale-key": "off"}"#).unwrap();
+        assert_eq!(rules.severity(Rule::Uncited), Severity::Error);
+        assert_eq!(rules.severity(Rule::StaleKey), Severity::Off);
+        assert_eq!(rules.severity(Rule::Unknown), Severity::Warn);
+        let strict = rules.strict();
+        assert_eq!(strict.severity(Rule::Uncited), Severity::Error);
+        assert_eq!(strict.severity(Rule::StaleKey), Severity::Off);
+        assert_eq!(strict.severity(Rule::DuplicateKey), Severity::Error);
+        assert_eq!(strict.severity(Rule::MissingFields), Severity::Off);
+        assert!(serde_json::from_str::<Rules>(r#"{"uncited": "fatal"}"#).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let bibliography = r#"[
+            {"id": "a", "citation-key": "A.2020", "type": "book", "title": "A", "issued": {"date-parts": [[2021]]}},
+            {"id": "b", "citation-key": "B.2021", "type": "book"},
+            {"id": "b2", "citation-key": "B.2021", "type": "book"}
+        ]"#;
+        let citations = |keys: &[&str]| -> Vec<Citations> {
+            keys.iter().map(|k| Citations { citation_key: k.to_string() }).collect()
+        };
+        let report = CoverageReport {
+            cited: vec!["B.2021".to_string()],
+            uncited: vec!["A.2020".to_string()],
+            unknown: vec!["old.2019".to_string()],
+        };
+        let aliases = BTreeMap::from([("old.2019".to_string(), "A.2020".to_string())]);
+        let document = vec!["B.2021".to_string(), "old.2019".to_string(), "old.2019".to_string()];
+
+        let findings = check(&report, &document, &citations(&["A.2020", "B.2021", "B.2021"]), bibliography, &aliases).unwrap();
+        let rules: Vec<(Rule, &str)> = findings.iter().map(|f| (f.rule, f.key.as_str())).collect();
+        assert_eq!(
+            rules,
+            vec![
+                (Rule::Uncited, "A.2020"),
+                (Rule::Unknown, "old.2019"),
+                (Rule::DuplicateKey, "B.2021"),
+                (Rule::MissingFields, "B.2021"),
+                (Rule::MissingFields, "B.2021"),
+                (Rule::KeyYear, "A.2020"),
+                (Rule::StaleKey, "old.2019"),
+            ]
+        );
+        assert_eq!((findings[3].message, arg(&findings[3], "fields")), ("finding-missing-fields", "title, issued"));
+        assert_eq!((findings[5].message, arg(&findings[5], "year")), ("finding-key-year", "2021"));
+
+        let mut findings = findings;
+        sort(&mut findings);
+        assert_eq!((findings[5].rule, findings[6].rule), (Rule::StaleKey, Rule::KeyYear));
+        assert_eq!((arg(&findings[3], "fields"), findings[4].rule), ("title, issued", Rule::MissingFields));
+    }
+
+    #[test]
+    fn test_key_case() {
+        let bibliography = r#"[{"citation-key": "bgh.2024"}, {"citation-key": "Müller.2020"}]"#;
+        let citations: Vec<Citations> = ["bgh.2024", "Müller.2020"]
+            .iter()
+            .map(|k| Citations { citation_key: k.to_string() })
+            .collect();
+        let report = CoverageReport {
+            cited: vec![],
+            uncited: vec![],
+            unknown: vec!["BGH.2024".to_string(), "MÜLLER.2020".to_string(), "Smith.2009".to_string()],
+        };
+        let findings = check(&report, &[], &citations, bibliography, &BTreeMap::new()).unwrap();
+        let key_case: Vec<(&str, &str)> = findings.iter().filter(|f| f.rule == Rule::KeyCase).map(|f| (arg(f, "key"), arg(f, "matches"))).collect();
+        assert_eq!(key_case, vec![("BGH.2024", "bgh.2024"), ("MÜLLER.2020", "Müller.2020")]);
+    }
+
+    #[test]
+    fn test_check_metadata() {
+        let base = std::env::temp_dir().join("zoterocoverage-metadata-test");
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("refs.json"), "[]").unwrap();
+        std::fs::write(base.join("apa.csl"), "").unwrap();
+
+        let document = "---\nbibliography: [refs.json, missing.json]\ncsl: apa\nlink-citations: yes\nsuppress-bibliography: false\n---\n";
+        let path = base.join("thesis.md");
+        let findings = check_metadata(document, Some(&path));
+        let messages: Vec<(&str, &str, &str)> = findings.iter().map(|f| (f.message, arg(f, "field"), arg(f, "value"))).collect();
+        assert_eq!(
+            messages,
+            vec![("finding-metadata-missing-file", "bibliography", "missing.json"), ("finding-metadata-boolean", "link-citations", "yes")]
+        );
+        assert!(check_metadata("No header", Some(&path)).is_empty());
+        assert_eq!(check_metadata("---\ncsl: [\n---\n", Some(&path)).len(), 1);
+
+        // Tests run in the crate directory, which has a Cargo.toml, the document's directory hasn't
+        let document = "---\nbibliography: Cargo.toml\n---\n";
+        assert_eq!(check_metadata(document, Some(&path)).len(), 1);
+        assert!(check_metadata(document, None).is_empty());
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_check_citation_counts() {
+        let keys: Vec<String> = ["Köhler.2024", "A.2020", "Köhler.2024", "A.2020", "Köhler.2024"].map(String::from).to_vec();
+        let findings = check_citation_counts(&keys, 2);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, Rule::OverCited);
+        assert_eq!((arg(&findings[0], "key"), arg(&findings[0], "count"), arg(&findings[0], "max")), ("Köhler.2024", "3", "2"));
+        assert!(check_citation_counts(&keys, 3).is_empty());
+    }
+
+    #[test]
+    fn test_check_key_pattern() {
+        let bibliography = get_citations_bibliography(
+            r#"[{"citation-key": "Alexander.2024"}, {"citation-key": "Smith2009a"}, {"citation-key": "BGH.2010c"},
+                {"citation-key": "Smith2009a"}, {"citation-key": "Alexander.2024x"}]"#,
+        )
+        .unwrap();
+        // As Config::key_pattern anchors key_pattern = '[A-Z][\w-]*\.\d{4}[a-z]?'
+        let pattern = Regex::new(r"^(?:[A-Z][\w-]*\.\d{4}[a-z]?)$").unwrap();
+        let findings = check_key_pattern(&bibliography, &pattern);
+        let keys: Vec<&str> = findings.iter().map(|finding| finding.key.as_str()).collect();
+        assert_eq!(keys, vec!["Smith2009a"]);
+        assert_eq!(findings[0].rule, Rule::KeyPattern);
+    }
+
+    #[test]
+    fn test_check_required() {
+        let list = "# Pflichtlektüre\n@Alexander.2024\nBGH.2024  # Leitentscheidung\n\nSmith.2009\nAlexander.2024\n";
+        let required = parse_required(list);
+        assert_eq!(required, vec!["Alexander.2024", "BGH.2024", "Smith.2009", "Alexander.2024"]);
+        let findings = check_required(&required, &["BGH.2024".to_string()]);
+        let keys: Vec<&str> = findings.iter().map(|finding| finding.key.as_str()).collect();
+        assert_eq!(keys, vec!["Alexander.2024", "Smith.2009"]);
+        assert_eq!(Rules::default().severity(findings[0].rule), Severity::Error);
+    }
+
+    #[test]
+    fn test_check_conflicting_entries() {
+        let bibliographies = [
+            ("refs.json".to_string(), r#"[{"id": "1", "citation-key": "A.2020", "title": "Kommentar"},
+                                          {"citation-key": "B.2021", "title": "Aufsatz"}]"#),
+            ("group.json".to_string(), r#"[{"id": "9", "citation-key": "A.2020", "title": "Kommentar", "dateAdded": "2024-01-01"},
+                                           {"citation-key": "B.2021", "title": "Anderer Aufsatz"}]"#),
+            ("old.json".to_string(), r#"[{"citation-key": "B.2021", "title": "Alter Aufsatz"}]"#),
+        ];
+        let findings = check_conflicting_entries(&bibliographies).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, Rule::ConflictingEntry);
+        assert_eq!((arg(&findings[0], "first"), arg(&findings[0], "other")), ("refs.json", "group.json"));
+    }
+
+    #[test]
+    fn test_check_export_age() {
+        let base = std::env::temp_dir().join("zoterocoverage-export-age-test");
+        std::fs::create_dir_all(&base).unwrap();
+        let (document, bibliography) = (base.join("thesis.md"), base.join("refs.json"));
+        let now = SystemTime::now();
+        for (path, modified) in [(&document, now), (&bibliography, now - Duration::from_secs(3 * 3600))] {
+            std::fs::File::create(path).unwrap().set_modified(modified).unwrap();
+        }
+
+        let finding = check_export_age(&document, &bibliography, Duration::from_secs(3600)).unwrap();
+        assert_eq!((arg(&finding, "amount"), arg(&finding, "unit")), ("3", "hours"));
+        assert!(arg(&finding, "bibliography").ends_with("refs.json"));
+        assert_eq!(check_export_age(&document, &bibliography, Duration::from_secs(4 * 3600)), None);
+        assert_eq!(check_export_age(&bibliography, &document, Duration::ZERO), None);
+        assert_eq!(check_export_age(&document, &base.join("missing.json"), Duration::ZERO), None);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_check_locators() {
+        let entries = get_library_entries(
+            r#"[{"citation-key": "BGH.2024", "type": "legal_case"}, {"citation-key": "Alexander.2024", "type": "entry-encyclopedia"},
+                {"citation-key": "Smith.2009", "type": "book"}]"#,
+        )
+        .unwrap();
+        let required = BTreeMap::from([
+            ("legal_case".to_string(), vec!["Rn.".to_string()]),
+            ("entry-encyclopedia".to_string(), vec![]),
+        ]);
+        let cites = parse_cites("[@BGH.2024 Rn. 3; @BGH.2024, S. 5]\n[@Alexander.2024; @Smith.2009]", true);
+        let findings = check_locators(&cites, &entries, &required);
+        let keys: Vec<&str> = findings.iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(keys, vec!["BGH.2024", "Alexander.2024"]);
+        assert_eq!((findings[0].message, arg(&findings[0], "column"), arg(&findings[0], "labels")), ("finding-missing-locator-label", "19", "Rn."));
+        assert_eq!(findings[1].message, "finding-missing-locator");
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Replace a file so that a crash or full disk mid-write leaves either the old or the new content,
+// never a truncated manuscript: the content goes to path.tmp, is flushed to disk and then renamed
+// over path. With backup the previous content is kept as path.bak. A new file is created as well.
+pub fn write(path: &Path, content: &[u8], backup: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let temporary = with_suffix(path, ".tmp");
+    let written = write_synced(&temporary, content, path).and_then(|()| {
+        if backup && path.exists() {
+            fs::copy(path, with_suffix(path, ".bak"))?;
+        }
+        fs::rename(&temporary, path)
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temporary);
+        return Err(format!("could not write {}: {}", path.display(), e).into());
+    }
+    sync_directory(path);
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// The temporary file gets the permissions of the file it replaces, e.g. a read-only chapter stays read-only
+fn write_synced(temporary: &Path, content: &[u8], path: &Path) -> std::io::Result<()> {
+    let mut file = File::create(temporary)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(temporary, metadata.permissions())?;
+    }
+    Ok(())
+}
+
+// The rename itself is only durable once the directory is flushed; Windows has no equivalent
+fn sync_directory(path: &Path) {
+    #[cfg(unix)]
+    if let Some(directory) = path.parent() {
+        let directory = if directory.as_os_str().is_empty() { Path::new(".") } else { directory };
+        if let Ok(directory) = File::open(directory) {
+            let _ = directory.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write;
+    use std::fs;
+
+    #[test]
+    fn test_write() {
+        let directory = std::env::temp_dir().join("zoterocoverage-safe-write");
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("chapter.md");
+        write(&path, b"first", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+        assert!(!directory.join("chapter.md.bak").exists());
+
+        write(&path, b"second", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_to_string(directory.join("chapter.md.bak")).unwrap(), "first");
+        assert!(!directory.join("chapter.md.tmp").exists());
+
+        let error = write(&directory.join("missing/chapter.md"), b"third", false).unwrap_err();
+        assert!(error.to_string().contains("could not write"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use rhai::{AST, Dynamic, Engine, Scope};
+use std::path::Path;
+
+use crate::{Citations, CoverageReport};
+
+// User hooks written in Rhai, all of them optional:
+//   fn normalize_key(key)   -> string   rewrite keys from both document and bibliography
+//   fn keep_key(key)        -> bool     false drops the key from both sides
+//   fn post_report(report)  -> map      edit the #{cited, uncited, unknown} report before printing
+pub struct Hooks {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Hooks {
+    pub fn compile(script: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let engine = Engine::new();
+        let ast = engine.compile(script)?;
+        Ok(Hooks { engine, ast })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let script = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read script {}: {}", path.display(), e))?;
+        Hooks::compile(&script).map_err(|e| format!("invalid script {}: {}", path.display(), e).into())
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name && f.params.len() == 1)
+    }
+
+    fn call(&self, name: &str, arg: impl Into<Dynamic>) -> Result<Dynamic, Box<dyn std::error::Error>> {
+        self.engine
+            .call_fn(&mut Scope::new(), &self.ast, name, (arg.into(),))
+            .map_err(|e| format!("{} failed: {}", name, e).into())
+    }
+
+    // Normalized keys without the dropped ones
+    pub fn apply_keys(&self, keys: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let (normalize, filter) = (self.defines("normalize_key"), self.defines("keep_key"));
+        let mut kept = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key = if normalize {
+                self.call("normalize_key", key)?
+                    .into_string()
+                    .map_err(|t| format!("normalize_key returned {} instead of a string", t))?
+            } else {
+                key
+            };
+            let keep = !filter
+                || self
+                    .call("keep_key", key.clone())?
+                    .as_bool()
+                    .map_err(|t| format!("keep_key returned {} instead of a bool", t))?;
+            if keep {
+                kept.push(key);
+            }
+        }
+        Ok(kept)
+    }
+
+    pub fn apply_bibliography(
+        &self,
+        citations: Vec<Citations>,
+    ) -> Result<Vec<Citations>, Box<dyn std::error::Error>> {
+        let keys = citations.into_iter().map(|c| c.citation_key).collect();
+        Ok(self
+            .apply_keys(keys)?
+            .into_iter()
+            .map(|citation_key| Citations { citation_key })
+            .collect())
+    }
+
+    pub fn post_report(&self, report: CoverageReport) -> Result<CoverageReport, Box<dyn std::error::Error>> {
+        if !self.defines("post_report") {
+            return Ok(report);
+        }
+        let report = rhai::serde::to_dynamic(report)?;
+        let report = self.call("post_report", report)?;
+        Ok(rhai::serde::from_dynamic(&report)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hooks;
+    use crate::CoverageReport;
+
+    #[test]
+    fn test_hooks() {
+        let hooks = Hooks::compile(
+            r#"
+fn normalize_key(key) { key.to_lower() }
+fn keep_key(key) { !key.starts_with("note-") }
+fn post_report(report) {
+    report.uncited = report.uncited.filter(|key| key != "ignored.2020");
+    report
+}
+"#,
+        )
+        .unwrap();
+
+        let keys = vec!["Smith.2009".to_string(), "note-Todo".to_string()];
+        assert_eq!(hooks.apply_keys(keys).unwrap(), vec!["smith.2009"]);
+
+        let report = CoverageReport {
+            cited: vec![],
+            uncited: vec!["ignored.2020".to_string(), "jones.2010".to_string()],
+            unknown: vec![],
+        };
+        assert_eq!(hooks.post_report(report).unwrap().uncited, vec!["jones.2010"]);
+    }
+
+    #[test]
+    fn test_hooks_missing() {
+        let hooks = Hooks::compile("fn unrelated(a, b) { a }").unwrap();
+        let keys = vec!["Smith.2009".to_string()];
+        assert_eq!(hooks.apply_keys(keys.clone()).unwrap(), keys);
+        assert!(Hooks::compile("fn broken(").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use regex::Regex;
+use serde::Serialize;
+use std::ops::Range;
+
+use crate::pandoc::parse_citations;
+
+// Byte ranges of the citations of key in a document: @key and @{key}, but not longer keys
+// like @keya, e-mail addresses or the key in plain prose
+pub fn citation_spans(document: &str, key: &str) -> Result<Vec<Range<usize>>, Box<dyn std::error::Error>> {
+    let re = Regex::new(&format!(r"@(\{{{0}\}}|{0})", regex::escape(key)))?;
+    let is_key_char = |c: char| c.is_alphanumeric() || c == '_';
+    Ok(re
+        .find_iter(document)
+        .filter(|m| {
+            let braced = m.as_str().starts_with("@{");
+            let before = document[..m.start()].chars().next_back();
+            let after = document[m.end()..].chars().next();
+            !before.is_some_and(is_key_char) && (braced || !after.is_some_and(is_key_char))
+        })
+        .map(|m| m.range())
+        .collect())
+}
+
+// A citation of the searched key, line and column start at 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+// The line and column of a byte offset
+pub fn location(document: &str, offset: usize) -> Location {
+    let before = &document[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Location {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+pub fn find_citations(document: &str, key: &str) -> Result<Vec<Location>, Box<dyn std::error::Error>> {
+    Ok(citation_spans(document, key)?
+        .into_iter()
+        .map(|span| location(document, span.start))
+        .collect())
+}
+
+// A citation in the document for editors: line and column (in characters) start at 1, start
+// and end are byte offsets of the key with its @ and braces
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub key: String,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+    // Cited in a footnote rather than the body text
+    pub footnote: bool,
+    // Path of the document, set in reports of several documents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<String>,
+}
+
+// Every citation of any key in document order, the document read once
+pub fn occurrences(document: &str) -> Vec<Occurrence> {
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut scanned = 0;
+    parse_citations(document)
+        .into_iter()
+        .map(|citation| {
+            let start = citation.span.start;
+            for (i, _) in document[scanned..start].match_indices('\n') {
+                line += 1;
+                line_start = scanned + i + 1;
+            }
+            scanned = start;
+            Occurrence {
+                key: citation.key,
+                line,
+                column: document[line_start..start].chars().count() + 1,
+                start,
+                end: citation.span.end,
+                footnote: citation.footnote,
+                document: None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Location, find_citations, location, occurrences};
+
+    #[test]
+    fn test_find_citations() {
+        let document = "# Intro\n[@BGH.2024; @BGH.2024a]\nsee mail@BGH.2024 and -@{BGH.2024}\n";
+        assert_eq!(
+            find_citations(document, "BGH.2024").unwrap(),
+            vec![Location { line: 2, column: 2 }, Location { line: 3, column: 24 }]
+        );
+        assert!(find_citations(document, "Smith.2009").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let document = "# Über\n[@BGH.2024; @Müller.2020]\n\nsee @{BGH.2024}\n";
+        let found = occurrences(document);
+        let positions: Vec<(&str, usize, usize)> = found.iter().map(|o| (o.key.as_str(), o.line, o.column)).collect();
+        assert_eq!(positions, vec![("BGH.2024", 2, 2), ("Müller.2020", 2, 13), ("BGH.2024", 4, 5)]);
+        for occurrence in &found {
+            assert_eq!(location(document, occurrence.start), Location { line: occurrence.line, column: occurrence.column });
+        }
+        assert_eq!(&document[found[2].start..found[2].end], "@{BGH.2024}");
+    }
+}
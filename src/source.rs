@@ -0,0 +1,231 @@
+use serde::Deserialize;
+use std::fmt;
+use std::io::{self, Read};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::{archive, better_bibtex, encoding, get_bibliography_path, mods, paths, rdf};
+#[cfg(feature = "network")]
+use crate::network;
+#[cfg(feature = "network")]
+use crate::zotero_api::{self, LibraryRef};
+
+// Settings shared by all network-backed bibliography sources
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkOptions {
+    pub timeout: Duration,
+    pub retries: u32,
+    // Proxy URL, e.g. http://proxy.uni.de:8080; without one HTTP(S)_PROXY / ALL_PROXY are used
+    pub proxy: Option<String>,
+    // Most requests running at once; None for one per CPU
+    pub jobs: Option<NonZeroUsize>,
+}
+
+// Where the bibliography is read from: a CSL-JSON file, stdin (-), the system clipboard (clipboard:)
+// or the Zotero Web API (zotero:users/<id>)
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub enum BibliographySource {
+    File(PathBuf),
+    Stdin,
+    #[cfg(feature = "clipboard")]
+    Clipboard,
+    #[cfg(feature = "network")]
+    ZoteroApi(LibraryRef),
+}
+
+impl FromStr for BibliographySource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(library) = s.strip_prefix("zotero:") {
+            #[cfg(feature = "network")]
+            return Ok(BibliographySource::ZoteroApi(library.parse()?));
+            #[cfg(not(feature = "network"))]
+            return Err(format!("cannot read {}, built without network support", library));
+        }
+        match s {
+            "-" => Ok(BibliographySource::Stdin),
+            #[cfg(feature = "clipboard")]
+            "clipboard:" => Ok(BibliographySource::Clipboard),
+            #[cfg(not(feature = "clipboard"))]
+            "clipboard:" => Err("cannot read the clipboard, built without clipboard support".to_string()),
+            path => Ok(BibliographySource::File(PathBuf::from(paths::native(&shellexpand::tilde(path))))),
+        }
+    }
+}
+
+impl TryFrom<String> for BibliographySource {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for BibliographySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BibliographySource::File(path) => write!(f, "{}", path.display()),
+            BibliographySource::Stdin => write!(f, "stdin"),
+            #[cfg(feature = "clipboard")]
+            BibliographySource::Clipboard => write!(f, "clipboard"),
+            #[cfg(feature = "network")]
+            BibliographySource::ZoteroApi(library) => write!(f, "zotero:{}", library),
+        }
+    }
+}
+
+impl BibliographySource {
+    // The bibliography named in the bibliography field of the document's yaml header
+    pub fn from_front_matter(document: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(get_bibliography_path(document)?.parse()?)
+    }
+
+    // Read the bibliography as CSL-JSON text; Better BibTeX JSON files are converted
+    #[cfg_attr(not(feature = "network"), allow(unused_variables))]
+    pub fn read(
+        &self,
+        api_key: Option<&str>,
+        network: &NetworkOptions,
+        mods_key: &str,
+        verbose: bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            BibliographySource::File(path) => read_file(path, mods_key),
+            BibliographySource::Stdin => {
+                let mut bibliography = Vec::new();
+                io::stdin().read_to_end(&mut bibliography)?;
+                to_csl_json(encoding::decode(&bibliography)?, mods_key)
+            }
+            // e.g. Zotero's quick copy with the Better CSL JSON format
+            #[cfg(feature = "clipboard")]
+            BibliographySource::Clipboard => {
+                let text = arboard::Clipboard::new()
+                    .and_then(|mut clipboard| clipboard.get_text())
+                    .map_err(|e| format!("could not read the clipboard: {}", e))?;
+                to_csl_json(text, mods_key)
+            }
+            #[cfg(feature = "network")]
+            BibliographySource::ZoteroApi(library) => {
+                api_to_csl(zotero_api::fetch_library(library, api_key, network, verbose)?)
+            }
+        }
+    }
+
+    // read on a tokio runtime, network-backed sources load without blocking it
+    #[cfg(feature = "network")]
+    pub async fn read_async(
+        &self,
+        api_key: Option<&str>,
+        network: &NetworkOptions,
+        mods_key: &str,
+        verbose: bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            BibliographySource::ZoteroApi(library) => {
+                api_to_csl(zotero_api::fetch_library_async(library, api_key, network, verbose).await?)
+            }
+            other => other.read(api_key, network, mods_key, verbose),
+        }
+    }
+
+    // Read several bibliographies at once, e.g. the libraries of all project mappings.
+    // The results are in the order of the sources.
+    #[cfg(feature = "network")]
+    pub fn read_all(
+        sources: &[BibliographySource],
+        api_key: Option<&str>,
+        network: &NetworkOptions,
+        mods_key: &str,
+        verbose: bool,
+    ) -> Result<Vec<Loaded>, Box<dyn std::error::Error>> {
+        let runtime = network::runtime(network.jobs)?;
+        // Errors are not Send, so the loads run as local tasks; their requests still run in parallel
+        let local = tokio::task::LocalSet::new();
+        Ok(local.block_on(&runtime, async {
+            let tasks: Vec<_> = sources
+                .iter()
+                .cloned()
+                .map(|source| {
+                    let api_key = api_key.map(str::to_string);
+                    let network = network.clone();
+                    let mods_key = mods_key.to_string();
+                    tokio::task::spawn_local(async move {
+                        source.read_async(api_key.as_deref(), &network, &mods_key, verbose).await
+                    })
+                })
+                .collect();
+            let mut bibliographies = Vec::new();
+            for task in tasks {
+                bibliographies.push(task.await.unwrap_or_else(|e| Err(e.into())));
+            }
+            bibliographies
+        }))
+    }
+}
+
+// A bibliography read by read_all, or why it couldn't be read
+#[cfg(feature = "network")]
+pub type Loaded = Result<String, Box<dyn std::error::Error>>;
+
+// The bibliography file as CSL-JSON; of a zip archive the bibliography inside is read
+pub fn read_file(path: &Path, mods_key: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let bibliography = if archive::is_zip(path) {
+        archive::read_bibliography(path)
+    } else {
+        encoding::read_to_string(path)
+    };
+    let bibliography = bibliography.map_err(|e| format!("could not read bibliography {}: {}", path.display(), e))?;
+    to_csl_json(bibliography, mods_key)
+}
+
+// The bibliography file or stdin as CSL-JSON: Better BibTeX JSON, MODS XML and Zotero RDF exports are
+// converted, the citation keys of MODS records are read from the mods_key field (see mods::to_csl_json)
+pub fn to_csl_json(bibliography: String, mods_key: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if mods::is_mods(&bibliography) {
+        mods::to_csl_json(&bibliography, mods_key)
+    } else if rdf::is_zotero_rdf(&bibliography) {
+        rdf::to_csl_json(&bibliography)
+    } else {
+        better_bibtex::normalize(bibliography)
+    }
+}
+
+// CSL-JSON text of the items fetched from the Zotero API
+#[cfg(feature = "network")]
+fn api_to_csl(fetched: zotero_api::CachedLibrary) -> Result<String, Box<dyn std::error::Error>> {
+    let attachments = zotero_api::attachments(&fetched.items);
+    let entries: Vec<_> = fetched.items.iter()
+        .filter_map(|item| zotero_api::item_to_csl(item, &fetched.collections, &attachments))
+        .collect();
+    Ok(serde_json::to_string(&entries)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BibliographySource;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_bibliography_source_from_str() {
+        assert_eq!(
+            "refs.json".parse::<BibliographySource>().unwrap(),
+            BibliographySource::File(PathBuf::from("refs.json"))
+        );
+        assert_eq!("-".parse::<BibliographySource>().unwrap(), BibliographySource::Stdin);
+        #[cfg(feature = "clipboard")]
+        assert_eq!("clipboard:".parse::<BibliographySource>().unwrap(), BibliographySource::Clipboard);
+        assert!("zotero:nonsense".parse::<BibliographySource>().is_err());
+        #[cfg(feature = "network")]
+        assert_eq!(
+            "zotero:groups/42".parse::<BibliographySource>().unwrap(),
+            BibliographySource::ZoteroApi(crate::zotero_api::LibraryRef {
+                kind: crate::zotero_api::LibraryKind::Group,
+                id: 42
+            })
+        );
+    }
+}
@@ -0,0 +1,528 @@
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::exclude::without_metadata;
+use crate::pandoc::{citation_clusters, cited_keys};
+use crate::library::LibraryEntry;
+use crate::search::location;
+
+// Citation and word counts for one section of the document
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionDensity {
+    // Heading text without the leading #, empty for text before the first heading
+    pub heading: String,
+    pub words: usize,
+    pub citations: usize,
+}
+
+impl SectionDensity {
+    pub fn per_thousand_words(&self) -> f64 {
+        if self.words == 0 {
+            0.0
+        } else {
+            self.citations as f64 * 1000.0 / self.words as f64
+        }
+    }
+}
+
+// Strip the yaml front matter, fenced code blocks and inline code so that only prose remains
+fn prose_lines(document: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut in_front_matter = false;
+    let mut fence: Option<&str> = None;
+
+    for (i, line) in document.lines().enumerate() {
+        let trimmed = line.trim();
+        if i == 0 && trimmed == "---" {
+            in_front_matter = true;
+            continue;
+        }
+        if in_front_matter {
+            if trimmed == "---" || trimmed == "..." {
+                in_front_matter = false;
+            }
+            continue;
+        }
+        match fence {
+            Some(marker) => {
+                if trimmed.starts_with(marker) {
+                    fence = None;
+                }
+            }
+            None if trimmed.starts_with("```") => fence = Some("```"),
+            None if trimmed.starts_with("~~~") => fence = Some("~~~"),
+            None => lines.push(line),
+        }
+    }
+
+    lines
+}
+
+fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+// Citations and words per section, sections start at markdown ATX headings
+pub fn citation_density(document: &str) -> Vec<SectionDensity> {
+    let heading = Regex::new(r"^#{1,6}\s+(?<title>.*?)\s*#*\s*$").unwrap();
+    let inline_code = Regex::new(r"`[^`]*`").unwrap();
+
+    let mut sections = vec![SectionDensity {
+        heading: String::new(),
+        words: 0,
+        citations: 0,
+    }];
+
+    for line in prose_lines(document) {
+        if let Some(caps) = heading.captures(line) {
+            sections.push(SectionDensity {
+                heading: caps["title"].to_string(),
+                words: 0,
+                citations: 0,
+            });
+            continue;
+        }
+        let text = inline_code.replace_all(line, " ");
+        let section = sections.last_mut().unwrap();
+        section.words += count_words(&text);
+        section.citations += cited_keys(&text).len();
+    }
+
+    // Drop the implicit first section if the document starts with a heading
+    if sections.len() > 1 && sections[0].words == 0 && sections[0].citations == 0 {
+        sections.remove(0);
+    }
+
+    sections
+}
+
+// Sum of all sections
+pub fn total_density(sections: &[SectionDensity]) -> SectionDensity {
+    SectionDensity {
+        heading: String::new(),
+        words: sections.iter().map(|s| s.words).sum(),
+        citations: sections.iter().map(|s| s.citations).sum(),
+    }
+}
+
+// Number of largest clusters listed
+const LARGEST_CLUSTERS: usize = 5;
+
+// Citation clusters of the document, see pandoc::citation_clusters; keys are counted once per cluster
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterStats {
+    pub clusters: usize,
+    pub keys: usize,
+    // Footnotes with citations, and those citing a single source
+    pub footnotes: usize,
+    pub single_source_footnotes: usize,
+    // Clusters of several keys with the most keys, as (keys, line of the first citation)
+    pub largest: Vec<(usize, usize)>,
+}
+
+impl ClusterStats {
+    pub fn keys_per_cluster(&self) -> f64 {
+        if self.clusters == 0 {
+            0.0
+        } else {
+            self.keys as f64 / self.clusters as f64
+        }
+    }
+}
+
+// Citations in the YAML header (nocite, abstract) are left out
+pub fn cluster_stats(document: &str) -> ClusterStats {
+    let document = without_metadata(document);
+    let clusters = citation_clusters(&document);
+    let mut stats = ClusterStats { clusters: clusters.len(), keys: 0, footnotes: 0, single_source_footnotes: 0, largest: Vec::new() };
+    let mut sizes = Vec::new();
+    for cluster in &clusters {
+        let keys = cluster.iter().map(|citation| citation.key.as_str()).collect::<HashSet<_>>().len();
+        stats.keys += keys;
+        if cluster[0].footnote {
+            stats.footnotes += 1;
+            if keys == 1 {
+                stats.single_source_footnotes += 1;
+            }
+        }
+        if keys > 1 {
+            sizes.push((keys, cluster[0].span.start));
+        }
+    }
+    // Ties in document order
+    sizes.sort_by_key(|&(keys, _)| std::cmp::Reverse(keys));
+    stats.largest = sizes
+        .into_iter()
+        .take(LARGEST_CLUSTERS)
+        .map(|(keys, start)| (keys, location(&document, start).line))
+        .collect();
+    stats
+}
+
+// The statistics as CSV for spreadsheets: a row per section and for the whole document with
+// citations, words and citations per 1000 words, then a row per cited key with its number of
+// citations (keys as extracted, in order of first citation)
+pub fn to_csv(sections: &[SectionDensity], keys: &[String]) -> String {
+    let mut csv = String::from("kind,name,citations,words,per_1000_words\n");
+    let total = total_density(sections);
+    for (kind, section) in sections.iter().map(|s| ("section", s)).chain([("total", &total)]) {
+        csv.push_str(&format!("{},{},{},{},{:.2}\n", kind, csv_field(&section.heading), section.citations,
+                              section.words, section.per_thousand_words()));
+    }
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for key in keys {
+        match counts.iter_mut().find(|(k, _)| k == key) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((key, 1)),
+        }
+    }
+    for (key, count) in counts {
+        csv.push_str(&format!("key,{},{},,\n", csv_field(key), count));
+    }
+    csv
+}
+
+// Quoted if it contains a separator, quote or line break (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Width of the longest bar of a chart, in characters
+const CHART_WIDTH: usize = 40;
+
+// Horizontal bar chart, a line per label with a bar of # scaled to the largest value, e.g.
+// "Einleitung  ##########  12"
+pub fn bar_chart(rows: &[(String, usize)]) -> Vec<String> {
+    let stacked: Vec<(String, usize, usize)> = rows.iter().map(|(label, value)| (label.clone(), *value, 0)).collect();
+    chart(&stacked, |value, _| value.to_string())
+}
+
+// Bar chart of cited (#) and uncited (-) entries, e.g. "2024  ######----  6/10"
+pub fn coverage_chart(rows: &[(String, BucketCount)]) -> Vec<String> {
+    let stacked: Vec<(String, usize, usize)> =
+        rows.iter().map(|(label, count)| (label.clone(), count.cited, count.uncited)).collect();
+    chart(&stacked, |cited, uncited| format!("{}/{}", cited, cited + uncited))
+}
+
+fn chart(rows: &[(String, usize, usize)], value: impl Fn(usize, usize) -> String) -> Vec<String> {
+    let max = rows.iter().map(|(_, first, second)| first + second).max().unwrap_or(0).max(1);
+    let label_width = rows.iter().map(|(label, _, _)| label.chars().count()).max().unwrap_or(0);
+    // Rounded, but a non-zero value always gets a character
+    let scale = |n: usize| if n == 0 { 0 } else { ((n * CHART_WIDTH + max / 2) / max).max(1) };
+    rows.iter()
+        .map(|(label, first, second)| {
+            let bar = format!("{}{}", "#".repeat(scale(*first)), "-".repeat(scale(*second)));
+            let padding = label_width - label.chars().count();
+            format!("{}{}  {:<width$}  {}", label, " ".repeat(padding), bar, value(*first, *second),
+                    width = CHART_WIDTH + 1)
+        })
+        .collect()
+}
+
+// Number of cited and uncited library entries falling into one bucket
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BucketCount {
+    pub cited: usize,
+    pub uncited: usize,
+}
+
+impl BucketCount {
+    pub fn total(&self) -> usize {
+        self.cited + self.uncited
+    }
+}
+
+// Library entries bucketed by year, type and language, legal cases also by jurisdiction;
+// missing values go to "unknown"
+#[derive(Debug, Default, PartialEq)]
+pub struct LibraryStats {
+    pub total: BucketCount,
+    pub by_year: BTreeMap<String, BucketCount>,
+    pub by_type: BTreeMap<String, BucketCount>,
+    pub by_language: BTreeMap<String, BucketCount>,
+    pub by_jurisdiction: BTreeMap<String, BucketCount>,
+}
+
+pub fn library_stats(entries: &[LibraryEntry], cited: &HashSet<&str>) -> LibraryStats {
+    let mut stats = LibraryStats::default();
+    let unknown = || "unknown".to_string();
+
+    for entry in entries {
+        let mut buckets = vec![
+            (&mut stats.by_year, entry.year().map(|y| y.to_string())),
+            (&mut stats.by_type, entry.item_type.clone()),
+            (&mut stats.by_language, entry.language.clone()),
+        ];
+        if entry.item_type.as_deref() == Some("legal_case") {
+            buckets.push((&mut stats.by_jurisdiction, entry.jurisdiction.clone()));
+        }
+        let is_cited = cited.contains(entry.citation_key.as_str());
+        for (map, value) in buckets {
+            let count = map.entry(value.unwrap_or_else(unknown)).or_default();
+            if is_cited {
+                count.cited += 1;
+            } else {
+                count.uncited += 1;
+            }
+        }
+        if is_cited {
+            stats.total.cited += 1;
+        } else {
+            stats.total.uncited += 1;
+        }
+    }
+
+    stats
+}
+
+// Cited and uncited entries per Zotero collection, entries in several collections count in each;
+// None holds the entries in no collection
+pub fn coverage_by_collection(entries: &[LibraryEntry], cited: &HashSet<&str>) -> BTreeMap<Option<String>, BucketCount> {
+    let mut by_collection: BTreeMap<Option<String>, BucketCount> = BTreeMap::new();
+    for entry in entries {
+        let collections: Vec<Option<String>> = if entry.collections.is_empty() {
+            vec![None]
+        } else {
+            entry.collections.iter().cloned().map(Some).collect()
+        };
+        for collection in collections {
+            let count = by_collection.entry(collection).or_default();
+            if cited.contains(entry.citation_key.as_str()) {
+                count.cited += 1;
+            } else {
+                count.uncited += 1;
+            }
+        }
+    }
+    by_collection
+}
+
+// Uncited entries per issued year, undated entries (None) first; keys missing from the entries are skipped
+pub fn uncited_by_year(entries: &[LibraryEntry], uncited: &[String]) -> BTreeMap<Option<i32>, Vec<String>> {
+    let mut by_year: BTreeMap<Option<i32>, Vec<String>> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| uncited.contains(&e.citation_key)) {
+        by_year.entry(entry.year()).or_default().push(entry.citation_key.clone());
+    }
+    by_year
+}
+
+// How German legal writing groups sources: case law, commentaries (entry-encyclopedia in Zotero)
+// and the remaining literature; case law per jurisdiction
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LegalGroup {
+    CaseLaw(Option<String>),
+    Commentaries,
+    Literature,
+}
+
+impl LegalGroup {
+    pub fn of(entry: Option<&LibraryEntry>) -> Self {
+        match entry.and_then(|e| e.item_type.as_deref()) {
+            Some("legal_case") => LegalGroup::CaseLaw(entry.and_then(|e| e.jurisdiction.clone())),
+            Some("entry-encyclopedia") => LegalGroup::Commentaries,
+            _ => LegalGroup::Literature,
+        }
+    }
+}
+
+impl fmt::Display for LegalGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LegalGroup::CaseLaw(Some(jurisdiction)) => write!(f, "Case law ({})", jurisdiction),
+            LegalGroup::CaseLaw(None) => write!(f, "Case law"),
+            LegalGroup::Commentaries => write!(f, "Commentaries"),
+            LegalGroup::Literature => write!(f, "Literature"),
+        }
+    }
+}
+
+// Uncited keys per legal group, in their original order; keys missing from the entries count as literature
+pub fn uncited_by_legal_group(entries: &[LibraryEntry], uncited: &[String]) -> BTreeMap<LegalGroup, Vec<String>> {
+    let mut groups: BTreeMap<LegalGroup, Vec<String>> = BTreeMap::new();
+    for key in uncited {
+        let entry = entries.iter().find(|e| &e.citation_key == key);
+        groups.entry(LegalGroup::of(entry)).or_default().push(key.clone());
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BucketCount, ClusterStats, LegalGroup, bar_chart, citation_density, cluster_stats, coverage_chart, coverage_by_collection, library_stats, to_csv, total_density, uncited_by_legal_group, uncited_by_year};
+    use crate::library::get_library_entries;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_citation_density() {
+        let document = r#"---
+bibliography: refs.json
+---
+# Einleitung
+
+Some words here.[@BGH.2024; @BGH.2010c]
+
+```
+@NotACitation.2020 inside code
+```
+
+## Zweiter Teil ##
+
+Four words, one `@Code.2021` citation @Alexander.2024
+"#;
+        let sections = citation_density(document);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].heading, "Einleitung");
+        assert_eq!(sections[0].citations, 2);
+        assert_eq!(sections[0].words, 4);
+        assert_eq!(sections[1].heading, "Zweiter Teil");
+        assert_eq!(sections[1].citations, 1);
+        assert_eq!(sections[1].words, 5);
+
+        let total = total_density(&sections);
+        assert_eq!(total.citations, 3);
+        assert_eq!(total.words, 9);
+        assert!((total.per_thousand_words() - 333.333).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_citation_density_no_headings() {
+        let sections = citation_density("Just @Alexander.2024 text");
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].heading, "");
+        assert_eq!(sections[0].citations, 1);
+    }
+
+    #[test]
+    fn test_citation_density_empty() {
+        let total = total_density(&citation_density(""));
+        assert_eq!(total.per_thousand_words(), 0.0);
+    }
+
+    #[test]
+    fn test_cluster_stats() {
+        let document = "---\nnocite: '@Z.2000'\n---\nText [@A.2020; @B.2021; @A.2020 p. 3].^[vgl. @BGH.2024 Rn. 3.]\n\n\
+                        More @C.2022.^[@BGH.2024; @A.2020; @Alexander.2024 Rn. 5; @D.2023]\n\n\
+                        [^1]: @BGH.2010c Rn. 36.\n";
+        let stats = cluster_stats(document);
+        assert_eq!(
+            stats,
+            ClusterStats { clusters: 5, keys: 9, footnotes: 3, single_source_footnotes: 2, largest: vec![(4, 6), (2, 4)] }
+        );
+        assert_eq!(stats.keys_per_cluster(), 1.8);
+        assert_eq!(cluster_stats("No citations").keys_per_cluster(), 0.0);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let sections = citation_density("# Einleitung, kurz\n\nWords @A.2020 and @B.2021\n\n# Teil\n\nMore @A.2020\n");
+        let keys: Vec<String> = ["A.2020", "B.2021", "A.2020"].map(String::from).to_vec();
+        assert_eq!(
+            to_csv(&sections, &keys),
+            "kind,name,citations,words,per_1000_words\n\
+             section,\"Einleitung, kurz\",2,4,500.00\n\
+             section,Teil,1,2,500.00\n\
+             total,,3,6,500.00\n\
+             key,A.2020,2,,\n\
+             key,B.2021,1,,\n"
+        );
+    }
+
+    #[test]
+    fn test_charts() {
+        let rows = vec![("Einleitung".to_string(), 12), ("Teil".to_string(), 3), ("Schluss".to_string(), 0)];
+        let lines = bar_chart(&rows);
+        assert_eq!(lines[0], format!("Einleitung  {}   12", "#".repeat(40)));
+        assert!(lines[1].starts_with("Teil        ##########  "));
+        assert!(lines[2].starts_with("Schluss     ") && lines[2].ends_with(" 0"));
+
+        let years = vec![("2010".to_string(), BucketCount { cited: 1, uncited: 3 }),
+                         ("2024".to_string(), BucketCount { cited: 0, uncited: 1 })];
+        let lines = coverage_chart(&years);
+        assert!(lines[0].starts_with(&format!("2010  ##########{}  ", "-".repeat(30))));
+        assert!(lines[0].ends_with("1/4"));
+        assert!(lines[1].starts_with("2024  ----------  ") && lines[1].ends_with("0/1"));
+    }
+
+    #[test]
+    fn test_library_stats() {
+        let entries = get_library_entries(
+            r#"[
+  {"citation-key": "BGH.2024", "type": "legal_case", "language": "de", "issued": {"date-parts": [["2024"]]}},
+  {"citation-key": "BGH.2010c", "type": "legal_case", "issued": {"date-parts": [[2010]]}},
+  {"citation-key": "Alexander.2024", "type": "entry-encyclopedia", "language": "de", "issued": {"date-parts": [["2024"]]}}
+]"#,
+        )
+        .unwrap();
+        let cited: HashSet<&str> = HashSet::from(["BGH.2024", "Alexander.2024"]);
+
+        let stats = library_stats(&entries, &cited);
+        assert_eq!(stats.total, BucketCount { cited: 2, uncited: 1 });
+        assert_eq!(stats.by_year["2024"], BucketCount { cited: 2, uncited: 0 });
+        assert_eq!(stats.by_year["2010"], BucketCount { cited: 0, uncited: 1 });
+        assert_eq!(stats.by_type["legal_case"].total(), 2);
+        assert_eq!(stats.by_language["unknown"], BucketCount { cited: 0, uncited: 1 });
+    }
+
+    #[test]
+    fn test_coverage_by_collection() {
+        let entries = get_library_entries(
+            r#"[
+  {"citation-key": "BGH.2024", "collections": ["Rechtsprechung BGH"]},
+  {"citation-key": "Smith.2009", "collections": ["Literatur KI-VO", "Rechtsprechung BGH"]},
+  {"citation-key": "Jones.2010", "collections": ["Literatur KI-VO"]},
+  {"citation-key": "Loose.2020"}
+]"#,
+        )
+        .unwrap();
+        let cited: HashSet<&str> = HashSet::from(["BGH.2024", "Smith.2009"]);
+        let by_collection = coverage_by_collection(&entries, &cited);
+        assert_eq!(by_collection[&Some("Rechtsprechung BGH".to_string())], BucketCount { cited: 2, uncited: 0 });
+        assert_eq!(by_collection[&Some("Literatur KI-VO".to_string())], BucketCount { cited: 1, uncited: 1 });
+        assert_eq!(by_collection[&None], BucketCount { cited: 0, uncited: 1 });
+    }
+
+    #[test]
+    fn test_uncited_by_year() {
+        let entries = get_library_entries(
+            r#"[
+  {"citation-key": "A.2010", "issued": {"date-parts": [[2010]]}},
+  {"citation-key": "B.2010", "issued": {"date-parts": [["2010", 5]]}},
+  {"citation-key": "C.2024", "issued": {"date-parts": [[2024]]}},
+  {"citation-key": "D"}
+]"#,
+        )
+        .unwrap();
+        let uncited: Vec<String> = ["A.2010", "B.2010", "D"].iter().map(|k| k.to_string()).collect();
+        let by_year = uncited_by_year(&entries, &uncited);
+        assert_eq!(by_year.len(), 2);
+        assert_eq!(by_year[&None], vec!["D"]);
+        assert_eq!(by_year[&Some(2010)], vec!["A.2010", "B.2010"]);
+    }
+
+    #[test]
+    fn test_uncited_by_legal_group() {
+        let entries = get_library_entries(
+            r#"[
+  {"citation-key": "BGH.2024", "type": "legal_case"},
+  {"citation-key": "EuGH.2024", "type": "legal_case", "jurisdiction": "eu"},
+  {"citation-key": "Alexander.2024", "type": "entry-encyclopedia"},
+  {"citation-key": "Smith.2009", "type": "book"}
+]"#,
+        )
+        .unwrap();
+        let uncited: Vec<String> = ["Smith.2009", "BGH.2024", "EuGH.2024", "Alexander.2024", "Missing.2020"].iter().map(|k| k.to_string()).collect();
+        let groups = uncited_by_legal_group(&entries, &uncited);
+        assert_eq!(
+            groups.keys().map(|g| g.to_string()).collect::<Vec<_>>(),
+            vec!["Case law", "Case law (eu)", "Commentaries", "Literature"]
+        );
+        assert_eq!(groups[&LegalGroup::Literature], vec!["Smith.2009", "Missing.2020"]);
+    }
+}
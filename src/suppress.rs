@@ -0,0 +1,103 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+
+use crate::CoverageReport;
+
+// An entry intentionally left uncited, with the reason if one was given
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suppression {
+    pub key: String,
+    pub reason: Option<String>,
+}
+
+// Suppressions from comments like
+//   <!-- zoterocoverage: allow-uncited @Alexander.2024a @BGH.2010c only in the appendix -->
+// the keys come first, the rest of the comment is the reason
+pub fn parse_suppressions(document: &str) -> Vec<Suppression> {
+    let mut suppressions = Vec::new();
+    for caps in directive_regex().captures_iter(document) {
+        let words: Vec<&str> = caps[1].split_whitespace().collect();
+        let keys = words.iter().take_while(|w| w.starts_with('@')).count();
+        let reason = (keys < words.len()).then(|| words[keys..].join(" "));
+        for key in &words[..keys] {
+            suppressions.push(Suppression {
+                key: key.trim_start_matches('@').to_string(),
+                reason: reason.clone(),
+            });
+        }
+    }
+    suppressions
+}
+
+fn directive_regex() -> Regex {
+    Regex::new(r"(?s)<!--\s*zoterocoverage:\s*allow-uncited\s+(.*?)\s*-->").unwrap()
+}
+
+// The document with the suppression comments blanked out, so their keys don't count as citations;
+// line breaks are kept so line numbers stay the same
+pub fn without_directives(document: &str) -> String {
+    directive_regex()
+        .replace_all(document, |caps: &regex::Captures| {
+            caps[0].chars().map(|c| if c == '\n' { c } else { ' ' }).collect::<String>()
+        })
+        .into_owned()
+}
+
+// Suppressions from the config, key = "reason"
+pub fn from_config(allow_uncited: &BTreeMap<String, String>) -> Vec<Suppression> {
+    allow_uncited
+        .iter()
+        .map(|(key, reason)| Suppression {
+            key: key.clone(),
+            reason: (!reason.is_empty()).then(|| reason.clone()),
+        })
+        .collect()
+}
+
+// Move the suppressed keys out of the uncited list, returns the suppressions that applied
+pub fn apply(report: &mut CoverageReport, suppressions: &[Suppression]) -> Vec<Suppression> {
+    let mut applied: Vec<Suppression> = Vec::new();
+    report.uncited.retain(|key| match suppressions.iter().find(|s| &s.key == key) {
+        Some(suppression) => {
+            applied.push(suppression.clone());
+            false
+        }
+        None => true,
+    });
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Suppression, apply, parse_suppressions, without_directives};
+    use crate::CoverageReport;
+
+    #[test]
+    fn test_suppressions() {
+        let document = "Text\n<!-- zoterocoverage: allow-uncited @A.2020 @B.2021 background\n reading -->\n\
+                        <!-- zoterocoverage: allow-uncited @C.2022 -->\n<!-- unrelated @D.2023 -->";
+        let suppressions = parse_suppressions(document);
+        assert_eq!(
+            suppressions,
+            vec![
+                Suppression { key: "A.2020".into(), reason: Some("background reading".into()) },
+                Suppression { key: "B.2021".into(), reason: Some("background reading".into()) },
+                Suppression { key: "C.2022".into(), reason: None },
+            ]
+        );
+
+        let mut report = CoverageReport {
+            cited: vec!["B.2021".into()],
+            uncited: vec!["A.2020".into(), "D.2023".into()],
+            unknown: vec![],
+        };
+        let applied = apply(&mut report, &suppressions);
+        assert_eq!(report.uncited, vec!["D.2023"]);
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].key, "A.2020");
+
+        let blanked = without_directives(document);
+        assert!(!blanked.contains("@A.2020") && blanked.contains("@D.2023"));
+        assert_eq!(blanked.lines().count(), document.lines().count());
+    }
+}
@@ -0,0 +1,423 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::pandoc;
+
+// Markup of a document, each with its own citation syntax
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Syntax {
+    // Pandoc markdown, also Quarto and R Markdown: [@key], @key
+    Markdown,
+    // LaTeX with natbib or biblatex: \cite{key}, \parencite[p. 3]{key}, \cites{a}{b}
+    Latex,
+    // Typst: @key, #cite(<key>)
+    Typst,
+    // Org mode: [cite:@key], org-ref cite:key
+    Org,
+    // AsciiDoc with asciidoctor-bibtex: cite:[key], citenp:[a(12),b]
+    AsciiDoc,
+    // Jupyter notebooks: pandoc citations in the markdown cells
+    Notebook,
+    // MultiMarkdown, e.g. exported from Scrivener: [#key], [p. 23][#key]
+    MultiMarkdown,
+    // Djot with pandoc's citations: [@key], @key
+    Djot,
+    // The .aux file LaTeX writes while compiling: \citation{key}, biblatex's \abx@aux@cite{0}{key}
+    Aux,
+}
+
+impl Syntax {
+    pub const ALL: [Syntax; 9] = [
+        Syntax::Markdown,
+        Syntax::Latex,
+        Syntax::Typst,
+        Syntax::Org,
+        Syntax::AsciiDoc,
+        Syntax::Notebook,
+        Syntax::MultiMarkdown,
+        Syntax::Djot,
+        Syntax::Aux,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Syntax::Markdown => "markdown",
+            Syntax::Latex => "latex",
+            Syntax::Typst => "typst",
+            Syntax::Org => "org",
+            Syntax::AsciiDoc => "asciidoc",
+            Syntax::Notebook => "notebook",
+            Syntax::MultiMarkdown => "multimarkdown",
+            Syntax::Djot => "djot",
+            Syntax::Aux => "aux",
+        }
+    }
+
+    // The cited keys in document order, including repetitions
+    pub fn cited_keys(self, document: &str) -> Vec<String> {
+        match self {
+            Syntax::Markdown => pandoc::cited_keys(document),
+            Syntax::Latex => latex_keys(document),
+            Syntax::Typst => typst_keys(document),
+            Syntax::Org => org_keys(document),
+            Syntax::AsciiDoc => asciidoc_keys(document),
+            Syntax::Notebook => notebook_keys(document),
+            Syntax::MultiMarkdown => multimarkdown_keys(document),
+            Syntax::Djot => djot_keys(document),
+            Syntax::Aux => aux_keys(document),
+        }
+    }
+
+    // The text holding the document's pandoc citations, for what needs more than the keys (repeated
+    // citations, locators, clusters, statistics): the markdown or Djot text without comments, the
+    // markdown cells of a notebook one paragraph each; None for syntaxes without pandoc citations
+    pub fn pandoc_text(self, document: &str) -> Option<Cow<'_, str>> {
+        match self {
+            Syntax::Markdown => Some(Cow::Borrowed(document)),
+            Syntax::Djot => Some(Cow::Owned(djot_without_comments(document))),
+            Syntax::Notebook => Some(match notebook_cells(document) {
+                Some(cells) => Cow::Owned(cells.join("\n\n")),
+                None => Cow::Borrowed(document),
+            }),
+            Syntax::Latex | Syntax::Typst | Syntax::Org | Syntax::AsciiDoc | Syntax::MultiMarkdown | Syntax::Aux => None,
+        }
+    }
+}
+
+const EXTENSIONS: [(&str, Syntax); 17] = [
+    ("md", Syntax::Markdown),
+    ("markdown", Syntax::Markdown),
+    ("qmd", Syntax::Markdown),
+    ("rmd", Syntax::Markdown),
+    ("txt", Syntax::Markdown),
+    ("tex", Syntax::Latex),
+    ("ltx", Syntax::Latex),
+    ("latex", Syntax::Latex),
+    ("typ", Syntax::Typst),
+    ("org", Syntax::Org),
+    ("adoc", Syntax::AsciiDoc),
+    ("asciidoc", Syntax::AsciiDoc),
+    ("ipynb", Syntax::Notebook),
+    ("mmd", Syntax::MultiMarkdown),
+    ("dj", Syntax::Djot),
+    ("djot", Syntax::Djot),
+    ("aux", Syntax::Aux),
+];
+
+// The syntax of documents by file extension, the built-in ones and the [extensions] of the config,
+// e.g. mdx = "markdown"
+#[derive(Debug, Clone)]
+pub struct Registry {
+    extensions: HashMap<String, Syntax>,
+}
+
+impl Registry {
+    pub fn new(custom: &BTreeMap<String, Syntax>) -> Self {
+        let mut extensions: HashMap<String, Syntax> =
+            EXTENSIONS.iter().map(|&(extension, syntax)| (extension.to_string(), syntax)).collect();
+        extensions.extend(
+            custom.iter().map(|(extension, &syntax)| (extension.trim_start_matches('.').to_ascii_lowercase(), syntax)),
+        );
+        Registry { extensions }
+    }
+
+    // The syntax for the extension of the file, e.g. thesis.tex; None for unknown extensions
+    pub fn get(&self, path: &Path) -> Option<&Syntax> {
+        self.extensions.get(&path.extension()?.to_str()?.to_ascii_lowercase())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new(&BTreeMap::new())
+    }
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Syntax::ALL
+            .into_iter()
+            .find(|syntax| syntax.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Syntax::ALL.iter().map(|syntax| syntax.name()).collect();
+                format!("unknown syntax {}, expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// \cite{a,b}, \citep[p. 3]{a}, \parencite[see][12]{a}, \autocite*{a} and the multicite commands
+// like \cites[3]{a}[5]{b}, not in % comments; \nocite{*} cites no key
+fn latex_keys(document: &str) -> Vec<String> {
+    let comment = Regex::new(r"(?m)(^|[^\\])%.*$").unwrap();
+    let command = Regex::new(r"\\[A-Za-z]*cite[A-Za-z]*\*?").unwrap();
+    let argument = Regex::new(r"^\s*(?:\([^)]*\)\s*)*(?:\[[^\]]*\]\s*){0,2}\{([^}]*)\}").unwrap();
+    let text = comment.replace_all(document, "${1}");
+    let mut keys = Vec::new();
+    for command in command.find_iter(&text) {
+        let multicite = command.as_str().trim_end_matches('*').ends_with("cites");
+        let mut rest = &text[command.end()..];
+        while let Some(caps) = argument.captures(rest) {
+            keys.extend(caps[1].split(',').map(str::trim).filter(|key| !key.is_empty() && *key != "*").map(String::from));
+            rest = &rest[caps[0].len()..];
+            if !multicite {
+                break;
+            }
+        }
+    }
+    keys
+}
+
+// @key and #cite(<key>) or #cite(label("key")), not in comments or raw text. References to labels
+// of the document itself (<fig-map>, <intro>) are cross-references, not citations.
+fn typst_keys(document: &str) -> Vec<String> {
+    let ignored = Regex::new(r"(?s)/\*.*?\*/|```.*?```|`[^`]*`|(?m:(?:^|[ \t])//.*$)").unwrap();
+    let citation = Regex::new(r#"(?:^|[^\w@])@([\w\-.:]*[\w\-])|#cite\(\s*(?:<([^>]+)>|label\("([^"]+)"\))"#).unwrap();
+    let label = Regex::new(r"<([\w\-.:]+)>").unwrap();
+    let text = ignored.replace_all(document, " ");
+    let without_citations = citation.replace_all(&text, " ");
+    let labels: HashSet<&str> = label
+        .captures_iter(&without_citations)
+        .map(|caps| caps.get(1).unwrap().as_str())
+        .collect();
+    citation
+        .captures_iter(&text)
+        .filter_map(|caps| caps.get(1).or(caps.get(2)).or(caps.get(3)))
+        .map(|key| key.as_str())
+        .filter(|key| !labels.contains(key))
+        .map(String::from)
+        .collect()
+}
+
+// org-cite [cite:@a;@b] and [cite/t:see @a p. 3], and org-ref links cite:a,b or citep:&a;&b,
+// not in comment lines or source and example blocks
+fn org_keys(document: &str) -> Vec<String> {
+    let mut text = String::new();
+    let mut block = false;
+    for line in document.split_inclusive('\n') {
+        let trimmed = line.trim_start().to_ascii_lowercase();
+        let comment = trimmed.starts_with("# ") || trimmed.trim_end() == "#";
+        if trimmed.starts_with("#+begin_") {
+            block = true;
+        } else if trimmed.starts_with("#+end_") {
+            block = false;
+        } else if !block && !comment {
+            text.push_str(line);
+            continue;
+        }
+        text.push('\n');
+    }
+
+    let org_cite = Regex::new(r"\[cite(?:/[\w/-]*)?:([^\]]*)\]").unwrap();
+    let org_cite_key = Regex::new(r"@([^\s;\]]+)").unwrap();
+    let org_ref = Regex::new(r"(?:^|[^\w\[/])[a-z]*cite[a-z]*\*?:(&?[\w\-.:/]+(?:[,;]\s*&?[\w\-.:/]+)*)").unwrap();
+    let mut keys: Vec<(usize, String)> = Vec::new();
+    for caps in org_cite.captures_iter(&text) {
+        let content = caps.get(1).unwrap();
+        for key in org_cite_key.captures_iter(content.as_str()) {
+            keys.push((content.start() + key.get(0).unwrap().start(), key[1].to_string()));
+        }
+    }
+    let without_org_cite = org_cite.replace_all(&text, |caps: &regex::Captures| " ".repeat(caps[0].len()));
+    for caps in org_ref.captures_iter(&without_org_cite) {
+        let list = caps.get(1).unwrap();
+        for key in list.as_str().split([',', ';']) {
+            let key = key.trim().trim_start_matches('&').trim_end_matches(['.', ':']);
+            if !key.is_empty() {
+                keys.push((list.start(), key.to_string()));
+            }
+        }
+    }
+    keys.sort_by_key(|(position, _)| *position);
+    keys.into_iter().map(|(_, key)| key).collect()
+}
+
+// asciidoctor-bibtex cite:[key] and citenp:[a(12),b], not in // comment lines or //// blocks
+fn asciidoc_keys(document: &str) -> Vec<String> {
+    let citation = Regex::new(r"\bcite(?:np)?:\[([^\]]*)\]").unwrap();
+    let mut keys = Vec::new();
+    let mut comment = false;
+    for line in document.lines() {
+        if line.trim_end() == "////" {
+            comment = !comment;
+        }
+        if comment || line.starts_with("//") {
+            continue;
+        }
+        for caps in citation.captures_iter(line) {
+            for key in caps[1].split(',') {
+                // a locator in parentheses follows the key
+                let key = key.split('(').next().unwrap_or_default().trim();
+                if !key.is_empty() {
+                    keys.push(key.to_string());
+                }
+            }
+        }
+    }
+    keys
+}
+
+// The citations of the markdown cells of a Jupyter notebook; text that isn't JSON is read as markdown
+fn notebook_keys(document: &str) -> Vec<String> {
+    match notebook_cells(document) {
+        Some(cells) => cells.iter().flat_map(|cell| pandoc::cited_keys(cell)).collect(),
+        None => pandoc::cited_keys(document),
+    }
+}
+
+// The markdown cells of a Jupyter notebook, with their source as one string or a list of lines;
+// None if the document isn't JSON
+fn notebook_cells(document: &str) -> Option<Vec<String>> {
+    let notebook = serde_json::from_str::<serde_json::Value>(document).ok()?;
+    let cells = notebook["cells"].as_array().map(Vec::as_slice).unwrap_or_default();
+    Some(
+        cells
+            .iter()
+            .filter(|cell| cell["cell_type"] == "markdown")
+            .map(|cell| match &cell["source"] {
+                serde_json::Value::Array(lines) => lines.iter().filter_map(|line| line.as_str()).collect(),
+                serde_json::Value::String(source) => source.clone(),
+                _ => String::new(),
+            })
+            .collect(),
+    )
+}
+
+// MultiMarkdown [#key], also after a locator or text, [p. 23][#key], not in code or HTML comments.
+// A line starting with [#key]: defines an entry of the document's own bibliography and cites nothing.
+fn multimarkdown_keys(document: &str) -> Vec<String> {
+    let citation = Regex::new(r"\[#([^\[\]]+)\](:)?").unwrap();
+    let text = pandoc::mask(document);
+    citation
+        .captures_iter(&text)
+        .filter(|caps| {
+            let start = caps.get(0).unwrap().start();
+            let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+            caps.get(2).is_none() || !text[line_start..start].trim().is_empty()
+        })
+        .map(|caps| caps[1].trim().to_string())
+        .filter(|key| !key.is_empty())
+        .collect()
+}
+
+// Djot has no citations of its own; pandoc's [@key] and @key are used like in markdown. Code spans
+// and blocks (``` and ~~~ fences as in markdown) cite nothing, neither do comments {% ... %}, which
+// may span lines within an attribute block.
+fn djot_keys(document: &str) -> Vec<String> {
+    pandoc::cited_keys(&djot_without_comments(document))
+}
+
+// Comments blanked out, keeping byte offsets and line breaks
+fn djot_without_comments(document: &str) -> String {
+    let comment = Regex::new(r"(?s)\{%.*?%\}").unwrap();
+    comment
+        .replace_all(document, |caps: &regex::Captures| caps[0].bytes().map(|b| if b == b'\n' { '\n' } else { ' ' }).collect::<String>())
+        .into_owned()
+}
+
+// The keys LaTeX resolved while compiling, with every macro expanded: \citation{a,b} of BibTeX and
+// natbib, \abx@aux@cite{key} of biblatex, since 3.12 with the refsection first, \abx@aux@cite{0}{key}.
+// \citation{*} of \nocite{*} cites no key; \bibcite lines are the resolved entries, not citations.
+fn aux_keys(document: &str) -> Vec<String> {
+    let citation = Regex::new(r"\\(?:citation|abx@aux@cite(?:\{\d+\})?)\{([^}]*)\}").unwrap();
+    citation
+        .captures_iter(document)
+        .flat_map(|caps| caps[1].split(',').map(str::trim).map(String::from).collect::<Vec<_>>())
+        .filter(|key| !key.is_empty() && key != "*")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Registry, Syntax};
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    #[test]
+    fn test_registry() {
+        let custom = BTreeMap::from([(".mdx".to_string(), Syntax::Markdown), ("txt".to_string(), Syntax::Org)]);
+        let registry = Registry::new(&custom);
+        assert_eq!(registry.get(Path::new("thesis.TEX")), Some(&Syntax::Latex));
+        assert_eq!(registry.get(Path::new("analysis.ipynb")), Some(&Syntax::Notebook));
+        assert_eq!(registry.get(Path::new("draft.mmd")), Some(&Syntax::MultiMarkdown));
+        assert_eq!(registry.get(Path::new("chapter.dj")), Some(&Syntax::Djot));
+        assert_eq!(registry.get(Path::new("build/thesis.aux")), Some(&Syntax::Aux));
+        assert_eq!(registry.get(Path::new("page.mdx")), Some(&Syntax::Markdown));
+        assert_eq!(registry.get(Path::new("notes.txt")), Some(&Syntax::Org));
+        assert_eq!(registry.get(Path::new("-")), None);
+        assert_eq!(Registry::default().get(Path::new("notes.txt")), Some(&Syntax::Markdown));
+    }
+
+    #[test]
+    fn test_syntaxes() {
+        assert_eq!("Typst".parse::<Syntax>(), Ok(Syntax::Typst));
+        assert!("rst".parse::<Syntax>().unwrap_err().contains("markdown, latex, typst, org, asciidoc, notebook, multimarkdown, djot, aux"));
+        assert_eq!("MultiMarkdown".parse::<Syntax>(), Ok(Syntax::MultiMarkdown));
+
+        let latex = "As \\textcite{Smith.2009} shows \\parencite[see][12]{BGH.2024, EuGH.2019}.\n\
+                     % \\cite{Commented.2000}\n50\\% \\cites[3]{A.2020}[5]{B.2021} and \\nocite{*}\n";
+        assert_eq!(Syntax::Latex.cited_keys(latex), vec!["Smith.2009", "BGH.2024", "EuGH.2019", "A.2020", "B.2021"]);
+
+        let typst = "= Intro <intro>\nAs @Smith.2009 shows, see @intro and #cite(<BGH.2024>).\n\
+                     // @Commented.2000\nMail me@example.org, `@Raw.2001`\n";
+        assert_eq!(Syntax::Typst.cited_keys(typst), vec!["Smith.2009", "BGH.2024"]);
+
+        let org = "As [cite/t:@Smith.2009 p. 3;@BGH.2024] and citep:EuGH.2019,A.2020.\n\
+                   # cite:Commented.2000\n#+begin_src elisp\n[cite:@Code.2000]\n#+end_src\ncite:&B.2021;&C.2022\n";
+        assert_eq!(Syntax::Org.cited_keys(org), vec!["Smith.2009", "BGH.2024", "EuGH.2019", "A.2020", "B.2021", "C.2022"]);
+
+        let asciidoc = "As cite:[Smith.2009(12)] and citenp:[BGH.2024, EuGH.2019(Rn. 3)].\n// cite:[Commented.2000]\n\
+                        ////\ncite:[Block.2000]\n////\n";
+        assert_eq!(Syntax::AsciiDoc.cited_keys(asciidoc), vec!["Smith.2009", "BGH.2024", "EuGH.2019"]);
+
+        let notebook = r#"{"cells": [{"cell_type": "markdown", "source": ["As @Smith.2009\n", "shows [@BGH.2024]"]},
+                                     {"cell_type": "code", "source": "df = load('@Code.2000')"},
+                                     {"cell_type": "markdown", "source": "See @EuGH.2019"}]}"#;
+        assert_eq!(Syntax::Notebook.cited_keys(notebook), vec!["Smith.2009", "BGH.2024", "EuGH.2019"]);
+
+        let multimarkdown = "As shown [#Smith.2009] and [p. 23][#BGH.2024], see [Doe's view][#Doe:2011][#EuGH.2019].\n\
+                             A [link][ref], a footnote[^1], `[#Code.2000]` and <!-- [#Commented.2000] -->.\n\n\
+                             [#Smith.2009]: Smith, Adam. *Essays*. 2009.\n";
+        assert_eq!(Syntax::MultiMarkdown.cited_keys(multimarkdown), vec!["Smith.2009", "BGH.2024", "Doe:2011", "EuGH.2019"]);
+
+        let djot = "As @Smith.2009 shows [see @BGH.2024, Rn. 3]{.legal}.[^1]\n\n\
+                    {% [@Commented.2000]\n   still commented %}\nMail me@example.org, `@Code.2000`\n\n\
+                    [^1]: Also @EuGH.2019.\n";
+        assert_eq!(Syntax::Djot.cited_keys(djot), vec!["Smith.2009", "BGH.2024", "EuGH.2019"]);
+
+        let aux = "\\relax\n\\citation{Smith.2009}\n\\citation{BGH.2024,EuGH.2019}\n\\citation{*}\n\
+                   \\abx@aux@cite{A.2020}\n\\abx@aux@cite{0}{B.2021}\n\\abx@aux@segm{0}{0}{B.2021}\n\
+                   \\bibcite{Smith.2009}{1}\n\\@input{chapter1.aux}\n";
+        assert_eq!(Syntax::Aux.cited_keys(aux), vec!["Smith.2009", "BGH.2024", "EuGH.2019", "A.2020", "B.2021"]);
+    }
+
+    #[test]
+    fn test_pandoc_text() {
+        // Cells are paragraphs of their own, code cells aren't text
+        let notebook = r#"{"cells": [{"cell_type": "markdown", "source": "As @A.2020 shows"},
+                                     {"cell_type": "code", "source": "load('@C.2019')"},
+                                     {"cell_type": "markdown", "source": ["and @A.2020 ", "again."]}]}"#;
+        assert_eq!(crate::cluster::repeated_cites(notebook, false).len(), 1);
+        let text = Syntax::Notebook.pandoc_text(notebook).unwrap();
+        assert_eq!(text, "As @A.2020 shows\n\nand @A.2020 again.");
+        assert!(crate::cluster::repeated_cites(&text, false).is_empty());
+
+        let djot = "{% @A.2020\n@A.2020 %} @A.2020.\n";
+        assert_eq!(Syntax::Djot.pandoc_text(djot).unwrap(), "          \n           @A.2020.\n");
+        assert_eq!(Syntax::Markdown.pandoc_text("@A.2020").unwrap(), "@A.2020");
+        assert_eq!(Syntax::Latex.pandoc_text("\\cite{A.2020}"), None);
+    }
+}
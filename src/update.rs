@@ -0,0 +1,118 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+
+use zoterocoverage::network::HttpClient;
+use zoterocoverage::source::NetworkOptions;
+
+// Releases carry one binary per platform, e.g. ZoteroCoverage-x86_64-linux or
+// ZoteroCoverage-x86_64-windows.exe, each with a .sha256 file in sha256sum format
+const RELEASES: &str = "https://api.github.com/repos/bressco/ZoteroCoverage/releases/latest";
+
+// Downloaded binaries larger than this are refused
+const MAX_BINARY_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+pub fn asset_name() -> String {
+    format!("ZoteroCoverage-{}-{}{}", std::env::consts::ARCH, std::env::consts::OS, std::env::consts::EXE_SUFFIX)
+}
+
+// Version numbers of a tag like v1.2.3 or 1.2.3; pre-release suffixes are not compared
+fn version(tag: &str) -> Option<Vec<u64>> {
+    let version = tag.trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn is_newer(tag: &str, current: &str) -> bool {
+    matches!((version(tag), version(current)), (Some(tag), Some(current)) if tag > current)
+}
+
+// The binary matches the first field of the checksum file, which sha256sum writes as
+// "<hex digest>  <file name>"
+fn verify(binary: &[u8], checksum_file: &str) -> Result<(), String> {
+    let expected = checksum_file.split_whitespace().next().ok_or("empty checksum file")?.to_lowercase();
+    let actual: String = Sha256::digest(binary).iter().map(|byte| format!("{:02x}", byte)).collect();
+    if actual != expected {
+        return Err(format!("checksum mismatch: expected {}, downloaded binary has {}", expected, actual));
+    }
+    Ok(())
+}
+
+// Replace the running binary with the latest release if it is newer; with check_only just report.
+// The binary is only replaced after its checksum matched.
+pub fn self_update(network: &NetworkOptions, check_only: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let current = env!("CARGO_PKG_VERSION");
+    let client = HttpClient::new(network)?;
+    let headers = [("Accept", "application/vnd.github+json".to_string()), ("User-Agent", format!("ZoteroCoverage/{}", current))];
+    let release: Release = serde_json::from_str(&client.get(RELEASES, &headers)?.body_mut().read_to_string()?)?;
+    if !is_newer(&release.tag_name, current) {
+        println!("ZoteroCoverage {} is up to date (latest release {})", current, release.tag_name);
+        return Ok(());
+    }
+    if check_only {
+        println!("ZoteroCoverage {} is available (installed {}), run self-update to install it", release.tag_name, current);
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let find = |name: &str| release.assets.iter().find(|asset| asset.name == name);
+    let binary_asset = find(&name).ok_or_else(|| format!("release {} has no binary for this platform ({})", release.tag_name, name))?;
+    let checksum_asset = find(&format!("{}.sha256", name))
+        .ok_or_else(|| format!("release {} has no checksum for {}, not updating", release.tag_name, name))?;
+
+    println!("Downloading {} {}", name, release.tag_name);
+    let checksum = client.get(&checksum_asset.browser_download_url, &headers)?.body_mut().read_to_string()?;
+    let binary = client.get(&binary_asset.browser_download_url, &headers)?
+        .into_body()
+        .into_with_config()
+        .limit(MAX_BINARY_SIZE)
+        .read_to_vec()?;
+    verify(&binary, &checksum)?;
+
+    // Next to the binary, so the replacement stays on one file system
+    let executable = std::env::current_exe()?;
+    let download = executable.with_extension("download");
+    fs::write(&download, &binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&download, fs::Permissions::from_mode(0o755))?;
+    }
+    let replaced = self_replace::self_replace(&download);
+    let _ = fs::remove_file(&download);
+    replaced.map_err(|e| format!("could not replace {}: {}", executable.display(), e))?;
+    println!("Updated ZoteroCoverage {} to {}", current, release.tag_name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_newer, verify};
+
+    #[test]
+    fn test_update_checks() {
+        assert!(is_newer("v0.2.0", "0.1.9"));
+        assert!(is_newer("1.10.0", "1.9.3"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("v0.1.0-rc.1", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+
+        // sha256 of "abc"
+        let checksum = "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD  ZoteroCoverage-x86_64-linux\n";
+        assert!(verify(b"abc", checksum).is_ok());
+        assert!(verify(b"abd", checksum).unwrap_err().contains("checksum mismatch"));
+        assert!(verify(b"abc", "").is_err());
+    }
+}
@@ -0,0 +1,12 @@
+use wasm_bindgen::prelude::*;
+
+use crate::search::occurrences;
+use crate::{check_coverage, report_json};
+
+// Coverage report as JSON ({"schema_version": 1, "cited": [...], "uncited": [...], "unknown": [...],
+// "citations": [{"key", "line", "column", "start", "end"}, ...]}) for use from JavaScript
+#[wasm_bindgen(js_name = checkCoverage)]
+pub fn check_coverage_json(document: &str, bibliography: &str) -> Result<String, JsError> {
+    let report = check_coverage(document, bibliography).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(report_json(&report, Some(&occurrences(document)))?)
+}
@@ -0,0 +1,572 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+use crate::network::{self, HttpClient};
+use crate::source::NetworkOptions;
+
+const API_BASE: &str = "https://api.zotero.org";
+
+// A library on zotero.org, written as users/<id> or groups/<id>
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryRef {
+    pub kind: LibraryKind,
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LibraryKind {
+    User,
+    Group,
+}
+
+impl FromStr for LibraryRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, id) = s
+            .trim_matches('/')
+            .split_once('/')
+            .ok_or_else(|| format!("expected users/<id> or groups/<id>, got {}", s))?;
+        let kind = match kind {
+            "users" | "user" => LibraryKind::User,
+            "groups" | "group" => LibraryKind::Group,
+            _ => return Err(format!("unknown library type {}, expected users or groups", kind)),
+        };
+        let id = id
+            .parse()
+            .map_err(|_| format!("library id must be numeric, got {}", id))?;
+        Ok(LibraryRef { kind, id })
+    }
+}
+
+impl fmt::Display for LibraryRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            LibraryKind::User => write!(f, "users/{}", self.id),
+            LibraryKind::Group => write!(f, "groups/{}", self.id),
+        }
+    }
+}
+
+impl LibraryRef {
+    fn cache_file(&self) -> Option<PathBuf> {
+        let name = format!("{}.json", self.to_string().replace('/', "-"));
+        dirs::cache_dir().map(|dir| dir.join("zoterocoverage").join(name))
+    }
+}
+
+// Item list as stored on disk, together with the library version it was fetched at
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct CachedLibrary {
+    pub version: u64,
+    pub items: Vec<Value>,
+    // Collection names by collection key; missing in caches of earlier versions
+    #[serde(default)]
+    pub collections: BTreeMap<String, String>,
+}
+
+fn read_cache(library: &LibraryRef) -> Option<CachedLibrary> {
+    let content = fs::read_to_string(library.cache_file()?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_cache(library: &LibraryRef, cached: &CachedLibrary) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(path) = library.cache_file() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(cached)?)?;
+    Ok(())
+}
+
+// Maximum page size the API allows
+const PAGE_LIMIT: usize = 100;
+
+fn items_url(library: &LibraryRef, start: usize, since: Option<u64>) -> String {
+    let mut url = format!(
+        "{}/{}/items?format=json&include=data,csljson&start={}&limit={}",
+        API_BASE, library, start, PAGE_LIMIT
+    );
+    if let Some(version) = since {
+        url.push_str(&format!("&since={}", version));
+    }
+    url
+}
+
+enum Page {
+    NotModified,
+    Modified {
+        body: String,
+        version: u64,
+        total: Option<usize>,
+    },
+}
+
+fn header_number<T: FromStr>(response: &ureq::http::Response<ureq::Body>, name: &str) -> Option<T> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+// GET a URL, conditional on the library having changed since `since` if given
+fn get_page(
+    client: &HttpClient,
+    url: &str,
+    api_key: Option<&str>,
+    since: Option<u64>,
+) -> Result<Page, Box<dyn std::error::Error>> {
+    let mut headers = vec![("Zotero-API-Version", "3".to_string())];
+    if let Some(key) = api_key {
+        headers.push(("Zotero-API-Key", key.to_string()));
+    }
+    if let Some(version) = since {
+        headers.push(("If-Modified-Since-Version", version.to_string()));
+    }
+    let mut response = client.get(url, &headers)?;
+
+    if response.status().as_u16() == 304 {
+        return Ok(Page::NotModified);
+    }
+    Ok(Page::Modified {
+        version: header_number(&response, "Last-Modified-Version").unwrap_or(0),
+        total: header_number(&response, "Total-Results"),
+        body: response.body_mut().read_to_string()?,
+    })
+}
+
+// get_page on the blocking thread pool, so several pages load at once
+fn spawn_page(
+    client: &Arc<HttpClient>,
+    url: String,
+    api_key: Option<&str>,
+    since: Option<u64>,
+) -> JoinHandle<Result<Page, String>> {
+    let client = Arc::clone(client);
+    let api_key = api_key.map(str::to_string);
+    tokio::task::spawn_blocking(move || get_page(&client, &url, api_key.as_deref(), since).map_err(|e| e.to_string()))
+}
+
+// Starts of the pages after the first one, which had first_len of total items
+fn page_starts(first_len: usize, total: usize) -> Vec<usize> {
+    match first_len {
+        0 => Vec::new(),
+        step => (step..total).step_by(step).collect(),
+    }
+}
+
+// Length of a page and the total number of items if the API told it
+type PageSize = (usize, Option<usize>);
+
+// Add a page to the items, None if the library has not been modified.
+// Pages of different library versions can't be combined.
+fn add_page(
+    page: Page,
+    library: &LibraryRef,
+    items: &mut Vec<Value>,
+    version: &mut u64,
+) -> Result<Option<PageSize>, Box<dyn std::error::Error>> {
+    let (body, page_version, total) = match page {
+        Page::NotModified => return Ok(None),
+        Page::Modified { body, version, total } => (body, version, total),
+    };
+    let page: Vec<Value> = serde_json::from_str(&body)?;
+    if *version != 0 && page_version != *version {
+        return Err(format!("library {} changed while loading, please retry", library).into());
+    }
+    *version = page_version;
+    let page_len = page.len();
+    items.extend(page);
+    Ok(Some((page_len, total)))
+}
+
+// All items (changed since `since`, if given), following start/limit pagination. The first page
+// tells how many items there are, the remaining pages are then requested concurrently.
+// None if the library has not been modified since `since`.
+async fn request_items(
+    client: &Arc<HttpClient>,
+    library: &LibraryRef,
+    api_key: Option<&str>,
+    since: Option<u64>,
+) -> Result<Option<CachedLibrary>, Box<dyn std::error::Error>> {
+    let mut items: Vec<Value> = Vec::new();
+    let mut version = 0;
+
+    let first = spawn_page(client, items_url(library, 0, since), api_key, since).await??;
+    let Some((mut page_len, total)) = add_page(first, library, &mut items, &mut version)? else {
+        return Ok(None);
+    };
+    match total {
+        Some(total) => {
+            let pages: Vec<_> = page_starts(page_len, total)
+                .into_iter()
+                .map(|start| spawn_page(client, items_url(library, start, since), api_key, since))
+                .collect();
+            // Awaited in order, so the items keep the API's order
+            for page in pages {
+                if add_page(page.await??, library, &mut items, &mut version)?.is_none() {
+                    return Ok(None);
+                }
+            }
+        }
+        // Without a total, page on until a page comes back short
+        None => {
+            while page_len == PAGE_LIMIT {
+                let page = spawn_page(client, items_url(library, items.len(), since), api_key, since).await??;
+                match add_page(page, library, &mut items, &mut version)? {
+                    Some((len, _)) => page_len = len,
+                    None => return Ok(None),
+                }
+            }
+        }
+    }
+
+    Ok(Some(CachedLibrary { version, items, collections: BTreeMap::new() }))
+}
+
+// Names of all collections by key, page by page; there are few compared to items
+async fn request_collections(
+    client: &Arc<HttpClient>,
+    library: &LibraryRef,
+    api_key: Option<&str>,
+) -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+    let mut collections = BTreeMap::new();
+    loop {
+        let url = format!("{}/{}/collections?format=json&start={}&limit={}", API_BASE, library, collections.len(), PAGE_LIMIT);
+        let Page::Modified { body, .. } = spawn_page(client, url, api_key, None).await?? else {
+            break;
+        };
+        let page: Vec<Value> = serde_json::from_str(&body)?;
+        for collection in &page {
+            if let (Some(key), Some(name)) = (collection["key"].as_str(), collection["data"]["name"].as_str()) {
+                collections.insert(key.to_string(), name.to_string());
+            }
+        }
+        if page.len() < PAGE_LIMIT {
+            break;
+        }
+    }
+    Ok(collections)
+}
+
+// Keys of items deleted since `since`
+async fn request_deleted(
+    client: &Arc<HttpClient>,
+    library: &LibraryRef,
+    api_key: Option<&str>,
+    since: u64,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    #[derive(Deserialize)]
+    struct Deleted {
+        #[serde(default)]
+        items: Vec<String>,
+    }
+
+    let url = format!("{}/{}/deleted?since={}", API_BASE, library, since);
+    match spawn_page(client, url, api_key, None).await?? {
+        Page::NotModified => Ok(Vec::new()),
+        Page::Modified { body, .. } => Ok(serde_json::from_str::<Deleted>(&body)?.items),
+    }
+}
+
+// Apply changed and deleted items to a cached item list, items are identified by their "key"
+pub fn merge_items(cached: Vec<Value>, changed: Vec<Value>, deleted: &[String]) -> Vec<Value> {
+    let key = |item: &Value| item["key"].as_str().map(str::to_string);
+    let replaced: HashSet<String> = changed.iter().filter_map(key).collect();
+
+    cached
+        .into_iter()
+        .filter(|item| {
+            key(item).is_none_or(|k| !replaced.contains(&k) && !deleted.contains(&k))
+        })
+        .chain(changed)
+        .collect()
+}
+
+// Bring the cached library up to date, or load it completely if nothing is cached
+async fn sync_library(
+    client: &Arc<HttpClient>,
+    library: &LibraryRef,
+    api_key: Option<&str>,
+    cached: Option<CachedLibrary>,
+) -> Result<(CachedLibrary, bool), Box<dyn std::error::Error>> {
+    let Some(cached) = cached else {
+        let mut fetched = request_items(client, library, api_key, None).await?.unwrap_or(CachedLibrary {
+            version: 0,
+            items: Vec::new(),
+            collections: BTreeMap::new(),
+        });
+        fetched.collections = request_collections(client, library, api_key).await?;
+        return Ok((fetched, true));
+    };
+
+    match request_items(client, library, api_key, Some(cached.version)).await? {
+        // Caches of earlier versions lack the collections
+        None if cached.collections.is_empty() && !cached.items.is_empty() => {
+            let collections = request_collections(client, library, api_key).await?;
+            Ok((CachedLibrary { collections, ..cached }, true))
+        }
+        None => Ok((cached, false)),
+        Some(changed) => {
+            let deleted = request_deleted(client, library, api_key, cached.version).await?;
+            let items = merge_items(cached.items, changed.items, &deleted);
+            let collections = request_collections(client, library, api_key).await?;
+            Ok((CachedLibrary { version: changed.version, items, collections }, true))
+        }
+    }
+}
+
+// fetch_library_async for callers without a runtime
+pub fn fetch_library(
+    library: &LibraryRef,
+    api_key: Option<&str>,
+    network: &NetworkOptions,
+    verbose: bool,
+) -> Result<CachedLibrary, Box<dyn std::error::Error>> {
+    network::runtime(network.jobs)?.block_on(fetch_library_async(library, api_key, network, verbose))
+}
+
+// Fetch all items of a library, falling back to the on-disk cache if the API can't be reached.
+// With a cached copy only items changed since its version are requested.
+// Must run on the runtime from network::runtime (or any multi-threaded tokio runtime).
+pub async fn fetch_library_async(
+    library: &LibraryRef,
+    api_key: Option<&str>,
+    network: &NetworkOptions,
+    verbose: bool,
+) -> Result<CachedLibrary, Box<dyn std::error::Error>> {
+    let client = Arc::new(HttpClient::new(network)?);
+    match sync_library(&client, library, api_key, read_cache(library)).await {
+        Ok((fetched, modified)) => {
+            if verbose {
+                if modified {
+                    println!("Fetched {} items of {} at version {}", fetched.items.len(), library, fetched.version);
+                } else {
+                    println!("Library {} unchanged since version {}, using cache", library, fetched.version);
+                }
+            }
+            if modified && let Err(e) = write_cache(library, &fetched) {
+                eprintln!("Warning: could not cache library {}: {}", library, e);
+            }
+            Ok(fetched)
+        }
+        Err(e) => match read_cache(library) {
+            Some(cached) => {
+                eprintln!(
+                    "Warning: could not reach the Zotero API ({}), using cached library {} at version {}",
+                    e, library, cached.version
+                );
+                Ok(cached)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+// Attachment items by the key of their parent item, as title and contentType like the attachments
+// of Better BibTeX JSON; the files themselves are not downloaded
+pub fn attachments(items: &[Value]) -> BTreeMap<String, Vec<Value>> {
+    let mut attachments: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    for data in items.iter().map(|item| &item["data"]) {
+        if data["itemType"].as_str() != Some("attachment") {
+            continue;
+        }
+        if let Some(parent) = data["parentItem"].as_str() {
+            attachments.entry(parent.to_string()).or_default().push(serde_json::json!({
+                "title": data["title"],
+                "contentType": data["contentType"],
+            }));
+        }
+    }
+    attachments
+}
+
+// Convert an API item (format=json&include=data,csljson) to a CSL-JSON entry, with the names of
+// its collections and its attachments; notes and attachments have no bibliographic data and are skipped
+pub fn item_to_csl(
+    item: &Value,
+    collections: &BTreeMap<String, String>,
+    attachments: &BTreeMap<String, Vec<Value>>,
+) -> Option<Value> {
+    let data = &item["data"];
+    if matches!(
+        data["itemType"].as_str(),
+        Some("note") | Some("attachment") | Some("annotation")
+    ) {
+        return None;
+    }
+
+    let mut csl = item.get("csljson")?.clone();
+    let entry = csl.as_object_mut()?;
+    if !entry.contains_key("citation-key")
+        && let Some(key) = data["citationKey"].as_str().filter(|k| !k.is_empty())
+    {
+        entry.insert("citation-key".to_string(), Value::from(key));
+    }
+    // Not part of CSL-JSON, but needed for --added-within; ignored by other CSL consumers
+    if let Some(added) = data["dateAdded"].as_str() {
+        entry.insert("dateAdded".to_string(), Value::from(added));
+    }
+    // Like in Better BibTeX JSON, see better_bibtex::normalize
+    let names: Vec<Value> = data["collections"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|key| collections.get(key.as_str()?))
+        .map(|name| Value::from(name.as_str()))
+        .collect();
+    if !names.is_empty() {
+        entry.insert("collections".to_string(), Value::from(names));
+    }
+    if let Some(files) = item["key"].as_str().and_then(|key| attachments.get(key)) {
+        entry.insert("attachments".to_string(), Value::from(files.clone()));
+    }
+    Some(csl)
+}
+
+// The citation key of an API item: the citationKey field, or a Citation Key: line in extra as
+// Better BibTeX writes it for Zotero versions without the field
+fn citation_key(item: &Value) -> Option<&str> {
+    let data = &item["data"];
+    data["citationKey"].as_str().filter(|key| !key.is_empty()).or_else(|| crate::extra_key(data["extra"].as_str()?))
+}
+
+// Zotero's local API (Zotero 7, enable it in the settings under Advanced), which unlike the Web API
+// runs saved searches
+pub mod local_api {
+    use serde_json::Value;
+    use std::collections::HashSet;
+
+    use super::{PAGE_LIMIT, citation_key};
+    use crate::network::HttpClient;
+    use crate::source::NetworkOptions;
+
+    const BASE_URL: &str = "http://127.0.0.1:23119/api/users/0";
+
+    fn get_json(client: &HttpClient, url: &str) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+        let mut response = client
+            .get(url, &[("Zotero-API-Version", "3".to_string())])
+            .map_err(|e| format!("could not reach the local Zotero API, is Zotero running with it enabled? {}", e))?;
+        Ok(serde_json::from_str(&response.body_mut().read_to_string()?)?)
+    }
+
+    // The citation keys of the items found by the saved search with this name
+    pub fn saved_search_keys(name: &str, options: &NetworkOptions) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+        let client = HttpClient::local(options);
+        let searches = get_json(&client, &format!("{}/searches", BASE_URL))?;
+        let Some(search) = searches.iter().find(|search| search["data"]["name"] == name) else {
+            let names: Vec<&str> = searches.iter().filter_map(|search| search["data"]["name"].as_str()).collect();
+            return Err(format!("unknown saved search {}, saved searches: {}", name, names.join(", ")).into());
+        };
+        let key = search["key"].as_str().ok_or("saved search without key")?;
+
+        let mut keys = HashSet::new();
+        let mut start = 0;
+        loop {
+            let url = format!("{}/searches/{}/items?format=json&start={}&limit={}", BASE_URL, key, start, PAGE_LIMIT);
+            let page = get_json(&client, &url)?;
+            keys.extend(page.iter().filter_map(citation_key).map(str::to_string));
+            if page.len() < PAGE_LIMIT {
+                return Ok(keys);
+            }
+            start += page.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LibraryKind, LibraryRef, attachments, citation_key, item_to_csl, items_url, merge_items, page_starts};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_library_ref_from_str() {
+        assert_eq!(
+            "users/12345".parse::<LibraryRef>().unwrap(),
+            LibraryRef { kind: LibraryKind::User, id: 12345 }
+        );
+        assert_eq!("groups/42".parse::<LibraryRef>().unwrap().to_string(), "groups/42");
+        assert!("users/abc".parse::<LibraryRef>().is_err());
+        assert!("collections/1".parse::<LibraryRef>().is_err());
+        assert!("12345".parse::<LibraryRef>().is_err());
+    }
+
+    #[test]
+    fn test_item_to_csl() {
+        let item = json!({
+            "key": "ABCD1234",
+            "data": {"itemType": "book", "citationKey": "Alexander.2024", "dateAdded": "2024-03-04T10:00:00Z",
+                     "collections": ["C1", "GONE"]},
+            "csljson": {"id": "12345/ABCD1234", "type": "book", "title": "UWG"}
+        });
+        let collections = BTreeMap::from([("C1".to_string(), "Literatur KI-VO".to_string())]);
+        let pdf = json!({"key": "PDF1", "data": {"itemType": "attachment", "parentItem": "ABCD1234",
+                                                 "title": "Full Text PDF", "contentType": "application/pdf"}});
+        let files = attachments(&[item.clone(), pdf.clone()]);
+        let csl = item_to_csl(&item, &collections, &files).unwrap();
+        assert_eq!(csl["citation-key"], "Alexander.2024");
+        assert_eq!(csl["title"], "UWG");
+        assert_eq!(csl["dateAdded"], "2024-03-04T10:00:00Z");
+        assert_eq!(csl["collections"], json!(["Literatur KI-VO"]));
+        assert_eq!(csl["attachments"], json!([{"title": "Full Text PDF", "contentType": "application/pdf"}]));
+
+        let note = json!({"data": {"itemType": "note"}, "csljson": {}});
+        assert_eq!(item_to_csl(&note, &collections, &files), None);
+        assert_eq!(item_to_csl(&pdf, &collections, &files), None);
+    }
+
+    #[test]
+    fn test_citation_key() {
+        assert_eq!(citation_key(&json!({"data": {"citationKey": "Smith.2009"}})), Some("Smith.2009"));
+        let extra = json!({"data": {"citationKey": "", "extra": "tex.note: x\ncitation key:  Jones.2010"}});
+        assert_eq!(citation_key(&extra), Some("Jones.2010"));
+        assert_eq!(citation_key(&json!({"data": {"itemType": "note"}})), None);
+    }
+
+    #[test]
+    fn test_items_url() {
+        let library = LibraryRef { kind: LibraryKind::User, id: 1 };
+        assert_eq!(
+            items_url(&library, 200, Some(42)),
+            "https://api.zotero.org/users/1/items?format=json&include=data,csljson&start=200&limit=100&since=42"
+        );
+        assert!(!items_url(&library, 0, None).contains("since"));
+    }
+
+    #[test]
+    fn test_page_starts() {
+        assert_eq!(page_starts(100, 350), vec![100, 200, 300]);
+        assert_eq!(page_starts(100, 100), Vec::<usize>::new());
+        assert_eq!(page_starts(0, 350), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_merge_items() {
+        let cached = vec![
+            json!({"key": "A", "version": 1}),
+            json!({"key": "B", "version": 1}),
+            json!({"key": "C", "version": 1}),
+        ];
+        let changed = vec![json!({"key": "B", "version": 5}), json!({"key": "D", "version": 5})];
+        let merged = merge_items(cached, changed, &["C".to_string()]);
+        assert_eq!(
+            merged,
+            vec![
+                json!({"key": "A", "version": 1}),
+                json!({"key": "B", "version": 5}),
+                json!({"key": "D", "version": 5}),
+            ]
+        );
+    }
+}